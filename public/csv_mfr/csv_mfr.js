@@ -106,10 +106,40 @@ const map_signature = "function do_map (value, index, array) {"
 const filter_signature = "function do_filter (value, index, array) {"
 const reduce_signature = "function do_reduce (previousValue, currentValue, currentIndex, array) {"
 
+/**
+ * A loaded pipeline is code someone else wrote, so nothing runs until the user pressed Run pipeline.
+ * @type {boolean}
+ */
+let pipeline_paused = false;
+
+/**
+ * @param {boolean} paused
+ * @returns {void}
+ */
+function set_pipeline_paused(paused) {
+	pipeline_paused = paused;
+	const note = get_el("#pipeline_paused_note");
+	if (note) {
+		note.hidden = !paused;
+	}
+}
+
+/**
+ * @returns {void}
+ */
+function run_pipeline() {
+	set_pipeline_paused(false);
+	update_output();
+}
+
 /**
  * @returns {void}
  */
 function update_output() {
+	if (pipeline_paused) {
+		return;
+	}
+
 	read_csv_from_input("#main_file_input", content => {
 		raw_content = content;
 		const detect_instant = new_instant();
@@ -290,3 +320,104 @@ function download(file_name, content) {
 }
 
 
+
+/**
+ * @returns {Promise<void>}
+ */
+async function update_saved_pipelines() {
+	const select = get_el("#saved_pipelines_select");
+	if (!select) {
+		console.error("Could not find saved_pipelines_select");
+		return;
+	}
+
+	/** @type {string[]} */
+	const names = await fetch("/csv_mfr/pipelines")
+		.then(response => response.json())
+		.catch(error => {
+			console.error("Could not fetch the saved pipelines", error);
+			return [];
+		});
+
+	select.replaceChildren(...names.map(name => new Option(name, name)));
+}
+
+/**
+ * @returns {Promise<void>}
+ */
+async function save_pipeline() {
+	const name = get_el("#pipeline_name_input")?.value.trim();
+	if (!name) {
+		alert("Name the pipeline to save it.");
+		return;
+	}
+
+	const stages = [...document.querySelectorAll(".stage_section")].map(stage => ({
+		kind: stage.querySelector(".stage_type_select").value,
+		body: stage.querySelector(".stage_body").value,
+	}));
+
+	const response = await fetch("/csv_mfr/pipelines", {
+		method: "PUT",
+		headers: { "Content-Type": "application/json" },
+		body: JSON.stringify({ name, stages }),
+	});
+	if (!response.ok) {
+		alert(await response.text());
+		return;
+	}
+
+	await update_saved_pipelines();
+	get_el("#saved_pipelines_select").value = name;
+}
+
+/**
+ * @returns {Promise<void>}
+ */
+async function load_pipeline() {
+	const name = get_el("#saved_pipelines_select")?.value;
+	if (!name) {
+		return;
+	}
+
+	const response = await fetch(`/csv_mfr/pipelines/${encodeURIComponent(name)}`);
+	if (!response.ok) {
+		alert(await response.text());
+		return;
+	}
+
+	/** @type {{name: string, stages: {kind: string, body: string}[]}} */
+	const pipeline = await response.json();
+
+	set_pipeline_paused(true);
+	const pipeline_section = document.querySelector("#pipeline_section");
+	pipeline_section.replaceChildren();
+	for (let i = 0; i < pipeline.stages.length; i++) {
+		await add_stage(i);
+		const section = pipeline_section.children[i];
+		section.querySelector(".stage_type_select").value = pipeline.stages[i].kind;
+		section.querySelector(".stage_body").value = pipeline.stages[i].body;
+	}
+
+	get_el("#pipeline_name_input").value = pipeline.name;
+	update_stage_signatures();
+}
+
+/**
+ * @returns {Promise<void>}
+ */
+async function delete_pipeline() {
+	const name = get_el("#saved_pipelines_select")?.value;
+	if (!name || !confirm(`Delete the pipeline ${name}?`)) {
+		return;
+	}
+
+	const response = await fetch(`/csv_mfr/pipelines/${encodeURIComponent(name)}`, { method: "DELETE" });
+	if (!response.ok) {
+		alert(await response.text());
+	}
+
+	await update_saved_pipelines();
+}
+
+document.addEventListener("DOMContentLoaded", update_saved_pipelines);
@@ -0,0 +1,158 @@
+// Keeps the game on the page up to date with the moves of the other players.
+//
+// The server sends the revision of the game whenever it changes, and once right after connecting.
+// A revision newer than the one on the page reloads the game, so missed updates are caught up
+// with after a dropped connection. Spectators coming and going only change the count on the page. The game element says where its events and updates come
+// from, which differs for players and spectators.
+//
+// On the game page, the address names the game, so it can be shared with the other players and
+// reloading the page continues the game.
+//
+// The server keeps the time of games with a clock. The page only counts the running clock down
+// from the time the server sent, and asks the server for the game when it reaches zero, which
+// ends the game when the team really ran out of time.
+
+const RECONNECT_DELAY = 3000;
+const CLOCK_TICK = 100;
+
+/**
+* The game the clocks on the page came with, when it arrived, and whether they ran out since.
+* @type {{game: Element | null, since: number, flagged: boolean}}
+*/
+let clocks = { game: null, since: 0, flagged: false };
+
+/** @type {{events_url: string, source: EventSource} | null} */
+let game_events = null;
+
+/** @returns {Element | null} */
+function game_element() {
+	return document.getElementById("hexy_game");
+}
+
+/**
+* @param {string} events_url
+*/
+function watch_game(events_url) {
+	if (game_events) {
+		game_events.source.close();
+	}
+
+	const source = new EventSource(events_url);
+	game_events = { events_url, source };
+
+	source.addEventListener("update", event => {
+		const game = game_element();
+		if (!game || game.getAttribute("events-url") !== events_url) {
+			return;
+		}
+
+		if (Number(event.data) > Number(game.getAttribute("revision"))) {
+			htmx.ajax("GET", game.getAttribute("refresh-url"), { target: "#hexy_game", swap: "outerHTML" });
+		}
+	});
+
+	source.addEventListener("spectators", event => {
+		const game = game_element();
+		const spectators = game?.querySelector(".hexy_spectators");
+		if (!spectators || game.getAttribute("events-url") !== events_url) {
+			return;
+		}
+
+		spectators.textContent = `${event.data} watching`;
+		spectators.hidden = Number(event.data) === 0;
+	});
+
+	// The browser retries by itself, unless the server refused the stream.
+	source.addEventListener("error", () => {
+		if (source.readyState === EventSource.CLOSED && game_events && game_events.source === source) {
+			setTimeout(() => {
+				if (game_events && game_events.source === source) {
+					watch_game(events_url);
+				}
+			}, RECONNECT_DELAY);
+		}
+	});
+}
+
+/**
+* Writes a time like the server does, like 4:05, with tenths of a second in the last ten seconds.
+* @param {number} ms
+*/
+function clock_text(ms) {
+	const seconds = Math.floor(ms / 1000);
+	if (seconds < 10) {
+		return `0:0${seconds}.${Math.floor(ms % 1000 / 100)}`;
+	}
+
+	return `${Math.floor(seconds / 60)}:${String(seconds % 60).padStart(2, "0")}`;
+}
+
+function tick_clocks() {
+	const game = game_element();
+	const clock = game ? game.querySelector(".hexy_clock.running") : null;
+	if (!clock) {
+		return;
+	}
+
+	// Every update of the game replaces its element, with the clocks as they are now.
+	if (clocks.game !== game) {
+		clocks = { game, since: performance.now(), flagged: false };
+	}
+
+	const delay = Number(clock.getAttribute("delay-ms"));
+	const used = Math.max(0, performance.now() - clocks.since - delay);
+	const remaining = Math.max(0, Number(clock.getAttribute("remaining-ms")) - used);
+	clock.textContent = clock_text(remaining);
+
+	if (remaining === 0 && !clocks.flagged) {
+		clocks.flagged = true;
+		htmx.ajax("GET", game.getAttribute("refresh-url"), { target: "#hexy_game", swap: "outerHTML" });
+	}
+}
+
+setInterval(tick_clocks, CLOCK_TICK);
+
+function new_game() {
+	htmx.ajax("POST", "/hexy/games", { target: "#hexy_game", swap: "outerHTML", values: { size: 7, teams: 2 } });
+}
+
+/** Shows the game from the address of the page, or a new game when there is none. */
+function load_game() {
+	const game = game_element();
+	if (!game) {
+		return;
+	}
+
+	// The page came with its game, like the page for spectators.
+	if (game.getAttribute("events-url")) {
+		watch_game(game.getAttribute("events-url"));
+		return;
+	}
+
+	const game_id = new URLSearchParams(location.search).get("game");
+	if (!game_id) {
+		new_game();
+		return;
+	}
+
+	htmx.ajax("GET", `/hexy/games/${game_id}`, { target: "#hexy_game", swap: "outerHTML" }).then(() => {
+		// The game expired, or the address was wrong.
+		if (!game_element().getAttribute("game-id")) {
+			new_game();
+		}
+	});
+}
+
+document.addEventListener("DOMContentLoaded", load_game);
+
+document.addEventListener("htmx:afterSettle", () => {
+	const game = game_element();
+	const events_url = game ? game.getAttribute("events-url") : null;
+
+	if (events_url && (!game_events || game_events.events_url !== events_url)) {
+		if (location.pathname === "/hexy/") {
+			history.replaceState(null, "", `?game=${game.getAttribute("game-id")}`);
+		}
+		watch_game(events_url);
+	}
+});
@@ -0,0 +1,195 @@
+//! The built-in Hexy engines as a program that speaks the engine protocol on stdin and stdout,
+//! see `yground::hexy::engine::protocol`. The argument picks the engine, `alpha-beta` when
+//! there is none:
+//!
+//! ```text
+//! hexy-engine mcts
+//! ```
+//!
+//! `hexy-engine tournament` plays engines against each other instead, see `TOURNAMENT_USAGE`.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use yground::hexy::engine::external::ExternalEngines;
+use yground::hexy::engine::protocol::serve;
+use yground::hexy::engine::EngineKind;
+use yground::hexy::record::result_text;
+use yground::hexy::tournament::{
+    standings, Entrant, Format, ResultsTable, Tournament, TournamentSettings,
+};
+
+const TOURNAMENT_USAGE: &str = "\
+Usage: hexy-engine tournament [options] <engine> <engine>...
+
+An engine is alpha-beta, mcts, or name=command for a program that speaks the engine protocol,
+like new=\"target/release/hexy-engine mcts\".
+
+Options:
+  --gauntlet           the first engine plays each of the others, instead of everyone playing everyone
+  --sizes 5,7          the board sizes to play on
+  --openings N         the starting positions for each size, the first is the standard one
+  --opening-plies N    the random moves the other starting positions start with
+  --movetime MS        the time for every move, in milliseconds
+  --depth N            the depth of every alpha-beta search
+  --iterations N       the iterations of every Monte Carlo search
+  --max-plies N        games that go on longer are a draw
+  --threads N          the games played at the same time
+  --seed N             changes the random starting positions and engine choices
+  --records DIR        writes the record of every game to DIR";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let name = args.next().unwrap_or_else(|| "alpha-beta".into());
+
+    let result = match name.as_str() {
+        "tournament" => run_tournament(args.collect()),
+        _ => serve_engine(&name),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn serve_engine(name: &str) -> Result<(), String> {
+    let kind = EngineKind::from_name(name, &ExternalEngines::default())
+        .ok_or_else(|| format!("Unknown engine {name}, expected alpha-beta or mcts."))?;
+
+    let mut seed = 0;
+    serve(
+        &format!("Hexy {name}"),
+        io::stdin().lock(),
+        io::stdout(),
+        |limits| {
+            seed += 1;
+            kind.create(limits, seed)
+        },
+    )
+    .map_err(|error| error.to_string())
+}
+
+fn run_tournament(args: Vec<String>) -> Result<(), String> {
+    let (tournament, records) = parse_tournament(args)?;
+    if let Some(records) = &records {
+        fs::create_dir_all(records).map_err(|error| error.to_string())?;
+    }
+
+    let games = tournament.fixtures()?.len();
+    let mut failed_writes = Vec::new();
+    let results = tournament.run(|result| {
+        let fixture = &result.fixture;
+        println!(
+            "Game {}/{games}: {} - {}, size {}, opening {}: {} after {} moves{}",
+            result.index + 1,
+            tournament.entrants[fixture.first].name,
+            tournament.entrants[fixture.second].name,
+            fixture.size,
+            fixture.opening,
+            result_text(result.record.result),
+            result.record.moves.len(),
+            result
+                .record
+                .header("Termination")
+                .map(|termination| format!(" ({termination})"))
+                .unwrap_or_default(),
+        );
+
+        if let Some(records) = &records {
+            let path = records.join(format!("game-{:04}.hexy", result.index + 1));
+            if let Err(error) = fs::write(&path, result.record.to_text()) {
+                failed_writes.push(format!("{}: {error}", path.display()));
+            }
+        }
+    })?;
+
+    let standings = standings(tournament.entrants.len(), &results);
+    println!();
+    print!(
+        "{}",
+        ResultsTable {
+            entrants: &tournament.entrants,
+            standings: &standings,
+        }
+    );
+
+    match failed_writes.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "Could not write records:\n{}",
+            failed_writes.join("\n")
+        )),
+    }
+}
+
+fn parse_tournament(args: Vec<String>) -> Result<(Tournament, Option<PathBuf>), String> {
+    let mut settings = TournamentSettings::default();
+    let mut entrants = Vec::new();
+    let mut records = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{arg} needs a value.\n\n{TOURNAMENT_USAGE}"))
+        };
+
+        match arg.as_str() {
+            "--help" | "-h" => return Err(TOURNAMENT_USAGE.into()),
+            "--gauntlet" => settings.format = Format::Gauntlet,
+            "--sizes" => {
+                settings.sizes = value()?
+                    .split(',')
+                    .map(|size| number(&arg, size))
+                    .collect::<Result<_, _>>()?
+            }
+            "--openings" => settings.openings = number(&arg, &value()?)?,
+            "--opening-plies" => settings.opening_plies = number(&arg, &value()?)?,
+            "--movetime" => {
+                settings.limits.movetime = Some(Duration::from_millis(number(&arg, &value()?)?))
+            }
+            "--depth" => settings.limits.depth = Some(number(&arg, &value()?)?),
+            "--iterations" => settings.limits.iterations = Some(number(&arg, &value()?)?),
+            "--max-plies" => settings.max_plies = number(&arg, &value()?)?,
+            "--threads" => settings.threads = number(&arg, &value()?)?,
+            "--seed" => settings.seed = number(&arg, &value()?)?,
+            "--records" => records = Some(PathBuf::from(value()?)),
+            option if option.starts_with("--") => {
+                return Err(format!("Unknown option {option}.\n\n{TOURNAMENT_USAGE}"))
+            }
+            engine => entrants.push(parse_entrant(engine)?),
+        }
+    }
+
+    Ok((Tournament::new(entrants, settings), records))
+}
+
+fn number<T: std::str::FromStr>(option: &str, text: &str) -> Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("{option} expects a number, not {text}."))
+}
+
+fn parse_entrant(text: &str) -> Result<Entrant, String> {
+    let engine = match text.split_once('=') {
+        Some((name, command)) => EngineKind::External {
+            name: name.to_string(),
+            command: command.to_string(),
+        },
+        None => EngineKind::from_name(text, &ExternalEngines::default()).ok_or_else(|| {
+            format!("Unknown engine {text}, expected alpha-beta, mcts or name=command.")
+        })?,
+    };
+
+    Ok(Entrant {
+        name: engine.name().to_string(),
+        engine,
+    })
+}
@@ -1,17 +1,31 @@
 use std::path::Path;
 
+use rocket::delete;
 use rocket::fs::relative;
 use rocket::fs::NamedFile;
 use rocket::get;
+use rocket::http::Status;
+use rocket::put;
 use rocket::response::content::RawHtml;
 use rocket::routes;
+use rocket::serde::json::Json;
 use rocket::Route;
+use rocket::State;
 use tera::Context;
 
+use crate::hexy::lobby::Player;
+use crate::storage::{Pipeline, SharedStore};
 use crate::TEMPLATES;
 
 pub fn get_routes() -> Vec<Route> {
-    routes![index, get_pipeline_stage]
+    routes![
+        index,
+        get_pipeline_stage,
+        get_pipelines,
+        get_pipeline,
+        save_pipeline,
+        delete_pipeline
+    ]
 }
 
 #[get("/")]
@@ -24,9 +38,96 @@ pub async fn index() -> Option<NamedFile> {
 pub async fn get_pipeline_stage() -> Option<RawHtml<String>> {
     let result = TEMPLATES
         .render("/templates/csv_mfr/pipeline_stage.html", &Context::default())
-        .ok()
-        .map(|value| (value))
-        .unwrap_or(String::new());
+        .unwrap_or_default();
 
     Some(RawHtml(result))
 }
+
+/// The names of the saved pipelines.
+#[get("/pipelines")]
+pub async fn get_pipelines(
+    store: &State<SharedStore>,
+) -> Result<Json<Vec<String>>, (Status, String)> {
+    let pipelines = store.pipelines().map_err(internal_error)?;
+
+    Ok(Json(
+        pipelines
+            .into_iter()
+            .map(|pipeline| pipeline.name)
+            .collect(),
+    ))
+}
+
+#[get("/pipelines/<name>")]
+pub async fn get_pipeline(
+    name: &str,
+    store: &State<SharedStore>,
+) -> Result<Json<Pipeline>, (Status, String)> {
+    store
+        .pipeline(name)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                Status::NotFound,
+                format!("There is no pipeline called {name}."),
+            )
+        })
+}
+
+/// Saves a pipeline, replacing the one with the same name when the browser saved that one.
+#[put("/pipelines", data = "<pipeline>")]
+pub async fn save_pipeline(
+    pipeline: Json<Pipeline>,
+    player: Player,
+    store: &State<SharedStore>,
+) -> Result<Status, (Status, String)> {
+    let mut pipeline = pipeline.into_inner();
+    pipeline.name = pipeline.name.trim().to_string();
+    if pipeline.name.is_empty() {
+        return Err((Status::BadRequest, "A pipeline needs a name.".into()));
+    }
+
+    check_owner(&pipeline.name, &player, store)?;
+    pipeline.owner = Some(player.id);
+    store.save_pipeline(&pipeline).map_err(internal_error)?;
+
+    Ok(Status::NoContent)
+}
+
+#[delete("/pipelines/<name>")]
+pub async fn delete_pipeline(
+    name: &str,
+    player: Player,
+    store: &State<SharedStore>,
+) -> Result<Status, (Status, String)> {
+    check_owner(name, &player, store)?;
+    match store.delete_pipeline(name).map_err(internal_error)? {
+        true => Ok(Status::NoContent),
+        false => Err((
+            Status::NotFound,
+            format!("There is no pipeline called {name}."),
+        )),
+    }
+}
+
+/// Fails when another browser saved the pipeline with the name. Pipelines from before there
+/// were owners go to whoever saves them first.
+fn check_owner(name: &str, player: &Player, store: &SharedStore) -> Result<(), (Status, String)> {
+    let owner = store
+        .pipeline(name)
+        .map_err(internal_error)?
+        .and_then(|pipeline| pipeline.owner);
+
+    match owner.is_none_or(|owner| owner == player.id) {
+        true => Ok(()),
+        false => Err((
+            Status::Forbidden,
+            format!("The pipeline {name} belongs to someone else, save it under another name."),
+        )),
+    }
+}
+
+fn internal_error(error: impl ToString) -> (Status, String) {
+    (Status::InternalServerError, error.to_string())
+}
@@ -1,22 +1,66 @@
-mod hex_board;
-mod hexagon;
+pub mod clock;
+pub mod editor;
+pub mod engine;
+pub mod game;
+pub mod geometry;
+pub mod hex_board;
+pub mod hexagon;
+pub mod layout;
+pub mod lobby;
+pub mod moves;
+pub mod notation;
+pub mod picture;
+pub mod rating;
+pub mod record;
+pub mod replay;
+pub mod shape;
+pub mod svg;
+pub mod tournament;
+pub mod zobrist;
 
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
 
-use hex_board::HexBoard;
+use editor::{editor_html, reshape, EditorForm, EDITOR_SIZE};
+use engine::external::ExternalEngines;
+use engine::EngineKind;
+use game::{Computer, Game, GameId, GameSettings, GameUpdate, Games, Spectator, COMPUTER_TIME};
+use hex_board::{BoardView, HexBoard, Renderer};
+use hexagon::HexId;
+use lobby::{clean_name, Player, TimeControl};
+use picture::PictureOptions;
+use rating::{PlayerRating, RatingChange};
+use record::GameRecord;
+use replay::ReplayOptions;
+use rocket::delete;
+use rocket::form::Form;
 use rocket::fs::relative;
 use rocket::fs::NamedFile;
 use rocket::get;
+use rocket::http::ContentType;
+use rocket::http::CookieJar;
+use rocket::http::Header;
+use rocket::http::Status;
+use rocket::post;
 use rocket::response::content::RawHtml;
+use rocket::response::status::BadRequest;
+use rocket::response::stream::{Event, EventStream};
 use rocket::routes;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::FromForm;
+use rocket::Responder;
 use rocket::Route;
+use rocket::Shutdown;
+use rocket::State;
+use serde::Serialize;
+use shape::BoardShape;
+use tera::Context;
 
-use lazy_static::lazy_static;
-
-lazy_static! {
-    pub static ref BOARD: Option<HexBoard> = None;
-}
-
+use crate::storage::SharedStore;
+use crate::util::get_template;
 
 #[get("/")]
 pub async fn index() -> Option<NamedFile> {
@@ -24,17 +68,1120 @@ pub async fn index() -> Option<NamedFile> {
     NamedFile::open(path).await.ok()
 }
 
-#[get("/get_board?<size>")]
-pub async fn get_board(size: u32) -> Option<RawHtml<String>> {
-    if size == 0 {
-        return None;
+/// Renders an empty board of the given size or shape, like `r11` for a rhombus, or the position
+/// in `notation` when it is given. The `renderer` is `html`, `svg` or `svg-pointy`.
+#[get("/get_board?<size>&<shape>&<notation>&<renderer>")]
+pub async fn get_board(
+    size: Option<u32>,
+    shape: Option<&str>,
+    notation: Option<&str>,
+    renderer: Option<&str>,
+) -> Result<RawHtml<String>, BadRequest<String>> {
+    let renderer = match renderer {
+        Some(name) => Renderer::from_name(name)
+            .ok_or_else(|| BadRequest(format!("There is no renderer called {name}.")))?,
+        None => Renderer::default(),
+    };
+    let board = match (notation, shape, size) {
+        (Some(notation), _, _) => {
+            HexBoard::from_notation(notation).map_err(|error| BadRequest(error.to_string()))?
+        }
+        (None, Some(shape), _) => HexBoard::with_shape(
+            BoardShape::from_notation(shape).map_err(|error| BadRequest(error.to_string()))?,
+        ),
+        (None, None, Some(size)) if size > 0 => HexBoard::new(size),
+        _ => return Err(BadRequest("Expected a size, a shape or a notation.".into())),
+    };
+
+    let view = BoardView {
+        renderer,
+        ..Default::default()
+    };
+
+    Ok(RawHtml(board.render(&view)))
+}
+
+/// A PNG picture of the position in `notation`, with the hexes in `highlight` marked like the
+/// hexes a piece can move to.
+#[get("/picture?<notation>&<highlight>&<options..>")]
+pub async fn get_picture(
+    notation: &str,
+    highlight: Vec<&str>,
+    options: PictureOptions,
+) -> Result<(ContentType, Vec<u8>), BadRequest<String>> {
+    let board = HexBoard::from_notation(notation).map_err(|error| BadRequest(error.to_string()))?;
+    let targets = highlight
+        .into_iter()
+        .map(|hex| {
+            HexId::from_notation(hex).ok_or_else(|| BadRequest(format!("{hex} is not a hex.")))
+        })
+        .collect::<Result<_, _>>()?;
+    let view = BoardView {
+        targets,
+        ..Default::default()
+    };
+    let png = draw_png(board, view, options).await.map_err(BadRequest)?;
+
+    Ok((ContentType::PNG, png))
+}
+
+/// Starts a new game from the standard starting position and renders it.
+#[post("/games", data = "<settings>")]
+pub async fn create_game(
+    settings: Form<GameSettings>,
+    player: Player,
+    games: &State<Games>,
+    engines: &State<ExternalEngines>,
+) -> Result<RawHtml<String>, BadRequest<String>> {
+    let game = start_game(settings.into_inner(), games, engines)?;
+    let html = Game::lock(&game).to_html(&player, None);
+
+    Ok(RawHtml(html))
+}
+
+fn start_game(
+    settings: GameSettings,
+    games: &Games,
+    engines: &ExternalEngines,
+) -> Result<Arc<Mutex<Game>>, BadRequest<String>> {
+    let GameSettings {
+        size,
+        teams,
+        computer,
+        engine,
+        variant,
+        minutes,
+        increment,
+        delay,
+    } = settings;
+    let board = moves::starting_position(size, teams).ok_or_else(|| {
+        BadRequest(format!(
+            "A board of size {size} has no room for {teams} teams."
+        ))
+    })?;
+    let engine = EngineKind::from_name(&engine, engines)
+        .ok_or_else(|| BadRequest(format!("There is no engine called {engine}.")))?;
+
+    let game = games.create(board);
+    {
+        let mut game = Game::lock(&game);
+        game.variant = variant;
+        game.set_time_control(TimeControl::new(minutes, increment, delay));
+        if computer {
+            game.computer = Some(Computer {
+                teams: (1..teams).collect(),
+                engine,
+                time: COMPUTER_TIME,
+            });
+        }
+        game.save();
+    }
+
+    Ok(game)
+}
+
+#[get("/games/<id>")]
+pub async fn get_game(
+    id: GameId,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let html = Game::lock(&game).to_html(&player, None);
+
+    Ok(RawHtml(html))
+}
+
+#[get("/games/<id>/notation")]
+pub async fn get_notation(id: GameId, games: &State<Games>) -> Result<String, (Status, String)> {
+    let game = find_game(id, games)?;
+    let notation = Game::lock(&game).board.to_notation();
+
+    Ok(notation)
+}
+
+/// Plays a move for the side to move, `from` and `to` are hexes like `d2`.
+#[post("/games/<id>/move?<from>&<to>")]
+pub async fn make_move(
+    id: GameId,
+    from: &str,
+    to: &str,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    {
+        let mut game = Game::lock(&game);
+        game.check_turn(player.id)
+            .map_err(|message| (Status::Forbidden, message))?;
+        if game.status() != moves::GameStatus::Ongoing {
+            return Err((Status::BadRequest, "The game is over.".into()));
+        }
+
+        let (Some(from), Some(to)) = (HexId::from_notation(from), HexId::from_notation(to)) else {
+            return Err((Status::BadRequest, "Expected hexes like d2.".into()));
+        };
+        let mv = moves::find_move(&game.board, &from, &to).ok_or_else(|| {
+            (
+                Status::BadRequest,
+                format!(
+                    "{}-{} is not a legal move.",
+                    from.to_notation(),
+                    to.to_notation()
+                ),
+            )
+        })?;
+
+        game.play(&mv);
+        game.changed();
+    }
+
+    let played = play_computer_moves(&game).await;
+
+    let html = render_played(&game, games, &player, played.err().as_deref());
+
+    Ok(RawHtml(html))
+}
+
+/// Selects a piece or moves the selected piece, see `Game::click`.
+/// A click that does nothing is not an error, the rendered game explains why instead.
+#[post("/games/<id>/click?<hex>")]
+pub async fn click(
+    id: GameId,
+    hex: &str,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let result = match HexId::from_notation(hex) {
+        Some(hex_id) => {
+            let mut game = Game::lock(&game);
+            let result = game.check_turn(player.id).and_then(|_| game.click(&hex_id));
+            if result.is_ok() {
+                game.changed();
+            }
+            result
+        }
+        None => Err(format!("{hex} is not a hex.")),
+    };
+
+    let result = result.and(play_computer_moves(&game).await);
+
+    let html = render_played(&game, games, &player, result.err().as_deref());
+
+    Ok(RawHtml(html))
+}
+
+#[post("/games/<id>/undo")]
+pub async fn undo(
+    id: GameId,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let mut game = Game::lock(&game);
+
+    let undone = game.check_takeback(player.id).map(|_| game.undo_turn());
+    if undone == Ok(true) {
+        game.changed();
+    }
+
+    let message = match undone {
+        Ok(true) => None,
+        Ok(false) => Some("There is no move to undo.".to_string()),
+        Err(message) => Some(message),
+    };
+
+    Ok(RawHtml(game.to_html(&player, message.as_deref())))
+}
+
+#[post("/games/<id>/redo")]
+pub async fn redo(
+    id: GameId,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let redone = {
+        let mut game = Game::lock(&game);
+        let redone = game.check_takeback(player.id).map(|_| game.redo_turn());
+        if redone == Ok(true) {
+            game.changed();
+        }
+        redone
+    };
+
+    let played = play_computer_moves(&game).await;
+
+    let message = match (redone, played) {
+        (Ok(false), _) => Some("There is no move to redo.".to_string()),
+        (Err(message), _) | (_, Err(message)) => Some(message),
+        (Ok(true), Ok(())) => None,
+    };
+    let html = render_played(&game, games, &player, message.as_deref());
+
+    Ok(RawHtml(html))
+}
+
+/// Takes the game back or forward to the position after `ply` moves.
+/// Playing a move from an earlier position replaces the moves that came after it.
+#[post("/games/<id>/ply/<ply>")]
+pub async fn jump_to_ply(
+    id: GameId,
+    ply: usize,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let result = {
+        let mut game = Game::lock(&game);
+        let result = game
+            .check_takeback(player.id)
+            .and_then(|_| game.jump_to(ply));
+        if result.is_ok() {
+            game.changed();
+        }
+        result
+    };
+
+    let result = result.and(play_computer_moves(&game).await);
+
+    let html = render_played(&game, games, &player, result.err().as_deref());
+
+    Ok(RawHtml(html))
+}
+
+/// Sends an `update` event with the revision of the game whenever it changes, and one right
+/// away so a player that reconnects can tell whether it missed anything.
+#[get("/games/<id>/events")]
+pub async fn game_events(
+    id: GameId,
+    games: &State<Games>,
+    shutdown: Shutdown,
+) -> Result<EventStream![], (Status, String)> {
+    let game = find_game(id, games)?;
+
+    Ok(revision_events(&game, None, shutdown))
+}
+
+/// The events of `game_events` for a spectator, who is counted while the stream is open.
+#[get("/games/<token>/spectate/events")]
+pub async fn spectator_events(
+    token: &str,
+    games: &State<Games>,
+    shutdown: Shutdown,
+) -> Result<EventStream![], (Status, String)> {
+    let game = find_watched(token, games)?;
+    let spectator = Game::watch(&game);
+
+    Ok(revision_events(&game, Some(spectator), shutdown))
+}
+
+fn revision_events(
+    game: &Arc<Mutex<Game>>,
+    spectator: Option<Spectator>,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    let (mut updates, revision) = {
+        let game = Game::lock(game);
+        (game.subscribe(), game.revision())
+    };
+
+    EventStream! {
+        // Dropped with the stream, when the spectator goes away.
+        let _spectator = spectator;
+        yield update_event(revision);
+
+        loop {
+            let update = select! {
+                update = updates.recv() => match update {
+                    Ok(update) => update,
+                    // Only the latest revision matters, and a later update carries it.
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            yield match update {
+                GameUpdate::Changed(revision) => update_event(revision),
+                GameUpdate::Spectators(count) => Event::data(count.to_string()).event("spectators"),
+            };
+        }
+    }
+}
+
+fn update_event(revision: u64) -> Event {
+    Event::data(revision.to_string())
+        .event("update")
+        .id(revision.to_string())
+}
+
+/// A page to follow a game on without being able to play, that can be shared with anyone.
+/// It is found by the watch token of the game, the id would let spectators play.
+#[get("/watch/<token>")]
+pub async fn watch_page(
+    token: &str,
+    viewer: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_watched(token, games)?;
+
+    let mut context = Context::new();
+    context.insert(
+        "game",
+        &Game::lock(&game).to_spectator_html(viewer.renderer),
+    );
+    let html = get_template("templates/hexy/watch.html", Some(context)).ok_or_else(|| {
+        (
+            Status::InternalServerError,
+            "Failed to load the page.".into(),
+        )
+    })?;
+
+    Ok(RawHtml(html))
+}
+
+#[get("/games/<token>/spectate")]
+pub async fn spectate_game(
+    token: &str,
+    viewer: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_watched(token, games)?;
+    let html = Game::lock(&game).to_spectator_html(viewer.renderer);
+
+    Ok(RawHtml(html))
+}
+
+/// Renders the position after `ply` moves without changing the game.
+#[get("/games/<key>/ply/<ply>")]
+pub async fn preview_ply(
+    key: &str,
+    ply: usize,
+    viewer: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_viewed(key, games)?;
+    // Watch tokens are letters, ids are numbers.
+    let spectating = key.parse::<GameId>().is_err();
+    let html = Game::lock(&game)
+        .preview_html(ply, spectating, viewer.renderer)
+        .ok_or_else(|| (Status::NotFound, format!("The game has no move {ply}.")))?;
+
+    Ok(RawHtml(html))
+}
+
+/// A PNG picture of the current position of a game, with the last move marked.
+#[get("/games/<key>/picture?<options..>")]
+pub async fn game_picture(
+    key: &str,
+    options: PictureOptions,
+    games: &State<Games>,
+) -> Result<(ContentType, Vec<u8>), (Status, String)> {
+    let game = find_viewed(key, games)?;
+    let (board, view) = {
+        let game = Game::lock(&game);
+        let view = BoardView {
+            last_move: game.last_move().map(|mv| (mv.from.clone(), mv.to.clone())),
+            ..Default::default()
+        };
+        (game.board.clone(), view)
+    };
+    let png = draw_png(board, view, options)
+        .await
+        .map_err(|error| (Status::BadRequest, error))?;
+
+    Ok((ContentType::PNG, png))
+}
+
+/// Draws a picture on a thread of its own, big pictures of big boards take a while.
+async fn draw_png(
+    board: HexBoard,
+    view: BoardView,
+    options: PictureOptions,
+) -> Result<Vec<u8>, String> {
+    rocket::tokio::task::spawn_blocking(move || board.to_png(&view, &options))
+        .await
+        .unwrap_or_else(|_| Err("The picture could not be drawn.".into()))
+}
+
+/// A game record sent as a file to save.
+#[derive(Responder)]
+#[response(content_type = "text/plain")]
+pub struct RecordFile {
+    text: String,
+    disposition: Header<'static>,
+}
+
+#[get("/games/<key>/record")]
+pub async fn download_record(
+    key: &str,
+    games: &State<Games>,
+) -> Result<RecordFile, (Status, String)> {
+    let game = find_viewed(key, games)?;
+    let text = Game::lock(&game).to_record().to_text();
+
+    Ok(RecordFile {
+        text,
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"hexy-game-{key}.hexy\""),
+        ),
+    })
+}
+
+/// An animated picture of a game sent as a file to save.
+#[derive(Responder)]
+#[response(content_type = "image/svg+xml")]
+pub struct ReplayFile {
+    svg: String,
+    disposition: Header<'static>,
+}
+
+/// Plays the game move by move as an animated SVG image, ending with the result.
+#[get("/games/<key>/replay?<options..>")]
+pub async fn download_replay(
+    key: &str,
+    options: ReplayOptions,
+    games: &State<Games>,
+) -> Result<ReplayFile, (Status, String)> {
+    let game = find_viewed(key, games)?;
+    let svg = Game::lock(&game)
+        .to_record()
+        .to_replay_svg(&options)
+        .map_err(|error| (Status::BadRequest, error))?;
+
+    Ok(ReplayFile {
+        svg,
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"hexy-game-{key}.svg\""),
+        ),
+    })
+}
+
+/// A game record, from a file upload or a text field.
+#[derive(FromForm)]
+pub struct RecordUpload {
+    record: String,
+}
+
+/// Starts a game from a record, at the position after its last move.
+#[post("/games/import", data = "<upload>")]
+pub async fn upload_record(
+    upload: Form<RecordUpload>,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, BadRequest<String>> {
+    let record =
+        GameRecord::parse(&upload.record).map_err(|error| BadRequest(error.to_string()))?;
+
+    let game = games.create_with(|id| Game::from_record(id, record));
+    let html = Game::lock(&game).to_html(&player, None);
+
+    Ok(RawHtml(html))
+}
+
+#[get("/lobby")]
+pub async fn lobby_page() -> Option<NamedFile> {
+    let path = Path::new(relative!("public/hexy/lobby.html"));
+    NamedFile::open(path).await.ok()
+}
+
+/// Renders the games in the lobby that wait for players, newest first.
+#[get("/lobby/games")]
+pub async fn lobby_games(games: &State<Games>) -> Option<RawHtml<String>> {
+    let mut open: Vec<OpenGame> = games
+        .all()
+        .iter()
+        .filter_map(|game| {
+            let game = Game::lock(game);
+            game.is_open().then(|| OpenGame::new(&game))
+        })
+        .collect();
+    open.sort_by_key(|game| std::cmp::Reverse(game.id));
+
+    let mut context = Context::new();
+    context.insert("games", &open);
+
+    get_template("templates/hexy/lobby_games.html", Some(context)).map(RawHtml)
+}
+
+/// A game waiting for players, as shown in the lobby.
+#[derive(Serialize)]
+struct OpenGame {
+    id: GameId,
+    size: u32,
+    variant: &'static str,
+    time_control: Option<String>,
+    /// The players at every team, empty for free seats.
+    players: Vec<String>,
+}
+
+impl OpenGame {
+    fn new(game: &Game) -> OpenGame {
+        OpenGame {
+            id: game.id,
+            size: game.board.size(),
+            variant: game.variant.name(),
+            time_control: game.time_control().map(|time| time.to_string()),
+            players: (0..game.seats.teams())
+                .map(|team| {
+                    game.seats
+                        .get(team)
+                        .map(|seat| seat.name.clone())
+                        .unwrap_or_default()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The settings of a game started from the lobby, and the name of the player starting it.
+#[derive(FromForm)]
+pub struct LobbyGame {
+    name: String,
+    settings: GameSettings,
+}
+
+/// Sends the browser to another page or loads the page again, htmx follows the `HX-Redirect`
+/// and `HX-Refresh` headers.
+#[derive(Responder)]
+pub struct Redirect {
+    text: String,
+    header: Header<'static>,
+}
+
+impl Redirect {
+    fn to_game(id: GameId) -> Redirect {
+        Redirect {
+            text: String::new(),
+            header: Header::new("HX-Redirect", format!("/hexy/?game={id}")),
+        }
+    }
+
+    fn refresh() -> Redirect {
+        Redirect {
+            text: String::new(),
+            header: Header::new("HX-Refresh", "true"),
+        }
+    }
+}
+
+/// Starts a game that is shown in the lobby, with the player at the first team.
+#[post("/lobby/games", data = "<form>")]
+pub async fn create_lobby_game(
+    form: Form<LobbyGame>,
+    player: Player,
+    cookies: &CookieJar<'_>,
+    games: &State<Games>,
+    engines: &State<ExternalEngines>,
+) -> Result<Redirect, BadRequest<String>> {
+    let LobbyGame { name, settings } = form.into_inner();
+    let name = clean_name(&name).map_err(BadRequest)?;
+    games.check_name(&name, player.id).map_err(BadRequest)?;
+
+    let game = start_game(settings, games, engines)?;
+    let id = {
+        let mut game = Game::lock(&game);
+        game.listed = true;
+        game.join(0, player.id, &name).map_err(BadRequest)?;
+        game.save();
+        game.id
+    };
+    cookies.add(Player::name_cookie(&name));
+
+    Ok(Redirect::to_game(id))
+}
+
+#[derive(FromForm)]
+pub struct JoinForm {
+    name: String,
+}
+
+/// Seats the player at a team of the game.
+#[post("/games/<id>/join?<team>", data = "<form>")]
+pub async fn join_game(
+    id: GameId,
+    team: u32,
+    form: Form<JoinForm>,
+    mut player: Player,
+    cookies: &CookieJar<'_>,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let mut game = Game::lock(&game);
+
+    let joined = clean_name(&form.name).and_then(|name| {
+        games.check_name(&name, player.id)?;
+        game.join(team, player.id, &name)?;
+        Ok(name)
+    });
+    let message = match joined {
+        Ok(name) => {
+            game.changed();
+            cookies.add(Player::name_cookie(&name));
+            player.name = Some(name);
+            None
+        }
+        Err(message) => Some(message),
+    };
+
+    Ok(RawHtml(game.to_html(&player, message.as_deref())))
+}
+
+/// Frees the seats of the player, the game goes on for the others.
+#[post("/games/<id>/leave")]
+pub async fn leave_game(
+    id: GameId,
+    player: Player,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let mut game = Game::lock(&game);
+
+    let message = match game.seats.leave(player.id) {
+        true => {
+            game.changed();
+            None
+        }
+        false => Some("You do not play in this game."),
+    };
+
+    Ok(RawHtml(game.to_html(&player, message)))
+}
+
+/// The engines players can pick for the computer, as options of a select.
+#[get("/engines")]
+pub async fn get_engines(engines: &State<ExternalEngines>) -> Option<RawHtml<String>> {
+    let mut context = Context::new();
+    context.insert("external", &engines.names().collect::<Vec<&str>>());
+
+    get_template("templates/hexy/engine_options.html", Some(context)).map(RawHtml)
+}
+
+/// Lets the computer answer, on a thread where its thinking does not hold up other requests.
+/// The error is a message for the player when the computer could not play.
+async fn play_computer_moves(game: &Arc<Mutex<Game>>) -> Result<(), String> {
+    let game = game.clone();
+    let thinking = rocket::tokio::task::spawn_blocking(move || Game::play_computer_moves(&game));
+
+    // A panic while thinking leaves the game as it was before the computer's turn.
+    thinking
+        .await
+        .unwrap_or_else(|_| Err("The computer stopped thinking.".into()))
+}
+
+/// Finds a game, and ends it first when the team to move ran out of time since the last request.
+fn find_game(id: GameId, games: &Games) -> Result<Arc<Mutex<Game>>, (Status, String)> {
+    let game = games.get(id).ok_or_else(|| {
+        (
+            Status::NotFound,
+            format!("Game {id} does not exist or has expired."),
+        )
+    })?;
+
+    Ok(catch_up(game, games))
+}
+
+/// Finds a game by the watch token in a link for spectators.
+fn find_watched(token: &str, games: &Games) -> Result<Arc<Mutex<Game>>, (Status, String)> {
+    let game = games.watched(token).ok_or_else(|| {
+        (
+            Status::NotFound,
+            "There is no game to watch at this link, or it has expired.".to_string(),
+        )
+    })?;
+
+    Ok(catch_up(game, games))
+}
+
+/// Finds a game by its id for players, or by its watch token for spectators, for the pages that
+/// only show the game.
+fn find_viewed(key: &str, games: &Games) -> Result<Arc<Mutex<Game>>, (Status, String)> {
+    match key.parse() {
+        Ok(id) => find_game(id, games),
+        Err(_) => find_watched(key, games),
+    }
+}
+
+/// Ends the game when a clock ran out since anyone looked, and rates it when it just ended.
+fn catch_up(game: Arc<Mutex<Game>>, games: &Games) -> Arc<Mutex<Game>> {
+    {
+        let mut locked = Game::lock(&game);
+        locked.update_clock(Instant::now());
+        games.rate(&mut locked);
+    }
+
+    game
+}
+
+/// The players by rating, with how they did in their rated games.
+#[get("/ratings")]
+pub async fn ratings_page(games: &State<Games>) -> Option<RawHtml<String>> {
+    let players: Vec<RatedPlayer> = games
+        .ratings()
+        .leaderboard()
+        .iter()
+        .enumerate()
+        .map(|(i, player)| RatedPlayer::new(i + 1, player))
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("players", &players);
+
+    get_template("templates/hexy/ratings.html", Some(context)).map(RawHtml)
+}
+
+/// How the rating of a player changed with every rated game, latest first.
+#[get("/ratings/player?<name>")]
+pub async fn player_page(
+    name: &str,
+    games: &State<Games>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let ratings = games.ratings();
+    let player = ratings.player(name).ok_or_else(|| {
+        (
+            Status::NotFound,
+            format!("{name} has not played a rated game."),
+        )
+    })?;
+    let history: Vec<RatingRow> = player.history.iter().rev().map(RatingRow::new).collect();
+
+    let mut context = Context::new();
+    context.insert("player", &RatedPlayer::new(0, player));
+    context.insert("history", &history);
+    let html = get_template("templates/hexy/player.html", Some(context)).ok_or_else(|| {
+        (
+            Status::InternalServerError,
+            "Failed to load the page.".into(),
+        )
+    })?;
+
+    Ok(RawHtml(html))
+}
+
+/// A player on the leaderboard, with the rating rounded.
+#[derive(Serialize)]
+struct RatedPlayer {
+    rank: usize,
+    name: String,
+    rating: i64,
+    games: usize,
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl RatedPlayer {
+    fn new(rank: usize, player: &PlayerRating) -> RatedPlayer {
+        RatedPlayer {
+            rank,
+            name: player.name.clone(),
+            rating: player.rating.round() as i64,
+            games: player.games(),
+            wins: player.wins,
+            draws: player.draws,
+            losses: player.losses,
+        }
+    }
+}
+
+/// A rated game in the history of a player.
+#[derive(Serialize)]
+struct RatingRow {
+    game: usize,
+    date: String,
+    opponents: String,
+    result: &'static str,
+    /// Like `+12` or `-7`.
+    change: String,
+    rating: i64,
+}
+
+impl RatingRow {
+    fn new(change: &RatingChange) -> RatingRow {
+        RatingRow {
+            game: change.game + 1,
+            date: change.date.clone(),
+            opponents: change.opponents.join(", "),
+            result: change.result,
+            change: format!("{:+}", change.change.round() as i64),
+            rating: change.rating.round() as i64,
+        }
     }
+}
+
+#[derive(FromForm)]
+pub struct PositionForm {
+    name: String,
+}
+
+/// Keeps the position on the board of the game, to start other games from later.
+#[post("/games/<id>/positions", data = "<form>")]
+pub async fn save_position(
+    id: GameId,
+    form: Form<PositionForm>,
+    player: Player,
+    games: &State<Games>,
+    store: &State<SharedStore>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let game = find_game(id, games)?;
+    let game = Game::lock(&game);
+
+    let name = form.name.trim();
+    let message = match name.is_empty() {
+        true => "Name the position to save it.".to_string(),
+        false => {
+            let position = store
+                .save_position(name, &game.board.to_notation())
+                .map_err(internal_error)?;
+            format!("Saved the position as {}.", position.name)
+        }
+    };
+
+    Ok(RawHtml(game.to_html(&player, Some(&message))))
+}
+
+/// Renders the saved positions, to start a game from.
+#[get("/positions")]
+pub async fn get_positions(
+    store: &State<SharedStore>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let positions = store.positions().map_err(internal_error)?;
+
+    let mut context = Context::new();
+    context.insert("positions", &positions);
+    let html = get_template("templates/hexy/positions.html", Some(context))
+        .ok_or_else(|| internal_error("Failed to load the positions."))?;
+
+    Ok(RawHtml(html))
+}
+
+/// Starts a game from a saved position.
+#[post("/positions/<id>/play")]
+pub async fn play_position(
+    id: i64,
+    player: Player,
+    games: &State<Games>,
+    store: &State<SharedStore>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let position = store
+        .position(id)
+        .map_err(internal_error)?
+        .ok_or_else(|| (Status::NotFound, format!("There is no position {id}.")))?;
+    let board = HexBoard::from_notation(&position.notation)
+        .map_err(|error| (Status::InternalServerError, error.to_string()))?;
+
+    let game = games.create(board);
+    let html = Game::lock(&game).to_html(&player, None);
+
+    Ok(RawHtml(html))
+}
+
+/// Forgets a saved position, and renders the ones that are left.
+#[delete("/positions/<id>")]
+pub async fn delete_position(
+    id: i64,
+    store: &State<SharedStore>,
+) -> Result<RawHtml<String>, (Status, String)> {
+    store.delete_position(id).map_err(internal_error)?;
+
+    get_positions(store).await
+}
+
+/// A page to set up a position on, starting from the position in `notation` when it is given.
+#[get("/editor?<notation>")]
+pub async fn editor_page(
+    notation: Option<&str>,
+    player: Player,
+) -> Result<RawHtml<String>, (Status, String)> {
+    let (board, message) = match notation.map(HexBoard::from_notation) {
+        Some(Ok(board)) => (board, None),
+        Some(Err(error)) => (HexBoard::new(EDITOR_SIZE), Some(error.to_string())),
+        None => (HexBoard::new(EDITOR_SIZE), None),
+    };
 
-    let board = HexBoard::new(size);
+    let form = EditorForm::new(&board);
+    let mut context = Context::new();
+    context.insert(
+        "editor",
+        &editor_html(&board, &form, player.renderer, message.as_deref()),
+    );
+    let html = get_template("templates/hexy/editor.html", Some(context))
+        .ok_or_else(|| internal_error("Failed to load the page."))?;
+
+    Ok(RawHtml(html))
+}
+
+/// Changes the shape, the teams or the side to move of the position in the editor.
+#[post("/editor", data = "<form>")]
+pub async fn edit_setup(
+    form: Form<EditorForm>,
+    player: Player,
+) -> Result<RawHtml<String>, BadRequest<String>> {
+    let board = form.board().map_err(BadRequest)?;
+    let shape = match form.shape.as_deref().map(BoardShape::from_notation) {
+        Some(Ok(shape)) => shape,
+        Some(Err(error)) => {
+            let message = format!("Invalid shape: {}.", error.kind);
+            return Ok(RawHtml(editor_html(
+                &board,
+                &form,
+                player.renderer,
+                Some(&message),
+            )));
+        }
+        None => board.shape().clone(),
+    };
+    let teams = form.teams.unwrap_or(board.teams());
+    let to_move = form
+        .to_move
+        .unwrap_or(board.to_move())
+        .min(teams.saturating_sub(1));
+
+    let html = match reshape(&board, shape, teams, to_move) {
+        Ok(reshaped) => editor_html(&reshaped, &form, player.renderer, None),
+        Err(message) => editor_html(&board, &form, player.renderer, Some(&message)),
+    };
+
+    Ok(RawHtml(html))
+}
+
+/// Places or removes a piece on the hex that was clicked in the editor.
+#[post("/editor/click?<hex>", data = "<form>")]
+pub async fn edit_click(
+    hex: &str,
+    form: Form<EditorForm>,
+    player: Player,
+) -> Result<RawHtml<String>, BadRequest<String>> {
+    let mut board = form.board().map_err(BadRequest)?;
+    let hex_id =
+        HexId::from_notation(hex).ok_or_else(|| BadRequest(format!("{hex} is not a hex.")))?;
+
+    let message = editor::click(&mut board, &hex_id, &form).err();
+
+    Ok(RawHtml(editor_html(
+        &board,
+        &form,
+        player.renderer,
+        message.as_deref(),
+    )))
+}
+
+/// Starts a game from the position in the editor.
+#[post("/editor/play", data = "<form>")]
+pub async fn play_edited(
+    form: Form<EditorForm>,
+    games: &State<Games>,
+) -> Result<Redirect, BadRequest<String>> {
+    let board = form.board().map_err(BadRequest)?;
+    let id = Game::lock(&games.create(board)).id;
+
+    Ok(Redirect::to_game(id))
+}
+
+#[derive(Serialize)]
+struct RendererOption {
+    name: &'static str,
+    label: &'static str,
+    selected: bool,
+}
+
+/// A list to choose how boards are drawn, with the choice of the browser selected.
+#[get("/renderers")]
+pub async fn get_renderers(player: Player) -> Option<RawHtml<String>> {
+    let renderers: Vec<RendererOption> = Renderer::ALL
+        .into_iter()
+        .map(|renderer| RendererOption {
+            name: renderer.name(),
+            label: renderer.label(),
+            selected: renderer == player.renderer,
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("renderers", &renderers);
+
+    get_template("templates/hexy/renderers.html", Some(context)).map(RawHtml)
+}
+
+#[derive(FromForm)]
+pub struct RendererChoice {
+    renderer: String,
+}
+
+/// Remembers how the browser wants boards drawn, and loads the page again with it.
+#[post("/renderer", data = "<choice>")]
+pub async fn set_renderer(
+    choice: Form<RendererChoice>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, BadRequest<String>> {
+    let name = &choice.renderer;
+    let renderer = Renderer::from_name(name)
+        .ok_or_else(|| BadRequest(format!("There is no renderer called {name}.")))?;
+    cookies.add(Player::renderer_cookie(renderer));
+
+    Ok(Redirect::refresh())
+}
+
+fn internal_error(error: impl ToString) -> (Status, String) {
+    (Status::InternalServerError, error.to_string())
+}
 
-    Some(RawHtml(board.to_html()))
+/// Renders the game after a move of the player and the computer's replies, and rates the game
+/// when they ended it.
+fn render_played(
+    game: &Mutex<Game>,
+    games: &Games,
+    player: &Player,
+    message: Option<&str>,
+) -> String {
+    let mut game = Game::lock(game);
+    games.rate(&mut game);
+    game.to_html(player, message)
 }
 
 pub fn get_routes() -> Vec<Route> {
-    routes![index, get_board]
+    routes![
+        index,
+        get_board,
+        get_picture,
+        create_game,
+        get_game,
+        get_notation,
+        make_move,
+        click,
+        undo,
+        redo,
+        jump_to_ply,
+        preview_ply,
+        game_events,
+        spectator_events,
+        watch_page,
+        spectate_game,
+        download_record,
+        download_replay,
+        game_picture,
+        upload_record,
+        get_engines,
+        lobby_page,
+        lobby_games,
+        create_lobby_game,
+        join_game,
+        leave_game,
+        ratings_page,
+        player_page,
+        save_position,
+        get_positions,
+        play_position,
+        delete_position,
+        editor_page,
+        edit_setup,
+        edit_click,
+        play_edited,
+        get_renderers,
+        set_renderer
+    ]
 }
@@ -0,0 +1,244 @@
+//! Chess clocks for Hexy games, kept by the server so players can not cheat on their time.
+//!
+//! Only the clock of the team to move runs. A move stops it and starts the clock of the next
+//! team. The time used for a move is the time it took minus the delay of the time control,
+//! and the increment is added after every move that was made in time.
+
+use std::time::{Duration, Instant};
+
+use crate::storage::SavedClock;
+
+use super::lobby::TimeControl;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clock {
+    time_control: TimeControl,
+    /// The time every team had left when its clock last stopped.
+    remaining: Vec<Duration>,
+    /// The team whose clock runs, and since when.
+    running: Option<(u32, Instant)>,
+}
+
+impl Clock {
+    /// Clocks for every team, all with the base time and none running.
+    pub fn new(time_control: TimeControl, teams: u32) -> Clock {
+        Clock {
+            time_control,
+            remaining: vec![time_control.base; teams as usize],
+            running: None,
+        }
+    }
+
+    pub fn time_control(&self) -> TimeControl {
+        self.time_control
+    }
+
+    pub fn running(&self) -> Option<u32> {
+        self.running.map(|(team, _)| team)
+    }
+
+    /// The time the team has left at `now`.
+    pub fn remaining(&self, team: u32, now: Instant) -> Duration {
+        let remaining = self
+            .remaining
+            .get(team as usize)
+            .copied()
+            .unwrap_or_default();
+
+        match self.running {
+            Some((running, since)) if running == team => {
+                remaining.saturating_sub(self.used(since, now))
+            }
+            _ => remaining,
+        }
+    }
+
+    /// How much of the delay of the running clock is left at `now`.
+    pub fn delay_left(&self, now: Instant) -> Duration {
+        match self.running {
+            Some((_, since)) => self
+                .time_control
+                .delay
+                .saturating_sub(now.saturating_duration_since(since)),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Starts the clock of a team, the clock that ran before is stopped.
+    pub fn start(&mut self, team: u32, now: Instant) {
+        self.stop(now);
+        self.running = Some((team, now));
+    }
+
+    /// Stops the running clock, and gives its team the increment unless it ran out of time.
+    pub fn stop(&mut self, now: Instant) {
+        let Some((team, since)) = self.running.take() else {
+            return;
+        };
+
+        let used = self.used(since, now);
+        let remaining = &mut self.remaining[team as usize];
+        *remaining = remaining.saturating_sub(used);
+        if !remaining.is_zero() {
+            *remaining += self.time_control.increment;
+        }
+    }
+
+    /// The team whose clock ran out, its clock stops at zero.
+    pub fn check_flag(&mut self, now: Instant) -> Option<u32> {
+        let team = self.running()?;
+        if !self.remaining(team, now).is_zero() {
+            return None;
+        }
+
+        self.running = None;
+        self.remaining[team as usize] = Duration::ZERO;
+        Some(team)
+    }
+
+    /// The clocks as they are at `now`, for storage.
+    pub fn to_saved(&self, now: Instant) -> SavedClock {
+        SavedClock {
+            base_ms: self.time_control.base.as_millis() as u64,
+            increment_ms: self.time_control.increment.as_millis() as u64,
+            delay_ms: self.time_control.delay.as_millis() as u64,
+            remaining_ms: (0..self.remaining.len() as u32)
+                .map(|team| self.remaining(team, now).as_millis() as u64)
+                .collect(),
+            running: self.running(),
+        }
+    }
+
+    /// Clocks from storage, the clock that ran when they were saved runs again from `now`.
+    pub fn from_saved(saved: &SavedClock, now: Instant) -> Clock {
+        Clock {
+            time_control: TimeControl {
+                base: Duration::from_millis(saved.base_ms),
+                increment: Duration::from_millis(saved.increment_ms),
+                delay: Duration::from_millis(saved.delay_ms),
+            },
+            remaining: saved
+                .remaining_ms
+                .iter()
+                .map(|ms| Duration::from_millis(*ms))
+                .collect(),
+            running: saved.running.map(|team| (team, now)),
+        }
+    }
+
+    fn used(&self, since: Instant, now: Instant) -> Duration {
+        now.saturating_duration_since(since)
+            .saturating_sub(self.time_control.delay)
+    }
+}
+
+/// Writes a time like a clock, `4:05`, with tenths of a second in the last ten seconds.
+pub fn clock_text(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds < 10 {
+        return format!("0:0{}.{}", seconds, time.subsec_millis() / 100);
+    }
+
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::hexy::clock::{clock_text, Clock};
+    use crate::hexy::lobby::TimeControl;
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_increment() {
+        let start = Instant::now();
+        let mut clock = Clock::new(
+            TimeControl {
+                base: seconds(60),
+                increment: seconds(2),
+                delay: Duration::ZERO,
+            },
+            2,
+        );
+        assert_eq!(None, clock.running());
+
+        clock.start(0, start);
+        assert_eq!(seconds(50), clock.remaining(0, start + seconds(10)));
+        assert_eq!(seconds(60), clock.remaining(1, start + seconds(10)));
+
+        clock.start(1, start + seconds(10));
+        assert_eq!(Some(1), clock.running());
+        assert_eq!(seconds(52), clock.remaining(0, start + seconds(30)));
+        assert_eq!(seconds(40), clock.remaining(1, start + seconds(30)));
+        assert_eq!(None, clock.check_flag(start + seconds(30)));
+    }
+
+    #[test]
+    fn test_delay() {
+        let start = Instant::now();
+        let mut clock = Clock::new(
+            TimeControl {
+                base: seconds(60),
+                increment: Duration::ZERO,
+                delay: seconds(5),
+            },
+            2,
+        );
+
+        clock.start(0, start);
+        assert_eq!(seconds(60), clock.remaining(0, start + seconds(4)));
+        assert_eq!(seconds(1), clock.delay_left(start + seconds(4)));
+        assert_eq!(seconds(57), clock.remaining(0, start + seconds(8)));
+
+        clock.stop(start + seconds(8));
+        assert_eq!(None, clock.running());
+        assert_eq!(seconds(57), clock.remaining(0, start + seconds(100)));
+    }
+
+    #[test]
+    fn test_flag() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::new(1, 5, 0).unwrap(), 3);
+
+        clock.start(2, start);
+        assert_eq!(None, clock.check_flag(start + seconds(59)));
+        assert_eq!(Some(2), clock.check_flag(start + seconds(61)));
+        assert_eq!(None, clock.running());
+        assert_eq!(Duration::ZERO, clock.remaining(2, start + seconds(61)));
+
+        // No increment for a move that came too late.
+        clock.start(1, start);
+        clock.start(2, start + seconds(70));
+        assert_eq!(Duration::ZERO, clock.remaining(1, start + seconds(70)));
+    }
+
+    #[test]
+    fn test_saved_clock() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::new(1, 0, 0).unwrap(), 2);
+        clock.start(1, start);
+
+        let saved = clock.to_saved(start + seconds(10));
+        assert_eq!(vec![60_000, 50_000], saved.remaining_ms);
+
+        // The time between saving and loading does not count.
+        let later = start + seconds(1000);
+        let clock = Clock::from_saved(&saved, later);
+        assert_eq!(Some(1), clock.running());
+        assert_eq!(seconds(45), clock.remaining(1, later + seconds(5)));
+        assert_eq!(TimeControl::new(1, 0, 0).unwrap(), clock.time_control());
+    }
+
+    #[test]
+    fn test_clock_text() {
+        assert_eq!("5:00", clock_text(Duration::from_secs(300)));
+        assert_eq!("1:05", clock_text(Duration::from_secs(65)));
+        assert_eq!("0:10", clock_text(Duration::from_secs(10)));
+        assert_eq!("0:09.5", clock_text(Duration::from_millis(9_500)));
+        assert_eq!("0:00.0", clock_text(Duration::ZERO));
+    }
+}
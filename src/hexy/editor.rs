@@ -0,0 +1,216 @@
+//! Setting up a position by hand, to start a game from or to write down as notation.
+//!
+//! The editor keeps nothing on the server: the page sends the notation of the position with
+//! every change, and gets the editor back with the position after the change.
+
+use rocket::{FromForm, FromFormField};
+use serde::Serialize;
+use tera::Context;
+
+use crate::util::get_template;
+
+use super::hex_board::{BoardView, HexBoard, Renderer};
+use super::hexagon::{HexId, HexState};
+use super::notation::{team_letter, MAX_TEAMS, MAX_VALUE};
+use super::shape::{BoardShape, Shape};
+
+/// The size of the board a new editor starts with.
+pub const EDITOR_SIZE: u32 = 7;
+
+/// What clicking a hex does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromFormField, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    /// Puts the piece of the form on the hex, replacing what was there.
+    #[field(value = "place")]
+    Place,
+    #[field(value = "remove")]
+    Remove,
+}
+
+/// The position being edited and the settings of the editor, sent along with every change.
+#[derive(Debug, FromForm)]
+pub struct EditorForm {
+    pub notation: String,
+    #[field(default = Tool::Place)]
+    pub tool: Tool,
+    /// The team of the pieces to place.
+    #[field(default = 0)]
+    pub team: u32,
+    /// The value of the pieces to place, 0 for a leader.
+    #[field(default = 1)]
+    pub value: u32,
+    /// The shape to change the board to, in notation like `7` or `r11`.
+    pub shape: Option<String>,
+    pub teams: Option<u32>,
+    pub to_move: Option<u32>,
+}
+
+impl EditorForm {
+    /// The settings of a new editor for the position.
+    pub fn new(board: &HexBoard) -> EditorForm {
+        EditorForm {
+            notation: board.to_notation(),
+            tool: Tool::Place,
+            team: 0,
+            value: 1,
+            shape: None,
+            teams: None,
+            to_move: None,
+        }
+    }
+
+    pub fn board(&self) -> Result<HexBoard, String> {
+        HexBoard::from_notation(&self.notation).map_err(|error| error.to_string())
+    }
+}
+
+/// Changes the hex that was clicked with the tool of the form.
+pub fn click(board: &mut HexBoard, hex_id: &HexId, form: &EditorForm) -> Result<(), String> {
+    let state = match form.tool {
+        Tool::Place if form.team >= board.teams() => {
+            return Err(format!("Team {} is not playing.", team_letter(form.team)));
+        }
+        Tool::Place if form.value > MAX_VALUE => {
+            return Err(format!("A piece is worth at most {MAX_VALUE}."));
+        }
+        Tool::Place => HexState::Piece {
+            team: form.team,
+            value: form.value,
+        },
+        Tool::Remove => HexState::Free,
+    };
+
+    board
+        .set_state(hex_id, state)
+        .map(|_| ())
+        .ok_or_else(|| format!("{} is not on the board.", hex_id.to_notation()))
+}
+
+/// The board with another shape, amount of teams and side to move. The pieces that are off the
+/// new board or of a team that no longer plays are left out.
+pub fn reshape(
+    board: &HexBoard,
+    shape: BoardShape,
+    teams: u32,
+    to_move: u32,
+) -> Result<HexBoard, String> {
+    if !(1..=MAX_TEAMS).contains(&teams) {
+        return Err(format!("A position has 1 to {MAX_TEAMS} teams."));
+    }
+    if to_move >= teams {
+        return Err(format!("Team {} is not playing.", team_letter(to_move)));
+    }
+
+    let mut reshaped = HexBoard::with_shape(shape);
+    for hexagon in board.hexagons() {
+        match hexagon.state {
+            HexState::Piece { team, .. }
+                if team < teams && reshaped.shape().contains(&hexagon.hex_id) =>
+            {
+                reshaped.set_state(&hexagon.hex_id, hexagon.state);
+            }
+            _ => {}
+        }
+    }
+    reshaped.set_teams(teams);
+    reshaped.set_to_move(to_move);
+
+    Ok(reshaped)
+}
+
+#[derive(Serialize)]
+struct TeamOption {
+    index: u32,
+    letter: char,
+}
+
+/// Renders the editor with the position on `board`, and a message when one is given.
+pub fn editor_html(
+    board: &HexBoard,
+    form: &EditorForm,
+    renderer: Renderer,
+    message: Option<&str>,
+) -> String {
+    let view = BoardView {
+        renderer,
+        click_url: Some("/hexy/editor/click".into()),
+        ..Default::default()
+    };
+    let teams: Vec<TeamOption> = (0..board.teams())
+        .map(|index| TeamOption {
+            index,
+            letter: team_letter(index),
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("notation", &board.to_notation());
+    context.insert("shape", &board.shape().to_notation());
+    context.insert("teams", &teams);
+    context.insert("team_count", &board.teams());
+    context.insert("to_move", &board.to_move());
+    context.insert("tool", &form.tool);
+    context.insert("team", &form.team);
+    context.insert("value", &form.value);
+    context.insert("max_teams", &MAX_TEAMS);
+    context.insert("max_value", &MAX_VALUE);
+    context.insert("message", message.unwrap_or_default());
+    context.insert("board", &board.render(&view));
+
+    get_template("templates/hexy/position_editor.html", Some(context))
+        .expect("Failed to load editor template.")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::editor::{click, reshape, EditorForm, Tool};
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{HexId, HexState};
+    use crate::hexy::shape::BoardShape;
+
+    #[test]
+    fn test_click() {
+        let mut board = HexBoard::new(3);
+        let mut form = EditorForm::new(&board);
+        form.team = 1;
+        form.value = 4;
+
+        click(&mut board, &HexId::new(2, 2), &form).unwrap();
+        assert_eq!(
+            Some(HexState::Piece { team: 1, value: 4 }),
+            board.get(&HexId::new(2, 2)).map(|hexagon| hexagon.state)
+        );
+        assert!(click(&mut board, &HexId::new(9, 9), &form).is_err());
+
+        form.team = 2;
+        assert!(click(&mut board, &HexId::new(2, 2), &form).is_err());
+
+        form.team = 0;
+        form.value = 30_000_000;
+        assert!(click(&mut board, &HexId::new(1, 1), &form).is_err());
+        assert_eq!(
+            Some(HexState::Free),
+            board.get(&HexId::new(1, 1)).map(|hexagon| hexagon.state)
+        );
+
+        form.tool = Tool::Remove;
+        click(&mut board, &HexId::new(2, 2), &form).unwrap();
+        assert_eq!(HexBoard::new(3).to_notation(), board.to_notation());
+    }
+
+    #[test]
+    fn test_reshape() {
+        let mut board = HexBoard::new(3);
+        board.set_teams(3);
+        board.set_state(&HexId::new(1, 1), HexState::Piece { team: 0, value: 0 });
+        board.set_state(&HexId::new(2, 2), HexState::Piece { team: 2, value: 3 });
+        board.set_state(&HexId::new(5, 5), HexState::Piece { team: 1, value: 0 });
+
+        let reshaped = reshape(&board, BoardShape::Rhombus(4), 2, 1).unwrap();
+        assert_eq!("r4 a03/4/4/4 b 2", reshaped.to_notation());
+
+        assert!(reshape(&board, BoardShape::Hexagon(3), 2, 2).is_err());
+        assert!(reshape(&board, BoardShape::Hexagon(3), 0, 0).is_err());
+    }
+}
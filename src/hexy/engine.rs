@@ -0,0 +1,230 @@
+//! Computer players for Hexy.
+
+pub mod alpha_beta;
+pub mod external;
+pub mod mcts;
+pub mod protocol;
+pub mod transposition;
+
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use super::hex_board::HexBoard;
+use super::hexagon::HexState;
+use super::moves::{teams_in_play, Move};
+use external::ExternalEngines;
+use protocol::{GoLimits, ProtocolError};
+
+/// Something that picks moves, so computer players can be swapped for each other.
+pub trait Engine {
+    /// The move to play for the side to move, None when it has no legal moves.
+    fn choose_move(&mut self, board: &HexBoard) -> Option<Move>;
+}
+
+/// The engines the server can play with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineKind {
+    AlphaBeta,
+    Mcts,
+    /// A program from `ExternalEngines`, started with `command`.
+    External {
+        name: String,
+        command: String,
+    },
+}
+
+impl EngineKind {
+    pub const BUILT_IN: [EngineKind; 2] = [EngineKind::AlphaBeta, EngineKind::Mcts];
+
+    /// Finds an engine by the name players pick it with.
+    pub fn from_name(name: &str, external: &ExternalEngines) -> Option<EngineKind> {
+        match name {
+            "alpha-beta" => Some(EngineKind::AlphaBeta),
+            "mcts" => Some(EngineKind::Mcts),
+            _ => external.command(name).map(|command| EngineKind::External {
+                name: name.to_string(),
+                command: command.to_string(),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            EngineKind::AlphaBeta => "alpha-beta",
+            EngineKind::Mcts => "mcts",
+            EngineKind::External { name, .. } => name,
+        }
+    }
+
+    /// An engine that searches within `limits`, `seed` makes its random choices.
+    /// Alpha-beta only uses the depth and MCTS only the iterations, without a movetime or the
+    /// limit of its own an engine thinks for `protocol::DEFAULT_MOVE_TIME`.
+    pub fn create(
+        &self,
+        limits: &GoLimits,
+        seed: u64,
+    ) -> Result<Box<dyn Engine + Send>, ProtocolError> {
+        let time = |own_limit: Option<u32>| {
+            limits
+                .movetime
+                .or(own_limit.is_none().then_some(protocol::DEFAULT_MOVE_TIME))
+        };
+
+        Ok(match self {
+            EngineKind::AlphaBeta => Box::new(alpha_beta::AlphaBeta::new(SearchLimits {
+                depth: limits
+                    .depth
+                    .unwrap_or(SearchLimits::MAX_DEPTH)
+                    .min(SearchLimits::MAX_DEPTH),
+                time: time(limits.depth),
+            })),
+            EngineKind::Mcts => Box::new(mcts::Mcts::new(mcts::MctsSettings {
+                iterations: limits.iterations.unwrap_or(u32::MAX),
+                time: time(limits.iterations),
+                seed,
+                ..Default::default()
+            })),
+            EngineKind::External { command, .. } => {
+                Box::new(external::ExternalEngine::start(command, *limits)?)
+            }
+        })
+    }
+}
+
+/// Scores a position from the point of view of one team, higher is better for that team.
+/// Positions where the game is over are scored by the search itself, not by the evaluator.
+pub trait Evaluator {
+    fn evaluate(&self, board: &HexBoard, team: u32) -> i32;
+}
+
+/// How much a search may do. It stops at whichever limit it reaches first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: u32,
+    pub time: Option<Duration>,
+}
+
+impl SearchLimits {
+    /// The deepest any search goes, which also limits searches that only have a time budget.
+    pub const MAX_DEPTH: u32 = 64;
+
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth: depth.min(SearchLimits::MAX_DEPTH),
+            time: None,
+        }
+    }
+
+    pub fn time(time: Duration) -> SearchLimits {
+        SearchLimits {
+            depth: SearchLimits::MAX_DEPTH,
+            time: Some(time),
+        }
+    }
+}
+
+/// Counts the pieces of a team against the average of its opponents, with a bonus for pieces
+/// near the center where they reach the most hexes. Teams that lost their leader count for nothing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Material;
+
+/// What a piece of value 1 is worth, a piece of value v is worth v times as much.
+const PIECE_WORTH: i64 = 100;
+
+/// The best and worst score of a position, well away from the scores of won and lost games.
+const MAX_SCORE: i64 = alpha_beta::WIN as i64 / 2;
+
+impl Evaluator for Material {
+    fn evaluate(&self, board: &HexBoard, team: u32) -> i32 {
+        let in_play = teams_in_play(board);
+        let size = board.size() as i64;
+        let center = board.shape().center();
+        // Summed in i64, the values of pieces can be as large as a u32.
+        let mut scores = vec![0i64; board.teams() as usize];
+
+        for hexagon in board.hexagons() {
+            let HexState::Piece {
+                team: piece_team,
+                value,
+            } = hexagon.state
+            else {
+                continue;
+            };
+
+            if let Some(score) = scores.get_mut(piece_team as usize) {
+                let centrality = size - 1 - hexagon.hex_id.distance(&center) as i64;
+                *score += value as i64 * PIECE_WORTH + centrality;
+            }
+        }
+
+        let opponents: Vec<i64> = in_play
+            .iter()
+            .filter(|other| **other != team)
+            .map(|other| scores[*other as usize])
+            .collect();
+        let own = if in_play.contains(&team) {
+            scores[team as usize]
+        } else {
+            0
+        };
+
+        let score = if opponents.is_empty() {
+            own
+        } else {
+            own - opponents.iter().sum::<i64>() / opponents.len() as i64
+        };
+
+        score.clamp(-MAX_SCORE, MAX_SCORE) as i32
+    }
+}
+
+/// Puts the moves most likely to be good first: captures of leaders, then captures of the
+/// most valuable pieces, then the rest in their original order.
+pub fn order_moves(moves: &mut [Move]) {
+    moves.sort_by_key(|mv| match mv.captured {
+        HexState::Piece { value: 0, .. } => (0, Reverse(0)),
+        HexState::Piece { value, .. } => (1, Reverse(value)),
+        HexState::Free => (2, Reverse(0)),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::engine::{order_moves, Evaluator, Material, MAX_SCORE};
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{HexId, HexState};
+    use crate::hexy::moves::{moves, starting_position};
+
+    #[test]
+    fn test_starting_position_is_even() {
+        for teams in 2..=6 {
+            let board = starting_position(7, teams).unwrap();
+            let scores: Vec<i32> = (0..teams)
+                .map(|team| Material.evaluate(&board, team))
+                .collect();
+            assert!(scores.iter().all(|score| *score == scores[0]), "{scores:?}");
+        }
+    }
+
+    #[test]
+    fn test_huge_values_do_not_overflow() {
+        let mut board = HexBoard::from_notation("2 a0a0/b02/2 a 2").unwrap();
+        let piece = HexState::Piece {
+            team: 0,
+            value: u32::MAX,
+        };
+        board.set_state(&HexId::new(1, 2), piece);
+
+        assert_eq!(MAX_SCORE as i32, Material.evaluate(&board, 0));
+        assert_eq!(-MAX_SCORE as i32, Material.evaluate(&board, 1));
+    }
+
+    #[test]
+    fn test_order_moves_puts_captures_first() {
+        let board = HexBoard::from_notation("3 1a2a1/2b31/1b13/b03/a02 a 2").unwrap();
+        let mut moves = moves(&board, 0);
+        order_moves(&mut moves);
+        assert_eq!("a2xc2", moves[0].to_string());
+        assert!(moves[1..].iter().all(|mv| !mv.to_string().contains('x')));
+    }
+}
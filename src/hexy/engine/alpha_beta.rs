@@ -0,0 +1,386 @@
+//! Minimax search with alpha-beta pruning and iterative deepening.
+//!
+//! With more than two teams the search is paranoid: every other team is assumed to play
+//! against the searching team, which keeps the search a two sided minimax.
+
+use std::time::Instant;
+
+use crate::hexy::hex_board::HexBoard;
+use crate::hexy::moves::{moves, teams_in_play, Move};
+
+use super::transposition::TranspositionTable;
+use super::{order_moves, Engine, Evaluator, Material, SearchLimits};
+
+/// The score of a won game. Wins found sooner score higher, so the search goes for the
+/// quickest win and the slowest loss.
+pub const WIN: i32 = 1_000_000;
+
+/// How many nodes are searched between looking at the clock.
+const NODES_PER_TIME_CHECK: u64 = 1024;
+
+/// How many positions the transposition table of a search remembers.
+const TABLE_SIZE: usize = 1 << 16;
+
+/// Scores further from zero than this are wins or losses, counted from the root of the search.
+const WIN_THRESHOLD: i32 = WIN - SearchLimits::MAX_DEPTH as i32 - 1;
+
+/// What a score in the table says about the real score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    /// The search was cut off, the real score is at least this high.
+    Lower,
+    /// No move reached alpha, the real score is at most this high.
+    Upper,
+}
+
+#[derive(Debug, Clone)]
+struct TableEntry {
+    /// The team the search played for, scores of other searches do not apply.
+    team: u32,
+    depth: u32,
+    /// Wins and losses are stored counted from this position instead of from the root.
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// What a search found at the deepest depth it finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// The score for the side that was to move.
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlphaBeta<E: Evaluator = Material> {
+    evaluator: E,
+    limits: SearchLimits,
+    table: TranspositionTable<TableEntry>,
+}
+
+impl AlphaBeta {
+    pub fn new(limits: SearchLimits) -> AlphaBeta {
+        AlphaBeta::with_evaluator(Material, limits)
+    }
+}
+
+impl<E: Evaluator> AlphaBeta<E> {
+    pub fn with_evaluator(evaluator: E, limits: SearchLimits) -> AlphaBeta<E> {
+        AlphaBeta {
+            evaluator,
+            limits,
+            table: TranspositionTable::new(TABLE_SIZE),
+        }
+    }
+
+    /// Searches one depth deeper at a time until the depth or the time runs out. The result of
+    /// a depth that was cut short by the time is thrown away, except for moves it proved better.
+    /// What was learned about positions is kept for the next search.
+    pub fn search(&mut self, board: &HexBoard) -> SearchResult {
+        let mut search = Search {
+            evaluator: &self.evaluator,
+            table: &mut self.table,
+            team: board.to_move(),
+            deadline: self.limits.time.map(|time| Instant::now() + time),
+            nodes: 0,
+            stopped: false,
+        };
+
+        let mut root_moves = moves(board, board.to_move());
+        order_moves(&mut root_moves);
+        let mut result = SearchResult {
+            best_move: root_moves.first().cloned(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        };
+        if root_moves.len() < 2 {
+            return result;
+        }
+
+        let mut board = board.clone();
+        for depth in 1..=self.limits.depth {
+            let mut alpha = -WIN - 1;
+            let mut best = None;
+
+            for (i, mv) in root_moves.iter().enumerate() {
+                board.apply_move(mv);
+                let score = search.node(&mut board, depth - 1, 1, alpha, WIN + 1);
+                board.undo_move(mv);
+
+                let Some(score) = score else {
+                    break;
+                };
+                if score > alpha {
+                    alpha = score;
+                    best = Some(i);
+                }
+            }
+
+            if let Some(best) = best {
+                // Searched first at the next depth, which makes the cutoffs come sooner.
+                let mv = root_moves.remove(best);
+                root_moves.insert(0, mv);
+                result.best_move = root_moves.first().cloned();
+                if !search.stopped {
+                    result.score = alpha;
+                    result.depth = depth;
+                }
+            }
+
+            if search.stopped || alpha.abs() > WIN_THRESHOLD {
+                break;
+            }
+        }
+
+        result.nodes = search.nodes;
+        result
+    }
+}
+
+impl<E: Evaluator> Engine for AlphaBeta<E> {
+    fn choose_move(&mut self, board: &HexBoard) -> Option<Move> {
+        self.search(board).best_move
+    }
+}
+
+struct Search<'a, E: Evaluator> {
+    evaluator: &'a E,
+    table: &'a mut TranspositionTable<TableEntry>,
+    /// The team the search plays for, every other team plays against it.
+    team: u32,
+    deadline: Option<Instant>,
+    nodes: u64,
+    stopped: bool,
+}
+
+impl<E: Evaluator> Search<'_, E> {
+    /// The score of a position for the searching team, None when the time ran out.
+    fn node(
+        &mut self,
+        board: &mut HexBoard,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> Option<i32> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_PER_TIME_CHECK)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return None;
+        }
+
+        let in_play = teams_in_play(board);
+        if !in_play.contains(&self.team) {
+            return Some(-WIN + ply as i32);
+        }
+        if in_play.len() == 1 {
+            return Some(WIN - ply as i32);
+        }
+        if depth == 0 {
+            return Some(self.evaluator.evaluate(board, self.team));
+        }
+
+        let hash = board.hash();
+        let mut table_move = None;
+        if let Some(entry) = self.table.get(hash).filter(|entry| entry.team == self.team) {
+            let score = from_table(entry.score, ply);
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if entry.depth >= depth && usable {
+                return Some(score);
+            }
+
+            table_move = entry.best_move.clone();
+        }
+
+        let mut moves = moves(board, board.to_move());
+        if moves.is_empty() {
+            return Some(0);
+        }
+        order_moves(&mut moves);
+        if let Some(index) = moves.iter().position(|mv| Some(mv) == table_move.as_ref()) {
+            moves[..=index].rotate_right(1);
+        }
+
+        let (original_alpha, original_beta) = (alpha, beta);
+        let maximizing = board.to_move() == self.team;
+        let mut best = if maximizing { -WIN - 1 } else { WIN + 1 };
+        let mut best_move = None;
+        for mv in &moves {
+            board.apply_move(mv);
+            let score = self.node(board, depth - 1, ply + 1, alpha, beta);
+            board.undo_move(mv);
+            let score = score?;
+
+            if (maximizing && score > best) || (!maximizing && score < best) {
+                best = score;
+                best_move = Some(mv);
+            }
+            if maximizing {
+                alpha = alpha.max(score);
+            } else {
+                beta = beta.min(score);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= original_beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(
+            hash,
+            TableEntry {
+                team: self.team,
+                depth,
+                score: to_table(best, ply),
+                bound,
+                best_move: best_move.cloned(),
+            },
+        );
+
+        Some(best)
+    }
+}
+
+/// Counts a win or loss from the position at `ply` instead of from the root.
+fn to_table(score: i32, ply: u32) -> i32 {
+    match score {
+        score if score > WIN_THRESHOLD => score + ply as i32,
+        score if score < -WIN_THRESHOLD => score - ply as i32,
+        score => score,
+    }
+}
+
+fn from_table(score: i32, ply: u32) -> i32 {
+    match score {
+        score if score > WIN_THRESHOLD => score - ply as i32,
+        score if score < -WIN_THRESHOLD => score + ply as i32,
+        score => score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::hexy::engine::alpha_beta::{AlphaBeta, NODES_PER_TIME_CHECK, WIN};
+    use crate::hexy::engine::{Engine, SearchLimits};
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{HexId, HexState};
+    use crate::hexy::moves::{moves, starting_position, status, GameStatus};
+
+    fn board_with(size: u32, pieces: &[((u32, u32), u32, u32)]) -> HexBoard {
+        let mut board = HexBoard::new(size);
+        for ((x, y), team, value) in pieces {
+            board.set_state(
+                &HexId::new(*x, *y),
+                HexState::Piece {
+                    team: *team,
+                    value: *value,
+                },
+            );
+        }
+
+        board
+    }
+
+    #[test]
+    fn test_takes_the_winning_capture() {
+        let board = board_with(
+            3,
+            &[
+                ((1, 1), 0, 0),
+                ((3, 3), 0, 2),
+                ((5, 5), 1, 0),
+                ((5, 3), 1, 1),
+            ],
+        );
+        let result = AlphaBeta::new(SearchLimits::depth(3)).search(&board);
+
+        assert_eq!("c3xe5", result.best_move.unwrap().to_string());
+        assert_eq!(WIN - 1, result.score);
+        assert_eq!(1, result.depth);
+    }
+
+    #[test]
+    fn test_moves_the_leader_out_of_reach() {
+        let mut board = board_with(3, &[((3, 3), 0, 0), ((3, 5), 1, 3), ((5, 5), 1, 0)]);
+        let mv = AlphaBeta::new(SearchLimits::depth(2))
+            .choose_move(&board)
+            .unwrap();
+        board.apply_move(&mv);
+
+        let threats = moves(&board, 1)
+            .into_iter()
+            .filter(|mv| mv.captured != HexState::Free)
+            .count();
+        assert_eq!(0, threats);
+    }
+
+    #[test]
+    fn test_table_is_reused_between_searches() {
+        let board = starting_position(4, 2).unwrap();
+        let mut engine = AlphaBeta::new(SearchLimits::depth(4));
+        let first = engine.search(&board);
+        let second = engine.search(&board);
+
+        assert_eq!(first.best_move, second.best_move);
+        assert_eq!(first.score, second.score);
+        assert!(
+            second.nodes < first.nodes / 2,
+            "{} {}",
+            first.nodes,
+            second.nodes
+        );
+    }
+
+    #[test]
+    fn test_respects_the_time_budget() {
+        // Without any time the search stops at the first look at the clock, however fast or slow
+        // the machine is, and still has a move from the depths it finished.
+        let board = starting_position(7, 2).unwrap();
+        let result = AlphaBeta::new(SearchLimits::time(Duration::ZERO)).search(&board);
+
+        assert_eq!(NODES_PER_TIME_CHECK, result.nodes);
+        assert!(result.depth < SearchLimits::MAX_DEPTH);
+        assert!(moves(&board, 0).contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn test_self_play_only_plays_legal_moves() {
+        for teams in [2, 3] {
+            let mut board = starting_position(4, teams).unwrap();
+            let mut engine = AlphaBeta::new(SearchLimits::depth(2));
+
+            for _ in 0..60 {
+                if status(&board) != GameStatus::Ongoing {
+                    break;
+                }
+
+                let mv = engine.choose_move(&board).unwrap();
+                assert!(moves(&board, board.to_move()).contains(&mv));
+                board.apply_move(&mv);
+            }
+        }
+    }
+}
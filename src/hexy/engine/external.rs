@@ -0,0 +1,278 @@
+//! Engine programs that speak the protocol of the `protocol` module, started by the server.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command as Process, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rocket::figment::Figment;
+
+use crate::hexy::hex_board::HexBoard;
+use crate::hexy::moves::{parse_move, Move};
+
+use super::protocol::{Command, GoLimits, ProtocolError, Reply, DEFAULT_MOVE_TIME};
+use super::Engine;
+
+/// How long an engine may take to start and answer `hexy`.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How much longer than its move time an engine may take to answer, for slow starts and pipes.
+const REPLY_GRACE: Duration = Duration::from_secs(2);
+
+/// How long to wait for a search that only has a depth or iteration limit.
+const UNTIMED_REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The engine programs players can pick, by name. Only the server's owner can add programs,
+/// with the `hexy_engines` config table from names to commands:
+///
+/// ```toml
+/// [default.hexy_engines]
+/// random-bot = "python3 /home/hexy/random_bot.py"
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ExternalEngines {
+    commands: BTreeMap<String, String>,
+}
+
+impl ExternalEngines {
+    pub fn new(commands: BTreeMap<String, String>) -> ExternalEngines {
+        ExternalEngines { commands }
+    }
+
+    pub fn from_figment(figment: &Figment) -> ExternalEngines {
+        let commands = figment
+            .extract_inner::<BTreeMap<String, String>>("hexy_engines")
+            .unwrap_or_default();
+
+        ExternalEngines::new(commands)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+
+    pub fn command(&self, name: &str) -> Option<&str> {
+        self.commands.get(name).map(String::as_str)
+    }
+}
+
+/// A running engine program. It is asked to quit when this is dropped.
+pub struct ExternalEngine {
+    name: Option<String>,
+    limits: GoLimits,
+    process: Child,
+    input: ChildStdin,
+    /// The lines the program writes, read on their own thread so waiting can time out.
+    lines: Receiver<String>,
+}
+
+impl ExternalEngine {
+    /// Starts a program from a command line of words separated by spaces, and introduces
+    /// the server. Every `go` it is sent asks for a search within `limits`.
+    pub fn start(command: &str, limits: GoLimits) -> Result<ExternalEngine, ProtocolError> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| ProtocolError::Start("the command is empty".into()))?;
+
+        let mut process = Process::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| ProtocolError::Start(error.to_string()))?;
+
+        let (Some(input), Some(output)) = (process.stdin.take(), process.stdout.take()) else {
+            process.kill().ok();
+            return Err(ProtocolError::Start("no pipes to the engine".into()));
+        };
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(output).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = ExternalEngine {
+            name: None,
+            limits,
+            process,
+            input,
+            lines,
+        };
+
+        engine.send(&Command::Hexy)?;
+        let deadline = Instant::now() + START_TIMEOUT;
+        loop {
+            match engine.next_reply(deadline, "hexyok")? {
+                Reply::HexyOk => break,
+                Reply::Id { key, value } if key == "name" => engine.name = Some(value),
+                _ => {}
+            }
+        }
+
+        Ok(engine)
+    }
+
+    /// The name the engine gave itself.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Asks the engine for a move, and checks that the move is legal.
+    pub fn best_move(&mut self, board: &HexBoard) -> Result<Option<Move>, ProtocolError> {
+        self.send(&Command::Position(board.clone()))?;
+        self.send(&Command::Go(self.limits))?;
+
+        let timeout = match self.limits {
+            GoLimits {
+                movetime: Some(movetime),
+                ..
+            } => movetime + REPLY_GRACE,
+            GoLimits {
+                depth: None,
+                iterations: None,
+                ..
+            } => DEFAULT_MOVE_TIME + REPLY_GRACE,
+            _ => UNTIMED_REPLY_TIMEOUT,
+        };
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            match self.next_reply(deadline, "bestmove")? {
+                Reply::BestMove(None) => return Ok(None),
+                Reply::BestMove(Some(text)) => {
+                    return parse_move(board, &text)
+                        .map(Some)
+                        .ok_or(ProtocolError::IllegalMove(text));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&mut self, command: &Command) -> Result<(), ProtocolError> {
+        writeln!(self.input, "{command}")
+            .and_then(|_| self.input.flush())
+            .map_err(|_| ProtocolError::Closed)
+    }
+
+    /// The next line the engine wrote that is a reply, skipping lines that are not.
+    fn next_reply(
+        &self,
+        deadline: Instant,
+        waiting_for: &'static str,
+    ) -> Result<Reply, ProtocolError> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(timeout) {
+                Ok(line) => {
+                    if let Some(reply) = Reply::parse(&line) {
+                        return Ok(reply);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return Err(ProtocolError::Timeout(waiting_for)),
+                Err(RecvTimeoutError::Disconnected) => return Err(ProtocolError::Closed),
+            }
+        }
+    }
+}
+
+impl Engine for ExternalEngine {
+    /// Any problem with the engine counts as not finding a move.
+    fn choose_move(&mut self, board: &HexBoard) -> Option<Move> {
+        self.best_move(board).ok().flatten()
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        self.send(&Command::Quit).ok();
+
+        // Engines that do not listen to quit, or are stuck thinking, are stopped the hard way.
+        let deadline = Instant::now() + Duration::from_millis(200);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        self.process.kill().ok();
+        self.process.wait().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::hexy::engine::external::ExternalEngine;
+    use crate::hexy::engine::protocol::{GoLimits, ProtocolError};
+    use crate::hexy::moves::starting_position;
+
+    /// A shell engine that plays the same move whatever the position, written to a file
+    /// because commands are split on spaces.
+    fn script_engine(name: &str, best_move: &str) -> String {
+        let path = std::env::temp_dir().join(format!("hexy-test-engine-{name}.sh"));
+        let script = format!(
+            "while read line; do\n\
+             case \"$line\" in\n\
+             hexy) echo \"id name {name}\"; echo hexyok ;;\n\
+             go*) echo \"info thinking\"; echo \"bestmove {best_move}\" ;;\n\
+             quit) exit ;;\n\
+             esac\n\
+             done\n"
+        );
+        std::fs::write(&path, script).unwrap();
+
+        format!("sh {}", path.display())
+    }
+
+    fn limits() -> GoLimits {
+        GoLimits {
+            movetime: Some(Duration::from_millis(10)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plays_the_engines_move() {
+        let board = starting_position(3, 2).unwrap();
+        let mut engine = ExternalEngine::start(&script_engine("legal", "a2-a3"), limits()).unwrap();
+
+        assert_eq!(Some("legal"), engine.name());
+        let mv = engine.best_move(&board).unwrap().unwrap();
+        assert_eq!("a2-a3", mv.to_string());
+    }
+
+    #[test]
+    fn test_rejects_illegal_moves() {
+        let board = starting_position(3, 2).unwrap();
+        let mut engine =
+            ExternalEngine::start(&script_engine("illegal", "a1-e5"), limits()).unwrap();
+
+        assert_eq!(
+            Err(ProtocolError::IllegalMove("a1-e5".into())),
+            engine.best_move(&board)
+        );
+    }
+
+    #[test]
+    fn test_programs_that_do_not_speak_the_protocol() {
+        assert!(matches!(
+            ExternalEngine::start("hexy-engine-that-does-not-exist", limits()),
+            Err(ProtocolError::Start(_))
+        ));
+        assert!(matches!(
+            ExternalEngine::start("true", limits()),
+            Err(ProtocolError::Closed)
+        ));
+    }
+}
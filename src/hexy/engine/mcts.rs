@@ -0,0 +1,322 @@
+//! Monte Carlo tree search with UCT selection.
+//!
+//! Every node keeps the score of the team that played the move leading to it, so each team
+//! picks the moves that are best for itself. That works for any amount of teams, unlike a
+//! minimax search that has to assume everyone plays against one team.
+
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::hexy::hex_board::HexBoard;
+use crate::hexy::hexagon::HexState;
+use crate::hexy::moves::{moves, teams_in_play, Move};
+
+use super::Engine;
+
+/// How random games are played out from a new node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollout {
+    /// Every legal move is as likely.
+    Random,
+    /// A leader is captured whenever possible, other captures are played half of the time.
+    Captures,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MctsSettings {
+    /// The most iterations of a search, each adds a node and plays out one game.
+    pub iterations: u32,
+    /// A search stops early when it runs out of time. Searches with a time budget are only
+    /// reproducible when they hit the iteration limit first.
+    pub time: Option<Duration>,
+    /// How much unexplored moves are favoured over moves that scored well, √2 in theory.
+    pub exploration: f64,
+    pub rollout: Rollout,
+    /// Random games that go on longer than this are scored as a draw between the teams left.
+    pub max_rollout_plies: u32,
+    pub seed: u64,
+}
+
+impl Default for MctsSettings {
+    fn default() -> MctsSettings {
+        MctsSettings {
+            iterations: 10_000,
+            time: None,
+            exploration: std::f64::consts::SQRT_2,
+            rollout: Rollout::Captures,
+            max_rollout_plies: 200,
+            seed: 0,
+        }
+    }
+}
+
+/// What a search found, `visits` and `score` are those of the best move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsResult {
+    pub best_move: Option<Move>,
+    pub iterations: u32,
+    pub visits: u32,
+    /// The average result of the games through the best move for the side to move, from 0 for
+    /// a loss to 1 for a win.
+    pub score: f64,
+}
+
+#[derive(Debug)]
+pub struct Mcts {
+    settings: MctsSettings,
+    rng: StdRng,
+}
+
+struct Node {
+    mv: Option<Move>,
+    /// The team that played `mv`, whose point of view `score` is from.
+    team: u32,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Move>,
+    visits: u32,
+    score: f64,
+}
+
+impl Mcts {
+    pub fn new(settings: MctsSettings) -> Mcts {
+        Mcts {
+            settings,
+            rng: StdRng::seed_from_u64(settings.seed),
+        }
+    }
+
+    pub fn search(&mut self, board: &HexBoard) -> MctsResult {
+        let deadline = self.settings.time.map(|time| Instant::now() + time);
+        let mut nodes = vec![Node {
+            mv: None,
+            team: board.to_move(),
+            parent: None,
+            children: Vec::new(),
+            untried: moves(board, board.to_move()),
+            visits: 0,
+            score: 0.0,
+        }];
+
+        let mut iterations = 0;
+        if nodes[0].untried.len() > 1 {
+            while iterations < self.settings.iterations
+                && deadline.is_none_or(|deadline| Instant::now() < deadline)
+            {
+                self.iterate(board, &mut nodes);
+                iterations += 1;
+            }
+        }
+
+        let root = &nodes[0];
+        let best = root
+            .children
+            .iter()
+            .map(|child| &nodes[*child])
+            .max_by_key(|child| child.visits);
+
+        match best {
+            Some(best) => MctsResult {
+                best_move: best.mv.clone(),
+                iterations,
+                visits: best.visits,
+                score: best.score / best.visits as f64,
+            },
+            None => MctsResult {
+                best_move: root.untried.first().cloned(),
+                iterations,
+                visits: 0,
+                score: 0.0,
+            },
+        }
+    }
+
+    /// Selects a leaf, expands it with one move, plays out a game and scores the path.
+    fn iterate(&mut self, root: &HexBoard, nodes: &mut Vec<Node>) {
+        let mut board = root.clone();
+        let mut node = 0;
+
+        while nodes[node].untried.is_empty() && !nodes[node].children.is_empty() {
+            node = self.select(nodes, node);
+            if let Some(mv) = &nodes[node].mv {
+                board.apply_move(mv);
+            }
+        }
+
+        if !nodes[node].untried.is_empty() {
+            let index = self.rng.gen_range(0..nodes[node].untried.len());
+            let mv = nodes[node].untried.swap_remove(index);
+            let team = board.to_move();
+            board.apply_move(&mv);
+
+            nodes.push(Node {
+                mv: Some(mv),
+                team,
+                parent: Some(node),
+                children: Vec::new(),
+                untried: moves(&board, board.to_move()),
+                visits: 0,
+                score: 0.0,
+            });
+            let child = nodes.len() - 1;
+            nodes[node].children.push(child);
+            node = child;
+        }
+
+        let scores = self.rollout(&mut board);
+
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut nodes[index];
+            node.visits += 1;
+            node.score += scores.get(node.team as usize).copied().unwrap_or_default();
+            current = node.parent;
+        }
+    }
+
+    /// The child with the best upper confidence bound for the team to move.
+    fn select(&self, nodes: &[Node], node: usize) -> usize {
+        let log_visits = (nodes[node].visits.max(1) as f64).ln();
+        let uct = |child: &Node| {
+            child.score / child.visits as f64
+                + self.settings.exploration * (log_visits / child.visits as f64).sqrt()
+        };
+
+        nodes[node]
+            .children
+            .iter()
+            .copied()
+            .max_by(|a, b| uct(&nodes[*a]).total_cmp(&uct(&nodes[*b])))
+            .unwrap_or(node)
+    }
+
+    /// Plays random moves until the game is over, and scores the end for every team.
+    fn rollout(&mut self, board: &mut HexBoard) -> Vec<f64> {
+        for _ in 0..self.settings.max_rollout_plies {
+            let moves = moves(board, board.to_move());
+            let Some(mv) = self.pick_rollout_move(&moves) else {
+                break;
+            };
+            board.apply_move(mv);
+        }
+
+        let in_play = teams_in_play(board);
+        let mut scores = vec![0.0; board.teams() as usize];
+        for team in &in_play {
+            scores[*team as usize] = 1.0 / in_play.len() as f64;
+        }
+
+        scores
+    }
+
+    fn pick_rollout_move<'a>(&mut self, moves: &'a [Move]) -> Option<&'a Move> {
+        if self.settings.rollout == Rollout::Captures {
+            let leader_capture = moves
+                .iter()
+                .find(|mv| matches!(mv.captured, HexState::Piece { value: 0, .. }));
+            if leader_capture.is_some() {
+                return leader_capture;
+            }
+
+            if self.rng.gen_bool(0.5) {
+                let captures: Vec<&Move> = moves
+                    .iter()
+                    .filter(|mv| mv.captured != HexState::Free)
+                    .collect();
+                if let Some(capture) = captures.choose(&mut self.rng) {
+                    return Some(capture);
+                }
+            }
+        }
+
+        moves.choose(&mut self.rng)
+    }
+}
+
+impl Engine for Mcts {
+    fn choose_move(&mut self, board: &HexBoard) -> Option<Move> {
+        self.search(board).best_move
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::engine::mcts::{Mcts, MctsSettings, Rollout};
+    use crate::hexy::engine::Engine;
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{HexId, HexState};
+    use crate::hexy::moves::{moves, starting_position, status, GameStatus};
+
+    fn settings(iterations: u32, seed: u64) -> MctsSettings {
+        MctsSettings {
+            iterations,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_search() {
+        let board = starting_position(4, 3).unwrap();
+        let first = Mcts::new(settings(100, 7)).search(&board);
+        let second = Mcts::new(settings(100, 7)).search(&board);
+
+        assert_eq!(100, first.iterations);
+        assert_eq!(first, second);
+        assert!(moves(&board, 0).contains(&first.best_move.unwrap()));
+    }
+
+    #[test]
+    fn test_takes_the_winning_capture() {
+        let mut board = HexBoard::new(3);
+        for ((x, y), team, value) in [
+            ((1, 1), 0, 0),
+            ((3, 3), 0, 2),
+            ((5, 5), 1, 0),
+            ((5, 3), 1, 1),
+        ] {
+            board.set_state(&HexId::new(x, y), HexState::Piece { team, value });
+        }
+
+        for rollout in [Rollout::Random, Rollout::Captures] {
+            let mut mcts = Mcts::new(MctsSettings {
+                rollout,
+                ..settings(500, 1)
+            });
+            let result = mcts.search(&board);
+            assert_eq!("c3xe5", result.best_move.unwrap().to_string());
+            assert!(result.score > 0.9);
+        }
+    }
+
+    #[test]
+    fn test_single_move_needs_no_search() {
+        let board = HexBoard::from_notation("2 a0b1/1b11/1b0 a 2").unwrap();
+        let result = Mcts::new(settings(100, 0)).search(&board);
+
+        assert_eq!(0, result.iterations);
+        assert_eq!(moves(&board, 0), vec![result.best_move.unwrap()]);
+    }
+
+    #[test]
+    fn test_six_team_self_play() {
+        let mut board = starting_position(5, 6).unwrap();
+        let mut mcts = Mcts::new(MctsSettings {
+            max_rollout_plies: 50,
+            ..settings(30, 3)
+        });
+
+        for _ in 0..12 {
+            if status(&board) != GameStatus::Ongoing {
+                break;
+            }
+
+            let mv = mcts.choose_move(&board).unwrap();
+            assert!(moves(&board, board.to_move()).contains(&mv));
+            board.apply_move(&mv);
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! A line based protocol between the server and engine programs, in the spirit of UCI for chess.
+//!
+//! The server writes commands to the engine's standard input, one per line:
+//!
+//! - `hexy` starts the conversation. The engine answers with `id name <name>` and
+//!   `id author <author>` when it likes, and then with `hexyok`.
+//! - `isready` is answered with `readyok` once the engine handled everything before it.
+//! - `newgame` says the next position is from another game.
+//! - `position <notation>` is the position to search, in board notation.
+//! - `go [depth <plies>] [movetime <milliseconds>] [iterations <count>]` starts a search of
+//!   the last position, within the limits that are given.
+//! - `quit` asks the engine to exit.
+//!
+//! The engine answers a `go` with `bestmove <move>`, with a move like `d2-e3` or `d2xe3`, or with
+//! `bestmove none` when the side to move has no legal moves. Lines starting with `info` are
+//! free text for people watching. Both sides ignore lines they do not know, so the protocol
+//! can grow without breaking older engines.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::hexy::hex_board::HexBoard;
+use crate::hexy::notation::NotationError;
+
+use super::Engine;
+
+/// How long a built-in engine thinks when `go` has no limits.
+pub const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    InvalidPosition(NotationError),
+    InvalidArgument(String),
+    IllegalMove(String),
+    Timeout(&'static str),
+    Closed,
+    Start(String),
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::InvalidPosition(error) => write!(f, "{error}"),
+            ProtocolError::InvalidArgument(argument) => write!(f, "invalid argument '{argument}'"),
+            ProtocolError::IllegalMove(text) => write!(f, "'{text}' is not a legal move"),
+            ProtocolError::Timeout(waiting_for) => write!(f, "no {waiting_for} in time"),
+            ProtocolError::Closed => write!(f, "the engine stopped"),
+            ProtocolError::Start(reason) => write!(f, "the engine could not start: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// The limits of a search, a search without any limit uses `DEFAULT_MOVE_TIME`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GoLimits {
+    pub depth: Option<u32>,
+    pub movetime: Option<Duration>,
+    pub iterations: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Hexy,
+    IsReady,
+    NewGame,
+    Position(HexBoard),
+    Go(GoLimits),
+    Quit,
+}
+
+impl Command {
+    /// Parses a line, unknown commands are None.
+    pub fn parse(line: &str) -> Result<Option<Command>, ProtocolError> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+        let command = match name {
+            "hexy" => Command::Hexy,
+            "isready" => Command::IsReady,
+            "newgame" => Command::NewGame,
+            "position" => Command::Position(
+                HexBoard::from_notation(rest.trim()).map_err(ProtocolError::InvalidPosition)?,
+            ),
+            "go" => Command::Go(parse_go(rest)?),
+            "quit" => Command::Quit,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(command))
+    }
+}
+
+fn parse_go(arguments: &str) -> Result<GoLimits, ProtocolError> {
+    let mut limits = GoLimits::default();
+    let mut words = arguments.split_whitespace();
+    while let Some(name) = words.next() {
+        let value = words.next().and_then(|value| value.parse::<u64>().ok());
+        let value = value.ok_or_else(|| ProtocolError::InvalidArgument(name.to_string()))?;
+
+        match name {
+            "depth" => limits.depth = Some(value.min(u32::MAX as u64) as u32),
+            "movetime" => limits.movetime = Some(Duration::from_millis(value)),
+            "iterations" => limits.iterations = Some(value.min(u32::MAX as u64) as u32),
+            _ => {}
+        }
+    }
+
+    Ok(limits)
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::Hexy => write!(f, "hexy"),
+            Command::IsReady => write!(f, "isready"),
+            Command::NewGame => write!(f, "newgame"),
+            Command::Position(board) => write!(f, "position {}", board.to_notation()),
+            Command::Go(limits) => {
+                write!(f, "go")?;
+                if let Some(depth) = limits.depth {
+                    write!(f, " depth {depth}")?;
+                }
+                if let Some(movetime) = limits.movetime {
+                    write!(f, " movetime {}", movetime.as_millis())?;
+                }
+                if let Some(iterations) = limits.iterations {
+                    write!(f, " iterations {iterations}")?;
+                }
+
+                Ok(())
+            }
+            Command::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Id {
+        key: String,
+        value: String,
+    },
+    HexyOk,
+    ReadyOk,
+    Info(String),
+    /// The move as the engine wrote it, None when it has no legal moves.
+    BestMove(Option<String>),
+}
+
+impl Reply {
+    /// Parses a line, unknown replies are None.
+    pub fn parse(line: &str) -> Option<Reply> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+        match name {
+            "id" => {
+                let (key, value) = rest.trim().split_once(' ')?;
+                Some(Reply::Id {
+                    key: key.to_string(),
+                    value: value.trim().to_string(),
+                })
+            }
+            "hexyok" => Some(Reply::HexyOk),
+            "readyok" => Some(Reply::ReadyOk),
+            "info" => Some(Reply::Info(rest.to_string())),
+            "bestmove" => match rest.split_whitespace().next()? {
+                "none" => Some(Reply::BestMove(None)),
+                mv => Some(Reply::BestMove(Some(mv.to_string()))),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reply::Id { key, value } => write!(f, "id {key} {value}"),
+            Reply::HexyOk => write!(f, "hexyok"),
+            Reply::ReadyOk => write!(f, "readyok"),
+            Reply::Info(text) => write!(f, "info {text}"),
+            Reply::BestMove(Some(mv)) => write!(f, "bestmove {mv}"),
+            Reply::BestMove(None) => write!(f, "bestmove none"),
+        }
+    }
+}
+
+/// Answers commands from `input` on `output` until `quit` or the end of the input.
+/// `make_engine` makes the engine for every `go`.
+pub fn serve(
+    name: &str,
+    input: impl BufRead,
+    mut output: impl Write,
+    mut make_engine: impl FnMut(&GoLimits) -> Result<Box<dyn Engine + Send>, ProtocolError>,
+) -> io::Result<()> {
+    let mut board = None;
+
+    for line in input.lines() {
+        let replies = match Command::parse(&line?) {
+            Ok(Some(Command::Hexy)) => vec![
+                Reply::Id {
+                    key: "name".into(),
+                    value: name.into(),
+                },
+                Reply::HexyOk,
+            ],
+            Ok(Some(Command::IsReady)) => vec![Reply::ReadyOk],
+            Ok(Some(Command::NewGame)) => {
+                board = None;
+                Vec::new()
+            }
+            Ok(Some(Command::Position(position))) => {
+                board = Some(position);
+                Vec::new()
+            }
+            Ok(Some(Command::Go(limits))) => match (&board, make_engine(&limits)) {
+                (_, Err(error)) => {
+                    vec![Reply::Info(format!("error {error}")), Reply::BestMove(None)]
+                }
+                (Some(board), Ok(mut engine)) => {
+                    let mv = engine.choose_move(board);
+                    vec![Reply::BestMove(mv.map(|mv| mv.to_string()))]
+                }
+                (None, Ok(_)) => vec![Reply::BestMove(None)],
+            },
+            Ok(Some(Command::Quit)) => break,
+            Ok(None) => Vec::new(),
+            Err(error) => vec![Reply::Info(format!("error {error}"))],
+        };
+
+        for reply in replies {
+            writeln!(output, "{reply}")?;
+        }
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::hexy::engine::protocol::{serve, Command, GoLimits, ProtocolError, Reply};
+    use crate::hexy::engine::EngineKind;
+    use crate::hexy::moves::{moves, parse_move, starting_position};
+
+    #[test]
+    fn test_commands_round_trip() {
+        let lines = [
+            "hexy",
+            "isready",
+            "newgame",
+            "position 3 a0a21/a2a32/5/2b3b2/1b2b0 a 2",
+            "go",
+            "go depth 3 movetime 250 iterations 1000",
+            "quit",
+        ];
+        for line in lines {
+            let command = Command::parse(line).unwrap().unwrap();
+            assert_eq!(line, command.to_string());
+        }
+
+        assert!(Command::parse("setoption name x").unwrap().is_none());
+        assert_eq!(
+            Some(Duration::from_millis(20)),
+            match Command::parse("go movetime 20 ponder 1").unwrap() {
+                Some(Command::Go(limits)) => limits.movetime,
+                _ => None,
+            }
+        );
+        assert_eq!(
+            ProtocolError::InvalidArgument("depth".into()),
+            Command::parse("go depth x").unwrap_err()
+        );
+        assert!(matches!(
+            Command::parse("position 3 a0"),
+            Err(ProtocolError::InvalidPosition(_))
+        ));
+    }
+
+    #[test]
+    fn test_replies_round_trip() {
+        for line in [
+            "id name Hexy bot",
+            "hexyok",
+            "readyok",
+            "info depth 3 score 12",
+            "bestmove d2-e3",
+            "bestmove none",
+        ] {
+            assert_eq!(line, Reply::parse(line).unwrap().to_string());
+        }
+
+        assert_eq!(None, Reply::parse("uciok"));
+        assert_eq!(None, Reply::parse("bestmove"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let board = starting_position(3, 2).unwrap();
+        let input = format!(
+            "hexy\nisready\nposition {}\ngo depth 2\nunknown\ngo depth x\nnewgame\ngo\nquit\nisready\n",
+            board.to_notation()
+        );
+        let mut output = Vec::new();
+        serve("Test", input.as_bytes(), &mut output, |limits| {
+            EngineKind::AlphaBeta.create(limits, 0)
+        })
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(6, lines.len(), "{output}");
+        assert_eq!(["id name Test", "hexyok", "readyok"], lines[..3]);
+
+        let mv = lines[3].strip_prefix("bestmove ").unwrap();
+        assert!(moves(&board, 0).contains(&parse_move(&board, mv).unwrap()));
+        assert!(lines[4].starts_with("info error"));
+        assert_eq!("bestmove none", lines[5]);
+    }
+
+    #[test]
+    fn test_built_in_engines() {
+        let board = starting_position(3, 2).unwrap();
+        let depth = GoLimits {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let iterations = GoLimits {
+            iterations: Some(50),
+            ..Default::default()
+        };
+
+        // Each engine with its own limit, and with only the limit of the other engine, which it
+        // ignores and thinks for the default time instead of forever.
+        for (kind, limits) in [
+            (EngineKind::AlphaBeta, depth),
+            (EngineKind::AlphaBeta, iterations),
+            (EngineKind::Mcts, iterations),
+            (EngineKind::Mcts, depth),
+        ] {
+            let mv = kind
+                .create(&limits, 1)
+                .unwrap()
+                .choose_move(&board)
+                .unwrap();
+            assert!(moves(&board, 0).contains(&mv), "{kind:?} {limits:?}");
+        }
+    }
+}
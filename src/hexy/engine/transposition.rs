@@ -0,0 +1,91 @@
+//! A cache of what engines learned about positions, by their Zobrist hash.
+
+/// A table of a fixed amount of slots, a position can only be in the slot its hash points to.
+/// Storing a position replaces whatever was in its slot, so the table never grows.
+#[derive(Debug, Clone)]
+pub struct TranspositionTable<T> {
+    slots: Vec<Option<(u64, T)>>,
+    used: usize,
+}
+
+impl<T> TranspositionTable<T> {
+    /// A table with room for `capacity` positions, rounded down to a power of two.
+    pub fn new(capacity: usize) -> TranspositionTable<T> {
+        let capacity = capacity.max(1);
+        let capacity = 1 << (usize::BITS - 1 - capacity.leading_zeros());
+
+        TranspositionTable {
+            slots: std::iter::repeat_with(|| None).take(capacity).collect(),
+            used: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The amount of slots in use.
+    pub fn len(&self) -> usize {
+        self.used
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used == 0
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&T> {
+        match &self.slots[self.index(hash)] {
+            Some((stored, value)) if *stored == hash => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, hash: u64, value: T) {
+        let index = self.index(hash);
+        if self.slots[index].is_none() {
+            self.used += 1;
+        }
+
+        self.slots[index] = Some((hash, value));
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.used = 0;
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & (self.slots.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::engine::transposition::TranspositionTable;
+
+    #[test]
+    fn test_capacity_is_a_power_of_two() {
+        assert_eq!(1, TranspositionTable::<u8>::new(0).capacity());
+        assert_eq!(8, TranspositionTable::<u8>::new(8).capacity());
+        assert_eq!(8, TranspositionTable::<u8>::new(15).capacity());
+    }
+
+    #[test]
+    fn test_insert_get_and_replace() {
+        let mut table = TranspositionTable::new(4);
+        table.insert(1, "one");
+        table.insert(2, "two");
+        assert_eq!(Some(&"one"), table.get(1));
+        assert_eq!(None, table.get(5));
+        assert_eq!(2, table.len());
+
+        table.insert(5, "five");
+        assert_eq!(Some(&"five"), table.get(5));
+        assert_eq!(None, table.get(1));
+        assert_eq!(2, table.len());
+
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(None, table.get(2));
+    }
+}
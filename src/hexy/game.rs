@@ -0,0 +1,1772 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use rocket::figment::Figment;
+use rocket::tokio::sync::broadcast;
+use rocket::{warn, FromForm};
+use serde::Serialize;
+use tera::Context;
+
+use crate::storage::{
+    MemoryStore, SavedComputer, SavedGame, SavedMove, SavedSeat, SharedStore, StoreError,
+};
+use crate::util::get_template;
+
+use super::clock::{clock_text, Clock};
+use super::engine::external::ExternalEngines;
+use super::engine::protocol::GoLimits;
+use super::engine::EngineKind;
+use super::hex_board::{BoardView, HexBoard, Renderer};
+use super::hexagon::{HexId, HexState};
+use super::lobby::{Player, PlayerId, Seats, TimeControl, Variant};
+use super::moves::{find_move, moves, parse_move, status, teams_in_play, GameStatus, Move};
+use super::notation::team_letter;
+use super::rating::Ratings;
+use super::record::{GameRecord, RecordedMove, VARIANT};
+
+pub type GameId = u64;
+
+/// How long a game is kept without anyone looking at it, when not configured otherwise.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// A game is a draw when the same position, with the same side to move, comes up this often.
+pub const REPETITIONS_FOR_DRAW: usize = 3;
+
+/// How many updates a slow listener can fall behind before it misses some.
+const UPDATE_CAPACITY: usize = 16;
+
+/// What the pages following a game are told about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameUpdate {
+    /// The game changed, this is its new revision.
+    Changed(u64),
+    /// Spectators came or went, this many are watching now. The game itself is the same.
+    Spectators(usize),
+}
+
+/// How long the computer thinks about a move.
+pub const COMPUTER_TIME: Duration = Duration::from_secs(1);
+
+/// The amount of letters in a watch token, too many for anyone to guess one.
+const WATCH_TOKEN_LENGTH: usize = 24;
+
+/// The choices made when creating a game.
+#[derive(Debug, Clone, FromForm)]
+pub struct GameSettings {
+    pub size: u32,
+    #[field(default = 2)]
+    pub teams: u32,
+    /// Whether the computer plays every team but the first.
+    #[field(default = false)]
+    pub computer: bool,
+    /// The name of the engine the computer plays with, see `EngineKind::from_name`.
+    #[field(default = String::from("alpha-beta"))]
+    pub engine: String,
+    #[field(default = Variant::Standard)]
+    pub variant: Variant,
+    /// The minutes every team has for the game, 0 for no time limit.
+    #[field(default = 0)]
+    pub minutes: u32,
+    /// The seconds a team gains with every move.
+    #[field(default = 0)]
+    pub increment: u32,
+    /// The seconds a team can think about every move before its clock runs.
+    #[field(default = 0)]
+    pub delay: u32,
+}
+
+/// The teams the server plays itself, with which engine and how long it thinks about a move.
+#[derive(Debug, Clone)]
+pub struct Computer {
+    pub teams: Vec<u32>,
+    pub engine: EngineKind,
+    pub time: Duration,
+}
+
+/// A game in progress on the server.
+#[derive(Debug)]
+pub struct Game {
+    pub id: GameId,
+    pub board: HexBoard,
+    /// The piece the side to move clicked on, waiting for a click on where it should go.
+    pub selected: Option<HexId>,
+    pub computer: Option<Computer>,
+    pub seats: Seats,
+    pub variant: Variant,
+    /// Whether the game is shown in the lobby, for others to join.
+    pub listed: bool,
+    /// The position the game started from.
+    initial: HexBoard,
+    /// Every move played, including the ones that were undone and can be redone.
+    history: Vec<Move>,
+    /// The amount of moves from `history` that are on the board.
+    ply: usize,
+    /// The hash of the position after every amount of moves from `history`, starting with none.
+    positions: Vec<u64>,
+    /// Comments by the ply they follow, 0 is before the first move.
+    comments: HashMap<usize, String>,
+    /// The record headers the game was imported with, kept when it is exported again.
+    headers: Option<Vec<(String, String)>>,
+    /// Counts the changes to the game, so players can tell whether they saw the latest one.
+    revision: u64,
+    /// Sends every change, and every change of the spectators, to the pages following the game.
+    updates: broadcast::Sender<GameUpdate>,
+    /// The amount of spectators following the game right now.
+    spectators: usize,
+    /// What spectators find the game by. Anyone who knows the id can play for the free teams of
+    /// some games, so the links of spectators never show it.
+    watch_token: String,
+    /// The clocks of the teams, in games with a time control.
+    clock: Option<Clock>,
+    /// The team that ran out of time, which ended the game.
+    flagged: Option<u32>,
+    /// Whether the result was handed out for the ratings already.
+    rated: bool,
+    /// Whether every team had a player when the first move was played. Only such games are
+    /// rated, so nobody is rated for a game they took over halfway or after it ended.
+    seated_at_start: bool,
+    /// Where the game is saved after every change.
+    store: Option<SharedStore>,
+}
+
+/// A seat of a game, as shown to `viewer`.
+#[derive(Debug, Serialize)]
+struct SeatEntry {
+    index: u32,
+    team: char,
+    name: String,
+    computer: bool,
+    /// Whether the viewer sits here.
+    mine: bool,
+    to_move: bool,
+    /// The time left on the clock of the team, in games with a clock.
+    clock: Option<ClockEntry>,
+}
+
+/// A clock as shown to the players, the page counts the running clock down by itself.
+#[derive(Debug, Serialize)]
+struct ClockEntry {
+    text: String,
+    remaining_ms: u128,
+    /// What is left of the delay before the time starts to run.
+    delay_ms: u128,
+    running: bool,
+}
+
+/// A move in the history of a game, as shown to the players.
+#[derive(Debug, Serialize)]
+struct HistoryEntry {
+    ply: usize,
+    team: char,
+    text: String,
+    comment: String,
+    current: bool,
+}
+
+impl Game {
+    pub fn new(id: GameId, board: HexBoard) -> Game {
+        Game {
+            id,
+            initial: board.clone(),
+            positions: vec![board.hash()],
+            seats: Seats::new(board.teams()),
+            board,
+            selected: None,
+            computer: None,
+            variant: Variant::Standard,
+            listed: false,
+            history: Vec::new(),
+            ply: 0,
+            comments: HashMap::new(),
+            headers: None,
+            revision: 0,
+            updates: broadcast::channel(UPDATE_CAPACITY).0,
+            spectators: 0,
+            watch_token: new_watch_token(),
+            clock: None,
+            flagged: None,
+            rated: false,
+            seated_at_start: false,
+            store: None,
+        }
+    }
+
+    /// Continues an imported game after its last move.
+    pub fn from_record(id: GameId, record: GameRecord) -> Game {
+        let mut game = Game::new(id, record.initial);
+        if let Some(comment) = record.comment {
+            game.comments.insert(0, comment);
+        }
+        for RecordedMove { mv, comment } in record.moves {
+            game.play(&mv);
+            if let Some(comment) = comment {
+                game.comments.insert(game.ply, comment);
+            }
+        }
+        game.headers = Some(record.headers);
+
+        game
+    }
+
+    /// The whole game with the moves that were undone, for storage.
+    pub fn to_saved(&self, now: Instant) -> SavedGame {
+        SavedGame {
+            id: self.id,
+            initial: self.initial.to_notation(),
+            moves: self
+                .history
+                .iter()
+                .enumerate()
+                .map(|(i, mv)| SavedMove {
+                    text: mv.to_string(),
+                    comment: self.comments.get(&(i + 1)).cloned(),
+                })
+                .collect(),
+            ply: self.ply,
+            comment: self.comments.get(&0).cloned(),
+            headers: self.headers.clone(),
+            seats: (0..self.seats.teams())
+                .filter_map(|team| {
+                    self.seats.get(team).map(|seat| SavedSeat {
+                        team,
+                        player: seat.player,
+                        name: seat.name.clone(),
+                    })
+                })
+                .collect(),
+            listed: self.listed,
+            variant: self.variant.name().to_string(),
+            clock: self.clock.as_ref().map(|clock| clock.to_saved(now)),
+            flagged: self.flagged,
+            rated: self.rated,
+            seated_at_start: self.seated_at_start,
+            computer: self.computer.as_ref().map(|computer| SavedComputer {
+                engine: computer.engine.name().to_string(),
+                teams: computer.teams.clone(),
+                time_ms: computer.time.as_millis() as u64,
+            }),
+            revision: self.revision,
+            watch_token: self.watch_token.clone(),
+        }
+    }
+
+    /// A game from storage, as it was when it was saved. Fails when the game can not be played
+    /// on, like when the computer played with an engine that is no longer configured.
+    pub fn from_saved(
+        saved: SavedGame,
+        engines: &ExternalEngines,
+        now: Instant,
+    ) -> Result<Game, String> {
+        let initial = HexBoard::from_notation(&saved.initial).map_err(|error| error.to_string())?;
+        let mut game = Game::new(saved.id, initial);
+
+        for SavedMove { text, comment } in saved.moves {
+            let mv = parse_move(&game.board, &text)
+                .ok_or_else(|| format!("{text} is not a legal move after ply {}.", game.ply))?;
+            game.play(&mv);
+            if let Some(comment) = comment {
+                game.comments.insert(game.ply, comment);
+            }
+        }
+        while game.ply > saved.ply && game.undo() {}
+        if let Some(comment) = saved.comment {
+            game.comments.insert(0, comment);
+        }
+
+        for SavedSeat { team, player, name } in saved.seats {
+            game.seats.join(team, player, &name)?;
+        }
+        game.headers = saved.headers;
+        game.listed = saved.listed;
+        game.variant = Variant::from_name(&saved.variant)
+            .ok_or_else(|| format!("There is no variant called {}.", saved.variant))?;
+        game.clock = saved.clock.map(|clock| Clock::from_saved(&clock, now));
+        game.flagged = saved.flagged;
+        game.rated = saved.rated;
+        game.seated_at_start = saved.seated_at_start;
+        game.revision = saved.revision;
+        if !saved.watch_token.is_empty() {
+            game.watch_token = saved.watch_token;
+        }
+        game.computer = saved
+            .computer
+            .map(|computer| {
+                let engine = EngineKind::from_name(&computer.engine, engines)
+                    .ok_or_else(|| format!("There is no engine called {}.", computer.engine))?;
+                Ok::<_, String>(Computer {
+                    teams: computer.teams,
+                    engine,
+                    time: Duration::from_millis(computer.time_ms),
+                })
+            })
+            .transpose()?;
+
+        Ok(game)
+    }
+
+    /// Saves the game in its store, when it has one. A game that can not be saved goes on, it is
+    /// only lost when the server restarts.
+    ///
+    /// The game is written right away, while the caller holds its lock and its worker thread,
+    /// so everyone waiting for the game waits for the database too. A SQLite write of one game
+    /// takes well under a millisecond on a local disk; a slower store would need a writer
+    /// thread of its own.
+    pub fn save(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+
+        if let Err(error) = store.save_game(&self.to_saved(Instant::now())) {
+            warn!("Could not save game {}: {error}", self.id);
+        }
+    }
+
+    /// The game up to the position on the board, moves that were undone are left out.
+    pub fn to_record(&self) -> GameRecord {
+        let mut record = GameRecord::new(self.initial.clone());
+        match &self.headers {
+            Some(headers) => record.headers = headers.clone(),
+            None => {
+                record.set_header(VARIANT, self.variant.name());
+                if let Some(time_control) = self.time_control() {
+                    record.set_header("TimeControl", &time_control.to_string());
+                }
+                for team in 0..self.seats.teams() {
+                    if let Some(seat) = self.seats.get(team) {
+                        record.set_player(team, &seat.name);
+                    }
+                }
+            }
+        }
+        record.comment = self.comments.get(&0).cloned();
+        record.moves = self.history[..self.ply]
+            .iter()
+            .enumerate()
+            .map(|(i, mv)| RecordedMove {
+                mv: mv.clone(),
+                comment: self.comments.get(&(i + 1)).cloned(),
+            })
+            .collect();
+        record.result = self.status();
+
+        record
+    }
+
+    /// The record of the game the first time it is asked for after the game ended, for the
+    /// ratings, with the ids of the players. Imported games, games without a different player
+    /// at every team, and games that did not have all their players before the first move are
+    /// not rated.
+    pub fn finished_record(&mut self) -> Option<GameRecord> {
+        let players: Vec<PlayerId> = (0..self.seats.teams())
+            .map(|team| self.seats.get(team).map(|seat| seat.player))
+            .collect::<Option<_>>()?;
+        let distinct = players
+            .iter()
+            .enumerate()
+            .all(|(i, player)| !players[..i].contains(player));
+
+        if self.rated
+            || self.headers.is_some()
+            || !distinct
+            || !self.seated_at_start
+            || self.status() == GameStatus::Ongoing
+        {
+            return None;
+        }
+
+        self.rated = true;
+        let mut record = self.to_record();
+        for (team, player) in players.into_iter().enumerate() {
+            record.set_player_id(team as u32, player);
+        }
+
+        Some(record)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Receives every update from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<GameUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Tells everyone watching that the game changed, and saves it.
+    pub fn changed(&mut self) {
+        self.revision += 1;
+        // Nobody might be watching, which is fine.
+        self.updates.send(GameUpdate::Changed(self.revision)).ok();
+        self.save();
+    }
+
+    pub fn time_control(&self) -> Option<TimeControl> {
+        self.clock.as_ref().map(Clock::time_control)
+    }
+
+    /// Gives every team a clock, which starts after the first move.
+    pub fn set_time_control(&mut self, time_control: Option<TimeControl>) {
+        self.clock = time_control.map(|time_control| Clock::new(time_control, self.board.teams()));
+    }
+
+    /// Ends the game when the team to move ran out of time, true when it did just now.
+    pub fn update_clock(&mut self, now: Instant) -> bool {
+        let Some(team) = self.clock.as_mut().and_then(|clock| clock.check_flag(now)) else {
+            return false;
+        };
+
+        self.flagged = Some(team);
+        self.selected = None;
+        self.changed();
+        true
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.spectators
+    }
+
+    pub fn watch_token(&self) -> &str {
+        &self.watch_token
+    }
+
+    /// Counts a spectator until the returned `Spectator` is dropped. The count is not a change
+    /// of the game, the pages show it when they update for the next move.
+    pub fn watch(game: &Arc<Mutex<Game>>) -> Spectator {
+        Game::lock(game).add_spectators(1);
+
+        Spectator {
+            game: Arc::downgrade(game),
+        }
+    }
+
+    /// Tells the pages following the game how many spectators there are now. The game is not
+    /// saved, spectators are not part of it.
+    fn add_spectators(&mut self, change: isize) {
+        self.spectators = self.spectators.saturating_add_signed(change);
+        // Nobody might be watching, which is fine.
+        self.updates
+            .send(GameUpdate::Spectators(self.spectators))
+            .ok();
+    }
+
+    /// Locks a shared game, a panic in another request does not make the game unusable.
+    pub fn lock(game: &Mutex<Game>) -> MutexGuard<'_, Game> {
+        game.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// How often the position on the board came up on the way to it, counting itself.
+    pub fn repetitions(&self) -> usize {
+        self.positions[..=self.ply]
+            .iter()
+            .filter(|hash| **hash == self.board.hash())
+            .count()
+    }
+
+    /// The status of the board, or a draw when the position came up too often.
+    /// A team that ran out of time loses, the game is a draw when more than one team is left.
+    pub fn status(&self) -> GameStatus {
+        if let Some(flagged) = self.flagged {
+            let teams: Vec<u32> = teams_in_play(&self.board)
+                .into_iter()
+                .filter(|team| *team != flagged)
+                .collect();
+
+            return match teams.as_slice() {
+                [team] => GameStatus::Won(*team),
+                _ => GameStatus::Draw,
+            };
+        }
+
+        match status(&self.board) {
+            GameStatus::Ongoing if self.repetitions() >= REPETITIONS_FOR_DRAW => GameStatus::Draw,
+            status => status,
+        }
+    }
+
+    pub fn status_text(&self) -> String {
+        if let Some(flagged) = self.flagged {
+            let result = match self.status() {
+                GameStatus::Won(team) => format!("team {} won", team_letter(team)),
+                _ => "draw".to_string(),
+            };
+            return format!("Team {} ran out of time, {result}", team_letter(flagged));
+        }
+
+        match self.status() {
+            GameStatus::Ongoing => format!("Team {} to move", team_letter(self.board.to_move())),
+            GameStatus::Won(team) => format!("Team {} won", team_letter(team)),
+            GameStatus::Draw if self.repetitions() >= REPETITIONS_FOR_DRAW => {
+                "Draw by repetition".to_string()
+            }
+            GameStatus::Draw => "Draw".to_string(),
+        }
+    }
+
+    /// Plays a legal move for the side to move. When earlier moves were undone, the game
+    /// continues from here and the undone moves are forgotten. In games with a clock, the move
+    /// stops the clock of the team and starts the clock of the next one.
+    pub fn play(&mut self, mv: &Move) {
+        if self.ply == 0 {
+            self.seated_at_start =
+                (0..self.seats.teams()).all(|team| self.seats.get(team).is_some());
+        }
+        self.board.apply_move(mv);
+        self.history.truncate(self.ply);
+        self.comments.retain(|ply, _| *ply <= self.ply);
+        self.history.push(mv.clone());
+        self.positions.truncate(self.ply + 1);
+        self.positions.push(self.board.hash());
+        self.ply += 1;
+        self.selected = None;
+
+        let ongoing = self.status() == GameStatus::Ongoing;
+        let to_move = self.board.to_move();
+        if let Some(clock) = &mut self.clock {
+            match ongoing {
+                true => clock.start(to_move, Instant::now()),
+                false => clock.stop(Instant::now()),
+            }
+        }
+    }
+
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn last_move(&self) -> Option<&Move> {
+        self.ply
+            .checked_sub(1)
+            .and_then(|ply| self.history.get(ply))
+    }
+
+    pub fn undo(&mut self) -> bool {
+        if self.ply == 0 {
+            return false;
+        }
+
+        self.ply -= 1;
+        self.board.undo_move(&self.history[self.ply]);
+        self.selected = None;
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(mv) = self.history.get(self.ply) else {
+            return false;
+        };
+
+        self.board.apply_move(mv);
+        self.ply += 1;
+        self.selected = None;
+        true
+    }
+
+    fn is_computer_team(&self, team: u32) -> bool {
+        self.computer
+            .as_ref()
+            .is_some_and(|computer| computer.teams.contains(&team))
+    }
+
+    /// Seats a player at a team that neither the computer nor someone else plays, while the
+    /// game goes on. A player who joins after the first move makes the game unrated.
+    pub fn join(&mut self, team: u32, player: PlayerId, name: &str) -> Result<(), String> {
+        if self.is_computer_team(team) {
+            return Err(format!(
+                "Team {} is played by the computer.",
+                team_letter(team)
+            ));
+        }
+        if self.status() != GameStatus::Ongoing {
+            return Err("The game is over.".into());
+        }
+
+        self.seats.join(team, player, name)?;
+        if self.ply > 0 {
+            self.seated_at_start = false;
+        }
+        Ok(())
+    }
+
+    /// Whether the game is in the lobby and waits for players.
+    pub fn is_open(&self) -> bool {
+        self.listed
+            && self.status() == GameStatus::Ongoing
+            && (0..self.seats.teams())
+                .any(|team| self.seats.get(team).is_none() && !self.is_computer_team(team))
+    }
+
+    /// Whether only seated players may play. Games that are not in the lobby and that nobody
+    /// sat down at are played by whoever has them open, like on a single screen.
+    fn needs_seats(&self) -> bool {
+        self.listed || !self.seats.is_empty()
+    }
+
+    /// Fails when the player does not sit at the team to move, in games that need seats.
+    pub fn check_turn(&self, player: PlayerId) -> Result<(), String> {
+        let to_move = self.board.to_move();
+        match self.seats.get(to_move) {
+            Some(seat) if seat.player != player => Err(format!(
+                "It is team {}'s turn, which {} plays.",
+                team_letter(to_move),
+                seat.name
+            )),
+            None if self.needs_seats() => Err(format!(
+                "Team {} has no player yet, take its seat to move it.",
+                team_letter(to_move)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails when the player can not take moves back or forward, which nobody can in games
+    /// with a clock.
+    pub fn check_takeback(&self, player: PlayerId) -> Result<(), String> {
+        self.check_player(player)?;
+        match self.clock {
+            Some(_) => Err("Moves can not be taken back in a game with a clock.".into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails when the game needs seats and the player does not sit at it.
+    pub fn check_player(&self, player: PlayerId) -> Result<(), String> {
+        match !self.needs_seats() || self.seats.is_seated(player) {
+            true => Ok(()),
+            false => Err("Only the players of this game can do that.".into()),
+        }
+    }
+
+    pub fn is_computer_turn(&self) -> bool {
+        self.is_computer_team(self.board.to_move()) && self.status() == GameStatus::Ongoing
+    }
+
+    /// Plays for the computer until it is a player's turn or the game is over.
+    /// The engine thinks on a copy of the board without holding the game's lock, so everyone
+    /// else can use the game meanwhile. When the game changed while it thought, the move is
+    /// dropped, whoever changed the game lets the computer answer that instead.
+    /// Fails when the engine cannot start or does not come up with a move.
+    pub fn play_computer_moves(game: &Mutex<Game>) -> Result<(), String> {
+        let (computer, id) = {
+            let game = Game::lock(game);
+            match &game.computer {
+                Some(computer) if game.is_computer_turn() => (computer.clone(), game.id),
+                _ => return Ok(()),
+            }
+        };
+
+        let limits = GoLimits {
+            movetime: Some(computer.time),
+            ..Default::default()
+        };
+        let mut engine = computer
+            .engine
+            .create(&limits, id)
+            .map_err(|error| format!("The computer cannot play, {error}."))?;
+        let name = computer.engine.name();
+
+        loop {
+            let (board, revision) = {
+                let game = Game::lock(game);
+                if !game.is_computer_turn() {
+                    return Ok(());
+                }
+                (game.board.clone(), game.revision)
+            };
+
+            let mv = engine
+                .choose_move(&board)
+                .ok_or_else(|| format!("The {name} engine did not play a move."))?;
+
+            let mut game = Game::lock(game);
+            if game.revision != revision {
+                return Ok(());
+            }
+            game.play(&mv);
+            game.changed();
+        }
+    }
+
+    /// Undoes a move together with the computer's replies to it, so a player is to move again.
+    pub fn undo_turn(&mut self) -> bool {
+        if !self.undo() {
+            return false;
+        }
+
+        while self.is_computer_turn() && self.undo() {}
+        true
+    }
+
+    /// Redoes a move together with the computer's replies to it.
+    pub fn redo_turn(&mut self) -> bool {
+        if !self.redo() {
+            return false;
+        }
+
+        while self.is_computer_turn() && self.redo() {}
+        true
+    }
+
+    /// Undoes or redoes moves until `ply` moves of the history are on the board.
+    pub fn jump_to(&mut self, ply: usize) -> Result<(), String> {
+        if ply > self.history.len() {
+            return Err(format!("The game has only {} moves.", self.history.len()));
+        }
+
+        while self.ply > ply && self.undo() {}
+        while self.ply < ply && self.redo() {}
+
+        Ok(())
+    }
+
+    /// The position after the first `ply` moves of the history, without changing the game.
+    pub fn board_at(&self, ply: usize) -> Option<HexBoard> {
+        let mut board = self.initial.clone();
+        for mv in self.history.get(..ply)? {
+            board.apply_move(mv);
+        }
+
+        Some(board)
+    }
+
+    /// Renders the position after `ply` moves, read only and with the move that led to it.
+    /// Players can continue the game from there, spectators can not.
+    pub fn preview_html(&self, ply: usize, spectating: bool, renderer: Renderer) -> Option<String> {
+        let board = self.board_at(ply)?;
+        let last_move = ply.checked_sub(1).and_then(|ply| self.history.get(ply));
+        let view = BoardView {
+            renderer,
+            last_move: last_move.map(|mv| (mv.from.clone(), mv.to.clone())),
+            ..Default::default()
+        };
+
+        let mut context = Context::new();
+        context.insert("game_id", &self.id);
+        context.insert("ply", &ply);
+        context.insert(
+            "last_move",
+            &last_move.map(Move::to_string).unwrap_or_default(),
+        );
+        context.insert("board", &board.render(&view));
+        context.insert("spectating", &spectating);
+
+        get_template("templates/hexy/preview.html", Some(context))
+    }
+
+    /// Handles a click on a hex: the first click selects a piece of the side to move, the second
+    /// click moves it there. Returns why nothing happened when the click does not fit.
+    pub fn click(&mut self, hex_id: &HexId) -> Result<(), String> {
+        if self.status() != GameStatus::Ongoing {
+            return Err("The game is over.".into());
+        }
+
+        let to_move = self.board.to_move();
+        let state = self
+            .board
+            .get(hex_id)
+            .map(|hexagon| hexagon.state)
+            .ok_or_else(|| format!("{} is not on the board.", hex_id.to_notation()))?;
+
+        if self.selected.as_ref() == Some(hex_id) {
+            self.selected = None;
+            return Ok(());
+        }
+
+        match (state, &self.selected) {
+            (HexState::Piece { team, .. }, _) if team == to_move => {
+                let can_move = moves(&self.board, to_move)
+                    .iter()
+                    .any(|mv| &mv.from == hex_id);
+                if !can_move {
+                    return Err(format!(
+                        "The piece on {} can not move.",
+                        hex_id.to_notation()
+                    ));
+                }
+
+                self.selected = Some(hex_id.clone());
+                Ok(())
+            }
+            (_, Some(selected)) => {
+                let mv = find_move(&self.board, selected, hex_id).ok_or_else(|| {
+                    format!(
+                        "{} can not move to {}.",
+                        selected.to_notation(),
+                        hex_id.to_notation()
+                    )
+                })?;
+
+                self.play(&mv);
+                Ok(())
+            }
+            (HexState::Piece { team, .. }, None) => Err(format!(
+                "That piece belongs to team {}, it is team {}'s turn.",
+                team_letter(team),
+                team_letter(to_move)
+            )),
+            (HexState::Free, None) => Err(format!(
+                "Select a piece of team {} first.",
+                team_letter(to_move)
+            )),
+        }
+    }
+
+    fn history_entries(&self) -> Vec<HistoryEntry> {
+        let mut board = self.initial.clone();
+        self.history
+            .iter()
+            .enumerate()
+            .map(|(i, mv)| {
+                let entry = HistoryEntry {
+                    ply: i + 1,
+                    team: team_letter(board.to_move()),
+                    text: mv.to_string(),
+                    comment: self.comments.get(&(i + 1)).cloned().unwrap_or_default(),
+                    current: i + 1 == self.ply,
+                };
+
+                board.apply_move(mv);
+                entry
+            })
+            .collect()
+    }
+
+    /// The board with the selected piece, where it can go and the last move marked.
+    /// Spectators can not click on it.
+    pub fn view(&self, spectating: bool) -> BoardView {
+        let mut view = BoardView {
+            click_url: (!spectating).then(|| format!("/hexy/games/{}/click", self.id)),
+            selected: self.selected.clone(),
+            last_move: self.last_move().map(|mv| (mv.from.clone(), mv.to.clone())),
+            ..Default::default()
+        };
+
+        if let Some(selected) = &self.selected {
+            for mv in moves(&self.board, self.board.to_move()) {
+                if &mv.from != selected {
+                    continue;
+                }
+
+                match mv.captured {
+                    HexState::Free => view.targets.push(mv.to),
+                    HexState::Piece { .. } => view.captures.push(mv.to),
+                }
+            }
+        }
+
+        view
+    }
+
+    fn clock_entry(&self, team: u32, now: Instant) -> Option<ClockEntry> {
+        let clock = self.clock.as_ref()?;
+        let remaining = clock.remaining(team, now);
+        let running = clock.running() == Some(team);
+
+        Some(ClockEntry {
+            text: clock_text(remaining),
+            remaining_ms: remaining.as_millis(),
+            delay_ms: match running {
+                true => clock.delay_left(now).as_millis(),
+                false => 0,
+            },
+            running,
+        })
+    }
+
+    fn seat_entries(&self, viewer: Option<&Player>) -> Vec<SeatEntry> {
+        let now = Instant::now();
+        (0..self.seats.teams())
+            .map(|team| {
+                let seat = self.seats.get(team);
+                SeatEntry {
+                    index: team,
+                    team: team_letter(team),
+                    name: seat.map(|seat| seat.name.clone()).unwrap_or_default(),
+                    computer: self.is_computer_team(team),
+                    mine: seat
+                        .is_some_and(|seat| viewer.is_some_and(|viewer| viewer.id == seat.player)),
+                    to_move: team == self.board.to_move(),
+                    clock: self.clock_entry(team, now),
+                }
+            })
+            .collect()
+    }
+
+    /// Renders the game as `viewer` sees it, with a message for them when one is given.
+    pub fn to_html(&self, viewer: &Player, message: Option<&str>) -> String {
+        self.render(Some(viewer), viewer.renderer, message)
+    }
+
+    /// Renders the game without anything to play with.
+    pub fn to_spectator_html(&self, renderer: Renderer) -> String {
+        self.render(None, renderer, None)
+    }
+
+    fn render(&self, viewer: Option<&Player>, renderer: Renderer, message: Option<&str>) -> String {
+        let spectating = viewer.is_none();
+        let view = BoardView {
+            renderer,
+            ..self.view(spectating)
+        };
+
+        let mut context = Context::new();
+        context.insert("game_id", &self.id);
+        context.insert("watch_token", &self.watch_token);
+        // Read only links work with either, spectators only get the token.
+        context.insert(
+            "game_key",
+            &match spectating {
+                true => self.watch_token.clone(),
+                false => self.id.to_string(),
+            },
+        );
+        context.insert("revision", &self.revision);
+        context.insert("status", &self.status_text());
+        context.insert("message", message.unwrap_or_default());
+        context.insert("board", &self.board.render(&view));
+        context.insert("notation", &self.board.to_notation());
+        context.insert("ply", &self.ply);
+        context.insert("can_undo", &(self.ply > 0 && self.clock.is_none()));
+        context.insert(
+            "can_redo",
+            &(self.ply < self.history.len() && self.clock.is_none()),
+        );
+        context.insert("history", &self.history_entries());
+        context.insert("seats", &self.seat_entries(viewer));
+        context.insert("spectating", &spectating);
+        context.insert("spectators", &self.spectators);
+        context.insert(
+            "viewer_name",
+            &viewer
+                .and_then(|viewer| viewer.name.clone())
+                .unwrap_or_default(),
+        );
+        context.insert(
+            "time_control",
+            &self.time_control().map(|time| time.to_string()),
+        );
+
+        get_template("templates/hexy/game.html", Some(context))
+            .expect("Failed to load game template.")
+    }
+}
+
+/// A spectator of a game, who stops being counted when this is dropped.
+#[derive(Debug)]
+pub struct Spectator {
+    game: Weak<Mutex<Game>>,
+}
+
+impl Drop for Spectator {
+    fn drop(&mut self) {
+        if let Some(game) = self.game.upgrade() {
+            Game::lock(&game).add_spectators(-1);
+        }
+    }
+}
+
+struct GameEntry {
+    game: Arc<Mutex<Game>>,
+    /// The watch token of the game, so finding it does not wait for the lock of every game.
+    watch_token: String,
+    last_access: Instant,
+}
+
+/// A token of lowercase letters, which is never mistaken for a game id.
+fn new_watch_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..WATCH_TOKEN_LENGTH)
+        .map(|_| rng.gen_range('a'..='z'))
+        .collect()
+}
+
+/// All games on the server, managed by Rocket.
+/// Every game has its own lock, so players of different games do not wait on each other.
+pub struct Games {
+    games: Mutex<HashMap<GameId, GameEntry>>,
+    next_id: AtomicU64,
+    timeout: Duration,
+    /// The ratings of the players, from the rated games that ended on the server.
+    ratings: Mutex<Ratings>,
+    /// Where every game and the record of every rated game is saved, so games and ratings
+    /// survive a restart.
+    store: SharedStore,
+}
+
+impl Games {
+    /// Games that are kept in memory only.
+    pub fn new(timeout: Duration) -> Games {
+        Games::with_store(timeout, Arc::new(MemoryStore::default()))
+    }
+
+    pub fn with_store(timeout: Duration, store: SharedStore) -> Games {
+        Games {
+            games: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            timeout,
+            ratings: Mutex::new(Ratings::default()),
+            store,
+        }
+    }
+
+    /// Reads the idle timeout in seconds from the `hexy_game_timeout` config value. Continues
+    /// the games and the ratings in the store.
+    pub fn from_figment(
+        figment: &Figment,
+        store: SharedStore,
+        engines: &ExternalEngines,
+    ) -> Result<Games, StoreError> {
+        let timeout = figment
+            .extract_inner::<u64>("hexy_game_timeout")
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TIMEOUT);
+
+        let games = Games::with_store(timeout, store);
+        games.load(engines)?;
+
+        Ok(games)
+    }
+
+    /// Continues the games in the store, as if they were just looked at, and computes the
+    /// ratings again from the rated games in it. Games that can not be continued are left in
+    /// the store, in case a later version can.
+    pub fn load(&self, engines: &ExternalEngines) -> Result<(), StoreError> {
+        *self.ratings() = load_ratings(self.store.rated_records()?);
+
+        let now = Instant::now();
+        let mut games = self.lock();
+        for saved in self.store.games()? {
+            let id = saved.id;
+            // Even a game that is skipped keeps its id, so no new game replaces it in the store.
+            self.next_id.fetch_max(id + 1, Ordering::Relaxed);
+            let mut game = match Game::from_saved(saved, engines, now) {
+                Ok(game) => game,
+                Err(error) => {
+                    warn!("Skipping saved game {id}: {error}");
+                    continue;
+                }
+            };
+            game.store = Some(self.store.clone());
+
+            games.insert(
+                id,
+                GameEntry {
+                    watch_token: game.watch_token.clone(),
+                    game: Arc::new(Mutex::new(game)),
+                    last_access: now,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn ratings(&self) -> MutexGuard<'_, Ratings> {
+        self.ratings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Fails when the name belongs to another player in the ratings, so nobody sits down under
+    /// the name of someone else.
+    pub fn check_name(&self, name: &str, player: PlayerId) -> Result<(), String> {
+        match self.ratings().may_use(name, player) {
+            true => Ok(()),
+            false => Err(format!("The name {name} belongs to another player.")),
+        }
+    }
+
+    /// Rates the game when it just ended, and keeps its record with the other rated games.
+    pub fn rate(&self, game: &mut Game) {
+        let Some(record) = game.finished_record() else {
+            return;
+        };
+        game.save();
+
+        let mut ratings = self.ratings();
+        let text = record.to_text();
+        if !ratings.add(record) {
+            return;
+        }
+
+        if let Err(error) = self.store.save_rated_record(&text) {
+            warn!("Could not keep the record of game {}: {error}", game.id);
+        }
+    }
+
+    pub fn create(&self, board: HexBoard) -> Arc<Mutex<Game>> {
+        self.create_with(|id| Game::new(id, board))
+    }
+
+    /// Adds the game made by `make_game` from a new id.
+    pub fn create_with(&self, make_game: impl FnOnce(GameId) -> Game) -> Arc<Mutex<Game>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut game = make_game(id);
+        game.store = Some(self.store.clone());
+        game.save();
+        let watch_token = game.watch_token.clone();
+        let game = Arc::new(Mutex::new(game));
+
+        let mut games = self.lock();
+        self.remove_expired(&mut games, Instant::now());
+        games.insert(
+            id,
+            GameEntry {
+                game: game.clone(),
+                watch_token,
+                last_access: Instant::now(),
+            },
+        );
+
+        game
+    }
+
+    /// Finds a game that has not expired, and keeps it alive for another timeout.
+    pub fn get(&self, id: GameId) -> Option<Arc<Mutex<Game>>> {
+        self.get_at(id, Instant::now())
+    }
+
+    /// Finds a game by its watch token, and keeps it alive like `get`.
+    pub fn watched(&self, token: &str) -> Option<Arc<Mutex<Game>>> {
+        let now = Instant::now();
+        let mut games = self.lock();
+        self.remove_expired(&mut games, now);
+
+        let entry = games
+            .values_mut()
+            .find(|entry| entry.watch_token == token)?;
+        entry.last_access = now;
+        Some(entry.game.clone())
+    }
+
+    /// Finds a game like `get`, as if it is `now`.
+    fn get_at(&self, id: GameId, now: Instant) -> Option<Arc<Mutex<Game>>> {
+        let mut games = self.lock();
+        self.remove_expired(&mut games, now);
+
+        let entry = games.get_mut(&id)?;
+        entry.last_access = now;
+        Some(entry.game.clone())
+    }
+
+    /// All games that have not expired, without keeping them alive.
+    pub fn all(&self) -> Vec<Arc<Mutex<Game>>> {
+        let mut games = self.lock();
+        self.remove_expired(&mut games, Instant::now());
+        games.values().map(|entry| entry.game.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        let mut games = self.lock();
+        self.remove_expired(&mut games, Instant::now());
+        games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<GameId, GameEntry>> {
+        // A panic while holding the lock can not leave the map itself in a broken state.
+        self.games
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Forgets the games nobody looked at for too long before `now`, in the store as well.
+    fn remove_expired(&self, games: &mut HashMap<GameId, GameEntry>, now: Instant) {
+        games.retain(|id, entry| {
+            let expired = now.saturating_duration_since(entry.last_access) >= self.timeout;
+            if expired {
+                if let Err(error) = self.store.delete_game(*id) {
+                    warn!("Could not delete game {id}: {error}");
+                }
+            }
+
+            !expired
+        });
+    }
+}
+
+/// Computes the ratings again from the records of the rated games, in the order they were
+/// rated. Records that can not be read are left out.
+fn load_ratings(records: Vec<String>) -> Ratings {
+    Ratings::from_records(records.iter().enumerate().filter_map(|(index, text)| {
+        let record = GameRecord::parse(text);
+        if let Err(error) = &record {
+            warn!("Skipping rated game {}: {error}", index + 1);
+        }
+
+        record.ok()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use crate::hexy::engine::external::ExternalEngines;
+    use crate::hexy::engine::EngineKind;
+    use crate::hexy::game::{Computer, Game, GameUpdate, Games};
+    use crate::hexy::hex_board::{BoardView, HexBoard, Renderer};
+    use crate::hexy::hexagon::HexId;
+    use crate::hexy::lobby::{Player, TimeControl};
+    use crate::hexy::moves::{moves, parse_move, starting_position, GameStatus};
+    use crate::hexy::record::GameRecord;
+    use crate::storage::{MemoryStore, SharedStore};
+
+    fn click(game: &mut Game, hex: &str) -> Result<(), String> {
+        game.click(&HexId::from_notation(hex).unwrap())
+    }
+
+    fn viewer() -> Player {
+        Player {
+            id: 1,
+            name: None,
+            renderer: Renderer::default(),
+        }
+    }
+
+    #[test]
+    fn test_click_select_then_move() {
+        let board = HexBoard::from_notation("3 a0a21/a2a32/5/2b3b2/1b2b0 a 2").unwrap();
+        let mut game = Game::new(1, board);
+
+        assert!(click(&mut game, "c1").is_err());
+        assert!(click(&mut game, "e5").is_err());
+        assert!(click(&mut game, "a1").is_err());
+        assert_eq!(None, game.selected);
+
+        click(&mut game, "a2").unwrap();
+        assert_eq!(HexId::from_notation("a2"), game.selected);
+        assert!(click(&mut game, "c1").is_err());
+        assert_eq!(HexId::from_notation("a2"), game.selected);
+
+        click(&mut game, "b1").unwrap();
+        assert_eq!(HexId::from_notation("b1"), game.selected);
+        click(&mut game, "b1").unwrap();
+        assert_eq!(None, game.selected);
+
+        click(&mut game, "a2").unwrap();
+        click(&mut game, "a3").unwrap();
+        assert_eq!(None, game.selected);
+        assert_eq!("3 a01a2/a2a32/5/2b3b2/1b2b0 b 2", game.board.to_notation());
+    }
+
+    #[test]
+    fn test_undo_redo_and_branch() {
+        let mut game = Game::new(1, starting_position(4, 2).unwrap());
+        let notations: Vec<String> = (0..4)
+            .map(|_| {
+                let notation = game.board.to_notation();
+                let mv = moves(&game.board, game.board.to_move()).remove(0);
+                game.play(&mv);
+                notation
+            })
+            .collect();
+
+        assert_eq!(4, game.ply());
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(notations[2], game.board.to_notation());
+        assert_eq!(4, game.history().len());
+
+        assert!(game.redo());
+        assert_eq!(notations[3], game.board.to_notation());
+
+        game.jump_to(0).unwrap();
+        assert_eq!(notations[0], game.board.to_notation());
+        assert!(!game.undo());
+        assert!(game.jump_to(5).is_err());
+
+        game.jump_to(4).unwrap();
+        assert!(!game.redo());
+
+        game.jump_to(1).unwrap();
+        let branch = moves(&game.board, game.board.to_move()).pop().unwrap();
+        game.play(&branch);
+        assert_eq!(2, game.ply());
+        assert_eq!(2, game.history().len());
+        assert_eq!(Some(&branch), game.last_move());
+    }
+
+    #[test]
+    fn test_board_at_matches_history() {
+        let mut game = Game::new(1, starting_position(3, 2).unwrap());
+        let mut played = Vec::new();
+        for _ in 0..6 {
+            let mv = moves(&game.board, game.board.to_move()).remove(0);
+            game.play(&mv);
+            played.push(mv);
+        }
+
+        game.jump_to(2).unwrap();
+        for ply in 0..=6 {
+            let mut replayed = starting_position(3, 2).unwrap();
+            for mv in &played[..ply] {
+                replayed.apply_move(mv);
+            }
+            let view = BoardView {
+                last_move: ply
+                    .checked_sub(1)
+                    .map(|ply| (played[ply].from.clone(), played[ply].to.clone())),
+                ..Default::default()
+            };
+
+            let board = game.board_at(ply).unwrap();
+            assert_eq!(replayed.to_notation(), board.to_notation());
+            let preview = game.preview_html(ply, false, Renderer::Html).unwrap();
+            assert!(preview.contains(&replayed.render(&view)), "{ply}");
+        }
+        assert!(game.board_at(7).is_none());
+        assert_eq!(2, game.ply());
+    }
+
+    #[test]
+    fn test_view_marks_targets_and_captures() {
+        let board = HexBoard::from_notation("3 1a2a1/2b31/1b13/b03/a02 a 2").unwrap();
+        let mut game = Game::new(1, board);
+        let hex = |notation| HexId::from_notation(notation).unwrap();
+
+        let view = game.view(false);
+        assert_eq!(None, view.selected);
+        assert!(view.targets.is_empty());
+
+        click(&mut game, "a2").unwrap();
+        let view = game.view(false);
+        assert_eq!(Some(hex("a2")), view.selected);
+        assert_eq!(vec![hex("b2"), hex("a1")], view.targets);
+        assert_eq!(vec![hex("c2")], view.captures);
+        assert!(view.marks(&hex("c2")).capture);
+        assert!(!view.marks(&hex("b3")).capture);
+
+        click(&mut game, "c2").unwrap();
+        let view = game.view(false);
+        assert_eq!(None, view.selected);
+        assert_eq!(Some((hex("a2"), hex("c2"))), view.last_move);
+        assert!(view.marks(&hex("a2")).last_move);
+    }
+
+    #[test]
+    fn test_seats() {
+        let mut game = Game::new(1, starting_position(4, 3).unwrap());
+        game.computer = Some(Computer {
+            teams: vec![2],
+            engine: EngineKind::AlphaBeta,
+            time: Duration::from_millis(20),
+        });
+        game.set_time_control(TimeControl::new(5, 2, 0));
+        assert!(game.check_turn(7).is_ok());
+        assert!(game.check_player(7).is_ok());
+
+        game.listed = true;
+        assert!(game.check_turn(7).is_err());
+        assert!(game.check_player(7).is_err());
+
+        game.join(0, 7, "Ann").unwrap();
+        assert!(game.join(2, 8, "Bob").is_err());
+        assert!(game.is_open());
+        assert_eq!(
+            Err("It is team a's turn, which Ann plays.".into()),
+            game.check_turn(8)
+        );
+        assert!(game.check_player(8).is_err());
+
+        game.join(1, 8, "Bob").unwrap();
+        assert!(!game.is_open());
+        assert!(game.check_turn(7).is_ok());
+
+        let record = game.to_record();
+        assert_eq!(Some("Ann"), record.player(0));
+        assert_eq!(Some("Bob"), record.player(1));
+        assert_eq!(Some("5+2"), record.header("TimeControl"));
+
+        let html = game.to_html(
+            &Player {
+                id: 8,
+                name: None,
+                renderer: Renderer::default(),
+            },
+            None,
+        );
+        assert!(html.contains("Bob (you)"));
+        assert!(html.contains("5+2"));
+    }
+
+    #[test]
+    fn test_empty_seats_can_not_be_played() {
+        let mut game = Game::new(1, starting_position(4, 2).unwrap());
+        game.join(0, 7, "Ann").unwrap();
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+
+        assert_eq!(
+            Err("Team b has no player yet, take its seat to move it.".into()),
+            game.check_turn(7)
+        );
+        assert!(game.check_turn(8).is_err());
+        assert!(game.check_player(8).is_err());
+
+        game.join(1, 8, "Bob").unwrap();
+        assert!(game.check_turn(8).is_ok());
+    }
+
+    #[test]
+    fn test_flag_fall_ends_the_game() {
+        let mut game = Game::new(1, starting_position(4, 2).unwrap());
+        game.set_time_control(TimeControl::new(1, 0, 0));
+        assert!(!game.update_clock(Instant::now() + Duration::from_secs(120)));
+
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+        assert!(game.check_takeback(7).is_err());
+        assert!(!game.update_clock(Instant::now()));
+        assert_eq!(GameStatus::Ongoing, game.status());
+
+        let revision = game.revision();
+        assert!(game.update_clock(Instant::now() + Duration::from_secs(61)));
+        assert!(game.revision() > revision);
+        assert_eq!(GameStatus::Won(0), game.status());
+        assert_eq!("Team b ran out of time, team a won", game.status_text());
+        assert!(game.click(&HexId::from_notation("a1").unwrap()).is_err());
+
+        let html = game.to_html(&viewer(), None);
+        assert!(html.contains("ran out of time"));
+        assert!(html.contains("0:00.0"));
+    }
+
+    #[test]
+    fn test_finished_games_are_rated() {
+        let store: SharedStore = Arc::new(MemoryStore::default());
+        let games = Games::with_store(Duration::from_secs(60), store.clone());
+        let game = games.create(starting_position(4, 2).unwrap());
+        let mut game = Game::lock(&game);
+        game.set_time_control(TimeControl::new(1, 0, 0));
+        game.join(0, 7, "Ann").unwrap();
+        game.join(1, 8, "Bob").unwrap();
+
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+        games.rate(&mut game);
+        assert!(game.finished_record().is_none(), "still going");
+
+        game.update_clock(Instant::now() + Duration::from_secs(61));
+        games.rate(&mut game);
+        assert!(game.finished_record().is_none(), "rated once");
+
+        // The names belong to the players now.
+        assert!(games.check_name("Ann", 7).is_ok());
+        assert!(games.check_name("Ann", 9).is_err());
+
+        let ratings = games.ratings();
+        assert_eq!(1, ratings.games().len());
+        assert_eq!(1, ratings.player("Ann").unwrap().wins);
+        assert_eq!(1, ratings.player("Bob").unwrap().losses);
+        assert_eq!(Some(7), ratings.games()[0].player_id(0));
+        assert_eq!(1, store.rated_records().unwrap().len());
+
+        // The ratings come back from the store after a restart.
+        let restarted = Games::with_store(Duration::from_secs(60), store);
+        restarted.load(&ExternalEngines::default()).unwrap();
+        let restarted = restarted.ratings();
+        assert_eq!(1, restarted.games().len());
+        assert_eq!(ratings.games()[0].to_text(), restarted.games()[0].to_text());
+        assert_eq!(1, restarted.player("Ann").unwrap().wins);
+        assert!(!restarted.may_use("Bob", 7));
+    }
+
+    #[test]
+    fn test_games_without_all_players_from_the_start_are_not_rated() {
+        let mut game = Game::new(1, starting_position(4, 2).unwrap());
+        game.set_time_control(TimeControl::new(1, 0, 0));
+        game.join(0, 7, "Ann").unwrap();
+        game.join(1, 7, "Ann").unwrap();
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+        game.update_clock(Instant::now() + Duration::from_secs(61));
+        assert!(game.finished_record().is_none(), "Ann played both teams");
+
+        let mut game = Game::new(2, starting_position(4, 2).unwrap());
+        game.set_time_control(TimeControl::new(1, 0, 0));
+        game.join(0, 7, "Ann").unwrap();
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+        game.join(1, 8, "Bob").unwrap();
+        game.update_clock(Instant::now() + Duration::from_secs(61));
+        assert!(game.finished_record().is_none(), "Bob joined late");
+
+        game.seats.leave(8);
+        assert_eq!(Err("The game is over.".into()), game.join(1, 8, "Bob"));
+    }
+
+    #[test]
+    fn test_spectators() {
+        let game = Arc::new(Mutex::new(Game::new(1, starting_position(3, 2).unwrap())));
+        let mut updates = Game::lock(&game).subscribe();
+
+        let first = Game::watch(&game);
+        let second = Game::watch(&game);
+        assert_eq!(2, Game::lock(&game).spectators());
+        drop(first);
+        assert_eq!(1, Game::lock(&game).spectators());
+        // Players see the spectators come and go, without the game changing.
+        for count in [1, 2, 1] {
+            assert_eq!(Ok(GameUpdate::Spectators(count)), updates.try_recv());
+        }
+        assert_eq!(0, Game::lock(&game).revision());
+
+        let html = Game::lock(&game).to_spectator_html(Renderer::Html);
+        assert!(html.contains("1 watching"));
+        assert!(!html.contains("click?hex"));
+        assert!(!html.contains("/undo"));
+        // Nothing on the page of a spectator gives away the id players play the game by.
+        assert!(!html.contains("games&#x2F;1&#x2F;"));
+        assert!(!html.contains("game-id"));
+        assert!(html.contains(Game::lock(&game).watch_token()));
+        assert!(Game::lock(&game)
+            .to_html(&viewer(), None)
+            .contains("click?hex"));
+
+        drop(game);
+        drop(second);
+    }
+
+    #[test]
+    fn test_games_are_watched_by_token() {
+        let games = Games::new(Duration::from_secs(60));
+        let first = games.create(HexBoard::new(3));
+        let second = games.create(HexBoard::new(3));
+        let token = Game::lock(&second).watch_token().to_string();
+
+        assert_eq!(24, token.len());
+        assert!(token.parse::<u64>().is_err());
+        assert_ne!(token, Game::lock(&first).watch_token());
+        let watched = games.watched(&token).unwrap();
+        assert!(Arc::ptr_eq(&second, &watched));
+        assert!(games.watched("2").is_none());
+    }
+
+    #[test]
+    fn test_changes_reach_subscribers() {
+        let mut game = Game::new(1, starting_position(3, 2).unwrap());
+        let mut first = game.subscribe();
+        game.changed();
+        let mut second = game.subscribe();
+        game.changed();
+
+        assert_eq!(2, game.revision());
+        assert_eq!(Ok(GameUpdate::Changed(1)), first.try_recv());
+        assert_eq!(Ok(GameUpdate::Changed(2)), first.try_recv());
+        assert_eq!(Ok(GameUpdate::Changed(2)), second.try_recv());
+        assert!(second.try_recv().is_err());
+
+        game.computer = Some(Computer {
+            teams: vec![1],
+            engine: EngineKind::AlphaBeta,
+            time: Duration::from_millis(20),
+        });
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+        let game = Mutex::new(game);
+        Game::play_computer_moves(&game).unwrap();
+        assert_eq!(Ok(GameUpdate::Changed(3)), second.try_recv());
+        assert!(Game::lock(&game)
+            .to_html(&viewer(), None)
+            .contains("revision=\"3\""));
+    }
+
+    #[test]
+    fn test_computer_thinks_without_the_lock() {
+        let mut game = Game::new(1, starting_position(4, 2).unwrap());
+        game.computer = Some(Computer {
+            teams: vec![1],
+            engine: EngineKind::Mcts,
+            time: Duration::from_millis(500),
+        });
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+        let game = Arc::new(Mutex::new(game));
+
+        let thinking = {
+            let game = game.clone();
+            std::thread::spawn(move || Game::play_computer_moves(&game))
+        };
+        std::thread::sleep(Duration::from_millis(100));
+        // The game is free while the computer thinks, and changing it drops the computer's move.
+        game.try_lock().unwrap().changed();
+        thinking.join().unwrap().unwrap();
+
+        let game = Game::lock(&game);
+        assert_eq!(1, game.ply());
+        assert!(game.is_computer_turn());
+    }
+
+    #[test]
+    fn test_computer_replies_and_takes_back() {
+        let mut game = Game::new(1, starting_position(4, 3).unwrap());
+        game.computer = Some(Computer {
+            teams: vec![1, 2],
+            engine: EngineKind::AlphaBeta,
+            time: Duration::from_millis(20),
+        });
+        assert!(!game.is_computer_turn());
+
+        let mv = moves(&game.board, 0).remove(0);
+        game.play(&mv);
+        assert!(game.is_computer_turn());
+        let game = Mutex::new(game);
+        Game::play_computer_moves(&game).unwrap();
+        let mut game = Game::lock(&game);
+        assert_eq!(3, game.ply());
+        assert_eq!(0, game.board.to_move());
+
+        assert!(game.undo_turn());
+        assert_eq!(0, game.ply());
+        assert!(game.redo_turn());
+        assert_eq!(3, game.ply());
+        assert!(game.undo_turn());
+        assert_eq!(0, game.ply());
+        assert!(!game.undo_turn());
+    }
+
+    #[test]
+    fn test_repetition_is_a_draw() {
+        let board = HexBoard::from_notation("3 a02/4/5/4/2b0 a 2").unwrap();
+        let mut game = Game::new(1, board);
+        let shuffle = ["a1-a2", "e5-e4", "a2-a1", "e4-e5"];
+
+        for mv in shuffle.iter().chain(&shuffle) {
+            assert_eq!(GameStatus::Ongoing, game.status());
+            let mv = parse_move(&game.board, mv).unwrap();
+            game.play(&mv);
+        }
+
+        assert_eq!(3, game.repetitions());
+        assert_eq!(GameStatus::Draw, game.status());
+        assert_eq!("Draw by repetition", game.status_text());
+        assert!(click(&mut game, "a1").is_err());
+
+        game.undo();
+        assert_eq!(GameStatus::Ongoing, game.status());
+        game.redo();
+        assert_eq!(GameStatus::Draw, game.status());
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let mut game = Game::new(1, starting_position(4, 2).unwrap());
+        for _ in 0..5 {
+            let mv = moves(&game.board, game.board.to_move()).remove(0);
+            game.play(&mv);
+        }
+        game.undo();
+
+        let mut record = game.to_record();
+        assert_eq!(4, record.moves.len());
+        record.set_player(0, "Alice");
+        record.moves[1].comment = Some("Interesting.".into());
+
+        let imported = Game::from_record(2, GameRecord::parse(&record.to_text()).unwrap());
+        assert_eq!(game.board.to_notation(), imported.board.to_notation());
+        assert_eq!(&game.history()[..4], imported.history());
+        assert_eq!(record.to_text(), imported.to_record().to_text());
+    }
+
+    #[test]
+    fn test_games_survive_a_restart() {
+        let store: SharedStore = Arc::new(MemoryStore::default());
+        let engines = ExternalEngines::default();
+
+        let id = {
+            let games = Games::with_store(Duration::from_secs(60), store.clone());
+            let game = games.create(starting_position(4, 2).unwrap());
+            let mut game = Game::lock(&game);
+            game.computer = Some(Computer {
+                teams: vec![1],
+                engine: EngineKind::Mcts,
+                time: Duration::from_millis(20),
+            });
+            game.set_time_control(TimeControl::new(3, 2, 1));
+            game.join(0, u64::MAX, "Ann").unwrap();
+            for _ in 0..3 {
+                let mv = moves(&game.board, game.board.to_move()).remove(0);
+                game.play(&mv);
+            }
+            game.undo();
+            game.changed();
+            game.id
+        };
+
+        let games = Games::with_store(Duration::from_secs(60), store.clone());
+        games.load(&engines).unwrap();
+        let game = games.get(id).unwrap();
+        let game = Game::lock(&game);
+        assert_eq!(2, game.ply());
+        assert_eq!(3, game.history().len());
+        assert_eq!(
+            game.board_at(2).unwrap().to_notation(),
+            game.board.to_notation()
+        );
+        assert_eq!("Ann", game.seats.get(0).unwrap().name);
+        assert_eq!(
+            Some(TimeControl::new(3, 2, 1).unwrap()),
+            game.time_control()
+        );
+        assert_eq!(EngineKind::Mcts, game.computer.as_ref().unwrap().engine);
+        assert_eq!(store.games().unwrap()[0], game.to_saved(Instant::now()));
+
+        // New games do not take the ids of the loaded ones.
+        assert_ne!(id, Game::lock(&games.create(HexBoard::new(3))).id);
+    }
+
+    #[test]
+    fn test_games_that_can_not_be_loaded_stay_in_the_store() {
+        let store: SharedStore = Arc::new(MemoryStore::default());
+        let mut saved = Game::new(4, starting_position(4, 2).unwrap()).to_saved(Instant::now());
+        saved.initial = "not a board".into();
+        store.save_game(&saved).unwrap();
+
+        let games = Games::with_store(Duration::from_secs(60), store.clone());
+        games.load(&ExternalEngines::default()).unwrap();
+        assert!(games.get(4).is_none());
+
+        let id = Game::lock(&games.create(HexBoard::new(3))).id;
+        assert!(id > 4);
+        assert!(store.games().unwrap().contains(&saved));
+    }
+
+    #[test]
+    fn test_create_and_get() {
+        let games = Games::new(Duration::from_secs(60));
+        let first = games.create(HexBoard::new(3)).lock().unwrap().id;
+        let second = games.create(HexBoard::new(4)).lock().unwrap().id;
+
+        assert_ne!(first, second);
+        assert_eq!(2, games.len());
+        assert_eq!(4, games.get(second).unwrap().lock().unwrap().board.size());
+        assert!(games.get(second + 1).is_none());
+    }
+
+    #[test]
+    fn test_idle_games_expire() {
+        let games = Games::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let kept = games.create(HexBoard::new(3)).lock().unwrap().id;
+        let idle = games.create(HexBoard::new(3)).lock().unwrap().id;
+
+        let soon = start + Duration::from_secs(40);
+        assert!(games.get_at(kept, soon).is_some());
+
+        let later = start + Duration::from_secs(80);
+        assert!(games.get_at(idle, later).is_none());
+        assert!(games.get_at(kept, later).is_some());
+        assert_eq!(1, games.len());
+    }
+}
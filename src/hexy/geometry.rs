@@ -0,0 +1,358 @@
+use std::fmt::Display;
+
+use super::hexagon::{CubeId, HexId};
+use super::shape::Shape;
+
+/// The six directions to a neighbouring hex, in clockwise order starting at the top.
+/// The hexes are flat-topped on the screen, so there is a neighbour straight above and below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    North,
+    NorthEast,
+    SouthEast,
+    South,
+    SouthWest,
+    NorthWest,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Direction::North => "N",
+            Direction::NorthEast => "NE",
+            Direction::SouthEast => "SE",
+            Direction::South => "S",
+            Direction::SouthWest => "SW",
+            Direction::NorthWest => "NW",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::NorthWest,
+    ];
+
+    /// The change in (x, y) of a HexId when taking one step in this direction.
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Direction::North => (-1, -1),
+            Direction::NorthEast => (-1, 0),
+            Direction::SouthEast => (0, 1),
+            Direction::South => (1, 1),
+            Direction::SouthWest => (1, 0),
+            Direction::NorthWest => (0, -1),
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        self.rotate_clockwise(3)
+    }
+
+    /// Rotates by `steps` sixths of a full turn.
+    pub fn rotate_clockwise(&self, steps: usize) -> Direction {
+        let index = Direction::ALL
+            .iter()
+            .position(|direction| direction == self)
+            .unwrap_or_default();
+        Direction::ALL[(index + steps) % Direction::ALL.len()]
+    }
+}
+
+impl HexId {
+    /// Takes `steps` steps in a direction, without checking the board in between.
+    /// The geometry takes the shape of the board, or just the size of a regular hexagon.
+    pub fn step(&self, direction: Direction, steps: u32, shape: impl Shape) -> Option<HexId> {
+        let (dx, dy) = direction.offset();
+        let x = self.x as i64 + dx as i64 * steps as i64;
+        let y = self.y as i64 + dy as i64 * steps as i64;
+        if x < 1 || y < 1 || x > u32::MAX as i64 || y > u32::MAX as i64 {
+            return None;
+        }
+
+        Some(HexId::new(x as u32, y as u32)).filter(|hex_id| shape.contains(hex_id))
+    }
+
+    pub fn neighbour(&self, direction: Direction, shape: impl Shape) -> Option<HexId> {
+        self.step(direction, 1, shape)
+    }
+
+    /// The neighbours that are on the board, in the order of `Direction::ALL`.
+    pub fn neighbours(&self, shape: impl Shape) -> Vec<HexId> {
+        Direction::ALL
+            .iter()
+            .filter_map(|direction| self.neighbour(*direction, &shape))
+            .collect()
+    }
+
+    /// The direction from this hex to another one, if they are on a straight line.
+    pub fn direction_to(&self, other: &HexId) -> Option<Direction> {
+        let dx = other.x as i64 - self.x as i64;
+        let dy = other.y as i64 - self.y as i64;
+        if dx == 0 && dy == 0 {
+            return None;
+        }
+
+        Direction::ALL.into_iter().find(|direction| {
+            let (x, y) = direction.offset();
+            let steps = if x != 0 { dx / x as i64 } else { dy / y as i64 };
+            steps > 0 && x as i64 * steps == dx && y as i64 * steps == dy
+        })
+    }
+
+    /// The amount of steps between two hexes. This does not depend on the size of the board.
+    pub fn distance(&self, other: &HexId) -> u32 {
+        let dx = self.x as i64 - other.x as i64;
+        let dy = self.y as i64 - other.y as i64;
+
+        dx.abs().max(dy.abs()).max((dx - dy).abs()) as u32
+    }
+
+    /// The hexes on the board at exactly `radius` steps, clockwise starting at the top.
+    pub fn ring(&self, radius: u32, shape: impl Shape) -> Vec<HexId> {
+        if radius == 0 {
+            return if shape.contains(self) {
+                vec![self.clone()]
+            } else {
+                Vec::new()
+            };
+        }
+
+        let center = self.to_cube(0);
+        let north = direction_to_cube(Direction::North);
+        let radius_i32 = radius as i32;
+        let mut cube = CubeId::new(
+            center.q + north.q * radius_i32,
+            center.r + north.r * radius_i32,
+            center.s + north.s * radius_i32,
+        );
+        let mut ring = Vec::new();
+
+        for direction in Direction::ALL
+            .iter()
+            .map(|direction| direction.rotate_clockwise(2))
+        {
+            let step = direction_to_cube(direction);
+            for _ in 0..radius {
+                if let Some(hex_id) = from_cube_on(cube, &shape) {
+                    ring.push(hex_id);
+                }
+
+                cube = CubeId::new(cube.q + step.q, cube.r + step.r, cube.s + step.s);
+            }
+        }
+
+        ring
+    }
+
+    /// The hexes on the board within `radius` steps, ring by ring from the center outwards.
+    pub fn spiral(&self, radius: u32, shape: impl Shape) -> Vec<HexId> {
+        (0..=radius)
+            .flat_map(|ring| self.ring(ring, &shape))
+            .collect()
+    }
+
+    /// The hexes on the straight line from this hex to another, including both ends.
+    /// Hexes off the board are left out, which can only happen for lines along the board's edge.
+    pub fn line_to(&self, other: &HexId, shape: impl Shape) -> Vec<HexId> {
+        let a = self.to_cube(0);
+        let b = other.to_cube(0);
+        let distance = self.distance(other);
+        if distance == 0 {
+            return self.ring(0, shape);
+        }
+
+        (0..=distance)
+            .filter_map(|i| {
+                let t = i as f64 / distance as f64;
+                // Nudge off the exact edges between hexes so ties always round the same way.
+                let lerp =
+                    |from: i32, to: i32, nudge: f64| from as f64 + nudge + (to - from) as f64 * t;
+                from_cube_on(
+                    round_cube(
+                        lerp(a.q, b.q, 1e-6),
+                        lerp(a.r, b.r, 2e-6),
+                        lerp(a.s, b.s, -3e-6),
+                    ),
+                    &shape,
+                )
+            })
+            .collect()
+    }
+
+    /// The hexes from this hex in a direction until the edge of the board, not including this hex.
+    pub fn ray(&self, direction: Direction, shape: impl Shape) -> Vec<HexId> {
+        let mut ray = Vec::new();
+        let mut current = self.neighbour(direction, &shape);
+        while let Some(hex_id) = current {
+            current = hex_id.neighbour(direction, &shape);
+            ray.push(hex_id);
+        }
+
+        ray
+    }
+}
+
+/// The hex at cube coordinates around HexId (0, 0), see `HexId::to_cube`, if it is on the board.
+fn from_cube_on(cube: CubeId, shape: impl Shape) -> Option<HexId> {
+    if cube.q < 1 || cube.r > -1 {
+        return None;
+    }
+
+    Some(HexId::new(cube.q as u32, cube.r.unsigned_abs())).filter(|hex_id| shape.contains(hex_id))
+}
+
+fn direction_to_cube(direction: Direction) -> CubeId {
+    let (dx, dy) = direction.offset();
+    CubeId::new(dx, -dy, dy - dx)
+}
+
+fn round_cube(q: f64, r: f64, s: f64) -> CubeId {
+    let mut rounded_q = q.round();
+    let mut rounded_r = r.round();
+    let mut rounded_s = s.round();
+
+    let diff_q = (rounded_q - q).abs();
+    let diff_r = (rounded_r - r).abs();
+    let diff_s = (rounded_s - s).abs();
+
+    if diff_q > diff_r && diff_q > diff_s {
+        rounded_q = -rounded_r - rounded_s;
+    } else if diff_r > diff_s {
+        rounded_r = -rounded_q - rounded_s;
+    } else {
+        rounded_s = -rounded_q - rounded_r;
+    }
+
+    CubeId::new(rounded_q as i32, rounded_r as i32, rounded_s as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::geometry::Direction;
+    use crate::hexy::hexagon::HexId;
+    use crate::util::hex_count;
+
+    #[test]
+    fn test_direction_offsets_match_cube() {
+        for direction in Direction::ALL {
+            let center = HexId::new(4, 4);
+            let neighbour = center.neighbour(direction, 4).unwrap();
+            assert_eq!(1, center.distance(&neighbour));
+            assert_eq!(
+                Some(center.clone()),
+                neighbour.neighbour(direction.opposite(), 4)
+            );
+            assert_eq!(Some(direction), center.direction_to(&neighbour));
+        }
+    }
+
+    #[test]
+    fn test_neighbours() {
+        assert_eq!(6, HexId::new(4, 4).neighbours(4).len());
+        assert_eq!(
+            vec![HexId::new(1, 2), HexId::new(2, 2), HexId::new(2, 1)],
+            HexId::new(1, 1).neighbours(4)
+        );
+        assert_eq!(4, HexId::new(2, 1).neighbours(4).len());
+        assert!(HexId::new(1, 1).neighbours(1).is_empty());
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(0, HexId::new(3, 2).distance(&HexId::new(3, 2)));
+        assert_eq!(6, HexId::new(1, 1).distance(&HexId::new(7, 7)));
+        assert_eq!(6, HexId::new(1, 4).distance(&HexId::new(7, 4)));
+        assert_eq!(3, HexId::new(1, 1).distance(&HexId::new(4, 1)));
+        assert_eq!(6, HexId::new(4, 1).distance(&HexId::new(1, 4)));
+    }
+
+    #[test]
+    fn test_rings_cover_board() {
+        for size in 1..=8 {
+            let center = HexId::new(size, size);
+            let spiral = center.spiral(size - 1, size);
+            assert_eq!(hex_count(size) as usize, spiral.len(), "size={size}");
+
+            for (radius, hex_id) in (0..size).flat_map(|radius| {
+                center
+                    .ring(radius, size)
+                    .into_iter()
+                    .map(move |hex_id| (radius, hex_id))
+            }) {
+                assert_eq!(radius, center.distance(&hex_id));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ring_order() {
+        assert_eq!(
+            vec![
+                HexId::new(3, 3),
+                HexId::new(3, 4),
+                HexId::new(4, 5),
+                HexId::new(5, 5),
+                HexId::new(5, 4),
+                HexId::new(4, 3)
+            ],
+            HexId::new(4, 4).ring(1, 4)
+        );
+    }
+
+    #[test]
+    fn test_ring_clipped_by_board() {
+        let corner = HexId::new(1, 1);
+        assert_eq!(vec![HexId::new(1, 1)], corner.ring(0, 4));
+        assert_eq!(3, corner.ring(1, 4).len());
+        assert_eq!(7, corner.ring(3, 4).len());
+        assert!(HexId::new(4, 4).ring(4, 4).is_empty());
+    }
+
+    #[test]
+    fn test_line_to() {
+        let line = HexId::new(1, 1).line_to(&HexId::new(7, 7), 4);
+        assert_eq!(7, line.len());
+        assert_eq!((1..=7).map(|i| HexId::new(i, i)).collect::<Vec<_>>(), line);
+
+        for size in 2..=6 {
+            let max = size * 2 - 1;
+            let from = HexId::new(1, size);
+            let to = HexId::new(max, size);
+            let line = from.line_to(&to, size);
+            assert_eq!(from.distance(&to) as usize + 1, line.len());
+            assert!(line.windows(2).all(|pair| pair[0].distance(&pair[1]) == 1));
+        }
+    }
+
+    #[test]
+    fn test_ray() {
+        assert_eq!(
+            vec![HexId::new(5, 5), HexId::new(6, 6), HexId::new(7, 7)],
+            HexId::new(4, 4).ray(Direction::South, 4)
+        );
+        assert!(HexId::new(1, 1).ray(Direction::North, 4).is_empty());
+        assert_eq!(3, HexId::new(1, 1).ray(Direction::SouthWest, 4).len());
+    }
+
+    #[test]
+    fn test_direction_to() {
+        assert_eq!(
+            Some(Direction::South),
+            HexId::new(1, 1).direction_to(&HexId::new(7, 7))
+        );
+        assert_eq!(
+            Some(Direction::NorthEast),
+            HexId::new(4, 1).direction_to(&HexId::new(1, 1))
+        );
+        assert_eq!(None, HexId::new(1, 1).direction_to(&HexId::new(3, 2)));
+        assert_eq!(None, HexId::new(1, 1).direction_to(&HexId::new(1, 1)));
+    }
+}
@@ -1,58 +1,253 @@
-use super::hexagon::{GridId, HexId, Hexagon};
+use super::hexagon::{GridId, HexId, HexMarks, HexState, Hexagon};
+use super::layout::Orientation;
+use super::shape::BoardShape;
+use super::zobrist;
 
-fn is_dead(x: u32, y: u32, size: u32, row_count: u32) -> bool {
-    if y < size {
-        return x > y;
+/// The largest board the game accepts from players.
+pub const MAX_SIZE: u32 = 50;
+
+/// The most lines a board can span, the star of the largest size spans this many.
+pub const MAX_EXTENT: u32 = MAX_SIZE * 4 - 3;
+
+/// An invisible hex that keeps the hexes of a row in their column where the board has no hex.
+const GAP_HTML: &str = "<div class=\"hex_gap\"><div class=\"shape\">&#x2B23;</div></div>";
+
+/// How a board is drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Renderer {
+    /// Rows of hexagon glyphs, laid out by the stylesheet.
+    #[default]
+    Html,
+    /// An SVG image with the exact shape of every hex, see the `layout` module.
+    Svg(Orientation),
+}
+
+impl Renderer {
+    pub const ALL: [Renderer; 3] = [
+        Renderer::Html,
+        Renderer::Svg(Orientation::FlatTop),
+        Renderer::Svg(Orientation::PointyTop),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Renderer::Html => "html",
+            Renderer::Svg(Orientation::FlatTop) => "svg",
+            Renderer::Svg(Orientation::PointyTop) => "svg-pointy",
+        }
     }
 
-    if y > row_count - size {
-        return x >= row_count - y;
+    pub fn label(&self) -> &'static str {
+        match self {
+            Renderer::Html => "Glyphs",
+            Renderer::Svg(Orientation::FlatTop) => "SVG, flat top",
+            Renderer::Svg(Orientation::PointyTop) => "SVG, pointy top",
+        }
     }
 
-    let is_even = size % 2;
-    if y % 2 == is_even {
-        return x == size - 1;
-    } else {
-        return false;
+    pub fn from_name(name: &str) -> Option<Renderer> {
+        Renderer::ALL
+            .into_iter()
+            .find(|renderer| renderer.name() == name)
     }
 }
 
+/// How a board is shown to the player.
+#[derive(Debug, Default, Clone)]
+pub struct BoardView {
+    pub renderer: Renderer,
+    /// Clicking a hex posts to this url, with the hex as a `hex` query parameter like `d2`.
+    pub click_url: Option<String>,
+    pub selected: Option<HexId>,
+    /// The free hexes the selected piece can move to.
+    pub targets: Vec<HexId>,
+    /// The hexes with pieces the selected piece can capture.
+    pub captures: Vec<HexId>,
+    /// The hexes the last move went from and to.
+    pub last_move: Option<(HexId, HexId)>,
+}
+
+impl BoardView {
+    pub fn marks(&self, hex_id: &HexId) -> HexMarks {
+        HexMarks {
+            selected: self.selected.as_ref() == Some(hex_id),
+            target: self.targets.contains(hex_id),
+            capture: self.captures.contains(hex_id),
+            last_move: self
+                .last_move
+                .as_ref()
+                .is_some_and(|(from, to)| from == hex_id || to == hex_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct HexBoard {
-    size: u32, // The amount of hexes on a side.
+    shape: BoardShape,
     hex_board: Vec<Vec<Hexagon>>,
+    teams: u32,   // The amount of teams playing, each team index is below this.
+    to_move: u32, // The team whose turn it is.
+    hash: u64,    // The Zobrist hash of the pieces and the side to move.
 }
 
 impl HexBoard {
+    /// An empty regular hexagon with `size` hexes on a side, the board of Hexy.
     pub fn new(size: u32) -> HexBoard {
-        let row_count = (size * 2 - 1) * 2 - 1;
-        let mut hex_board = Vec::new();
-        for y in 0..row_count {
-            let mut hex_row = Vec::new();
-
-            for x in 0..size {
-                if !is_dead(x, y, size, row_count) {
-                    let grid_id = GridId::new(x, y);
-                    let hex_id: HexId = HexId::from_grid_id(x, y, size);
-                    hex_row.push(Hexagon {
-                        grid_id,
+        HexBoard::with_shape(BoardShape::Hexagon(size))
+    }
+
+    pub fn with_shape(shape: BoardShape) -> HexBoard {
+        let hex_board = shape
+            .rows()
+            .into_iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.into_iter()
+                    .enumerate()
+                    .map(|(x, hex_id)| Hexagon {
+                        grid_id: GridId::new(x as u32, y as u32),
                         hex_id,
                         ..Default::default()
-                    });
-                }
-            }
+                    })
+                    .collect()
+            })
+            .collect();
 
-            hex_board.push(hex_row);
+        HexBoard {
+            shape,
+            hex_board,
+            teams: 2,
+            to_move: 0,
+            hash: zobrist::side_key(0),
         }
+    }
 
-        HexBoard { size, hex_board }
+    pub fn shape(&self) -> &BoardShape {
+        &self.shape
+    }
+
+    pub fn size(&self) -> u32 {
+        self.shape.size()
+    }
+
+    pub fn teams(&self) -> u32 {
+        self.teams
+    }
+
+    pub fn set_teams(&mut self, teams: u32) {
+        self.teams = teams;
+    }
+
+    pub fn to_move(&self) -> u32 {
+        self.to_move
+    }
+
+    pub fn set_to_move(&mut self, team: u32) {
+        self.hash ^= zobrist::side_key(self.to_move) ^ zobrist::side_key(team);
+        self.to_move = team;
+    }
+
+    /// Identifies the position, see the `zobrist` module.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// All hexagons on the board, row by row from the top of the screen.
+    pub fn hexagons(&self) -> impl Iterator<Item = &Hexagon> {
+        self.hex_board.iter().flatten()
+    }
+
+    pub fn get(&self, hex_id: &HexId) -> Option<&Hexagon> {
+        let grid_id = self.grid_id(hex_id)?;
+        self.hex_board
+            .get(grid_id.y as usize)?
+            .get(grid_id.x as usize)
+    }
+
+    /// Where a hex is in the rows of the board, None when it is not on the board.
+    fn grid_id(&self, hex_id: &HexId) -> Option<GridId> {
+        if let BoardShape::Hexagon(size) = self.shape {
+            return hex_id.to_grid_id(size);
+        }
+
+        // The rows are ordered by x + y, every row by decreasing x.
+        let first = &self.hex_board.first()?.first()?.hex_id;
+        // Far off the board x + y does not fit, which is no row either.
+        let row = hex_id.x.checked_add(hex_id.y)?;
+        let y = row.checked_sub(first.x + first.y)?;
+        let x = self
+            .hex_board
+            .get(y as usize)?
+            .binary_search_by(|hexagon| hex_id.x.cmp(&hexagon.hex_id.x))
+            .ok()?;
+
+        Some(GridId::new(x as u32, y))
+    }
+
+    /// Puts a piece on a hex or clears it, and returns what was there before.
+    /// Returns None when the hex is not on the board.
+    pub fn set_state(&mut self, hex_id: &HexId, state: HexState) -> Option<HexState> {
+        let grid_id = self.grid_id(hex_id)?;
+        let hexagon = self
+            .hex_board
+            .get_mut(grid_id.y as usize)?
+            .get_mut(grid_id.x as usize)?;
+        let old = std::mem::replace(&mut hexagon.state, state);
+        self.hash ^= zobrist::state_key(hex_id, old) ^ zobrist::state_key(hex_id, state);
+
+        Some(old)
     }
 
     pub fn to_html(&self) -> String {
+        self.to_html_with(&BoardView::default())
+    }
+
+    /// Draws the board with the renderer of the view.
+    pub fn render(&self, view: &BoardView) -> String {
+        match view.renderer {
+            Renderer::Html => self.to_html_with(view),
+            Renderer::Svg(orientation) => self.to_svg_with(view, orientation),
+        }
+    }
+
+    pub fn to_html_with(&self, view: &BoardView) -> String {
+        // The rows are centered on the screen, so every row gets gaps around the middle column
+        // to keep its hexes above the ones in the rows below.
+        let column = |hexagon: &Hexagon| hexagon.hex_id.y as i64 - hexagon.hex_id.x as i64;
+        let (left, right) = self
+            .hexagons()
+            .map(column)
+            .fold((i64::MAX, i64::MIN), |(left, right), column| {
+                (left.min(column), right.max(column))
+            });
+        let middle = (left + right).div_euclid(2);
+        let first_row = self
+            .hexagons()
+            .next()
+            .map_or(0, |hexagon| (hexagon.hex_id.x + hexagon.hex_id.y) as i64);
+
         let mut board_html = String::from("<div class=\"hex_grid\">");
-        for row in self.hex_board.iter() {
+        for (y, row) in self.hex_board.iter().enumerate() {
+            let half_width = row
+                .iter()
+                .map(|hexagon| (column(hexagon) - middle).abs())
+                .max()
+                .unwrap_or((first_row + y as i64 - middle).rem_euclid(2));
+            let mut hexagons = row.iter().peekable();
+
             let mut row_html = String::from("<div class=\"hex_row\">");
-            for hexagon in row.iter() {
-                row_html.push_str(hexagon.to_html().as_str());
+            for slot in (middle - half_width..=middle + half_width).step_by(2) {
+                let Some(hexagon) = hexagons.next_if(|hexagon| column(hexagon) == slot) else {
+                    row_html.push_str(GAP_HTML);
+                    continue;
+                };
+
+                let click_url = view
+                    .click_url
+                    .as_ref()
+                    .map(|url| format!("{url}?hex={}", hexagon.hex_id.to_notation()));
+                let marks = view.marks(&hexagon.hex_id);
+                row_html.push_str(hexagon.to_html(click_url.as_deref(), marks).as_str());
             }
 
             row_html.push_str("</div>");
@@ -63,3 +258,93 @@ impl HexBoard {
         board_html
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{GridId, HexId, HexState};
+    use crate::hexy::shape::BoardShape;
+    use crate::util::hex_count;
+
+    #[test]
+    fn test_board_ids_match_indices() {
+        for size in 1..=10 {
+            let board = HexBoard::new(size);
+            let mut count = 0;
+            for (y, row) in board.hex_board.iter().enumerate() {
+                for (x, hexagon) in row.iter().enumerate() {
+                    assert_eq!(GridId::new(x as u32, y as u32), hexagon.grid_id);
+                    assert_eq!(
+                        Some(hexagon.grid_id.clone()),
+                        hexagon.hex_id.to_grid_id(size)
+                    );
+                    assert_eq!(
+                        Some(&hexagon.hex_id),
+                        board.get(&hexagon.hex_id).map(|hex| &hex.hex_id)
+                    );
+                    count += 1;
+                }
+            }
+
+            assert_eq!(hex_count(size), count, "size={size}");
+        }
+    }
+
+    #[test]
+    fn test_shaped_boards() {
+        for shape in [
+            BoardShape::Rhombus(4),
+            BoardShape::Triangle(5),
+            BoardShape::Star(3),
+            BoardShape::Rectangle {
+                width: 4,
+                height: 3,
+            },
+            BoardShape::Hexagon(4).with_holes(&[HexId::new(4, 4), HexId::new(2, 3)]),
+        ] {
+            let board = HexBoard::with_shape(shape.clone());
+            assert_eq!(
+                shape.hex_count() as usize,
+                board.hexagons().count(),
+                "{shape:?}"
+            );
+            for (y, row) in board.hex_board.iter().enumerate() {
+                for (x, hexagon) in row.iter().enumerate() {
+                    assert_eq!(GridId::new(x as u32, y as u32), hexagon.grid_id);
+                    assert_eq!(
+                        Some(&hexagon.hex_id),
+                        board.get(&hexagon.hex_id).map(|hex| &hex.hex_id)
+                    );
+                }
+            }
+
+            assert!(board.get(&HexId::new(0, 1)).is_none());
+            assert!(board.get(&HexId::new(shape.max_x() + 1, 1)).is_none());
+        }
+    }
+
+    #[test]
+    fn test_hexes_far_off_the_board() {
+        for shape in [BoardShape::Rhombus(3), BoardShape::Hexagon(3)] {
+            let mut board = HexBoard::with_shape(shape);
+            for hex_id in [HexId::new(2, u32::MAX), HexId::new(u32::MAX, u32::MAX)] {
+                assert!(board.get(&hex_id).is_none());
+                assert!(board.set_state(&hex_id, HexState::Free).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_gaps() {
+        assert!(!HexBoard::new(4).to_html().contains("hex_gap"));
+
+        // The second row of the triangle only has a hex right of the middle.
+        let html = HexBoard::with_shape(BoardShape::Triangle(2)).to_html();
+        assert_eq!(3, html.matches("class=\"hex_row\"").count());
+        assert_eq!(1, html.matches("hex_gap").count());
+
+        let holed = HexBoard::with_shape(BoardShape::Hexagon(2).with_holes(&[HexId::new(2, 2)]));
+        assert_eq!(1, holed.to_html().matches("hex_gap").count());
+        assert!(holed.get(&HexId::new(2, 2)).is_none());
+    }
+}
@@ -4,9 +4,23 @@ use tera::Context;
 
 use crate::util::get_template;
 
-const TEAM_COLORS: [&str; 6] = ["#f00", "#0f0", "#00f", "#0ff", "#f0f", "#f00"];
+const TEAM_COLORS: [&str; 6] = ["#f00", "#0f0", "#00f", "#0ff", "#f0f", "#ff0"];
 
-#[derive(Debug, Default)]
+/// The colour the pieces of `team` are drawn in.
+pub fn team_color(team: u32) -> &'static str {
+    TEAM_COLORS[(team % TEAM_COLORS.len() as u32) as usize]
+}
+
+/// What is written on a piece, the sign of a leader for value 0.
+pub fn value_text(value: u32) -> String {
+    if value == 0 {
+        "\u{1F542}".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HexState {
     #[default]
     Free,
@@ -23,17 +37,27 @@ struct HexagonTemplate {
     hex_value: String,
     data_grid_id: String,
     data_hex_id: String,
+    click_url: String,
+    is_selected: bool,
+    is_target: bool,
+    is_capture: bool,
+    is_last_move: bool,
 }
 
-impl Into<Context> for HexagonTemplate {
-    fn into(self) -> Context {
+impl From<HexagonTemplate> for Context {
+    fn from(template: HexagonTemplate) -> Context {
         let mut context = Context::new();
 
-        context.insert("piece_style", self.piece_style.as_str());
-        context.insert("value_style", self.value_style.as_str());
-        context.insert("hex_value", self.hex_value.as_str());
-        context.insert("data_grid_id", self.data_grid_id.as_str());
-        context.insert("data_hex_id", self.data_hex_id.as_str());
+        context.insert("piece_style", template.piece_style.as_str());
+        context.insert("value_style", template.value_style.as_str());
+        context.insert("hex_value", template.hex_value.as_str());
+        context.insert("data_grid_id", template.data_grid_id.as_str());
+        context.insert("data_hex_id", template.data_hex_id.as_str());
+        context.insert("click_url", template.click_url.as_str());
+        context.insert("is_selected", &template.is_selected);
+        context.insert("is_target", &template.is_target);
+        context.insert("is_capture", &template.is_capture);
+        context.insert("is_last_move", &template.is_last_move);
 
         context
     }
@@ -42,7 +66,7 @@ impl Into<Context> for HexagonTemplate {
 /// The coordinates of the hex on the board itself, like on a chess board.
 /// On the screen the most top HexId is (0, 0) and the most bottom hex is (n, n) where n = (size * 2) - 1.
 /// Going to the left x increases, going to the right y increases.
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct HexId {
     pub x: u32,
     pub y: u32,
@@ -56,10 +80,10 @@ impl Display for HexId {
 
 /// The indices of the hex within the Vec<Vec<Hexagon>> of the HexBoard.
 /// On the screen the most top HexId is (0, 0) and the most bottom hex is (0, n) where n = (size * 2) + ((size - 1) * 2) - 1.
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct GridId {
-    x: u32,
-    y: u32,
+    pub x: u32,
+    pub y: u32,
 }
 
 impl Display for GridId {
@@ -73,12 +97,45 @@ impl GridId {
         GridId { x, y }
     }
 
+    /// Whether this GridId points at a hex on a board with the given size.
+    pub fn is_valid(&self, size: u32) -> bool {
+        if size == 0 || self.y >= row_count(size) {
+            return false;
+        }
+
+        let (min_x, max_x) = row_x_range(self.y, size);
+        self.x <= max_x - min_x
+    }
+
+    /// Expects a GridId that is valid for the given size, see `GridId::is_valid`.
     pub fn to_hex_id(&self, size: u32) -> HexId {
         HexId::from_grid_id(self.x, self.y, size)
     }
 }
 
-#[derive(Debug, Default)]
+/// The amount of rows in the grid of a board with the given size.
+pub fn row_count(size: u32) -> u32 {
+    if size == 0 {
+        return 0;
+    }
+
+    (size * 2 - 1) * 2 - 1
+}
+
+/// How a hexagon is highlighted, on top of the piece on it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HexMarks {
+    /// The piece on this hex is selected to move.
+    pub selected: bool,
+    /// The selected piece can move here.
+    pub target: bool,
+    /// The selected piece can capture the piece on this hex.
+    pub capture: bool,
+    /// The last move started or ended here.
+    pub last_move: bool,
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct Hexagon {
     pub state: HexState,
     pub hex_id: HexId,
@@ -86,27 +143,25 @@ pub struct Hexagon {
 }
 
 impl Hexagon {
-    pub fn to_html(&self) -> String {
+    /// Renders the hexagon, when a `click_url` is given clicking the hexagon posts to it.
+    pub fn to_html(&self, click_url: Option<&str>, marks: HexMarks) -> String {
         let mut template = HexagonTemplate {
             piece_style: "display: none;".into(),
             value_style: "display: none;".into(),
             hex_value: "".into(),
             data_grid_id: self.grid_id.to_string(),
             data_hex_id: self.hex_id.to_string(),
+            click_url: click_url.unwrap_or_default().into(),
+            is_selected: marks.selected,
+            is_target: marks.target,
+            is_capture: marks.capture,
+            is_last_move: marks.last_move,
         };
 
         if let HexState::Piece { team, value } = self.state {
-            let i_team_color = team % TEAM_COLORS.len() as u32;
-            let team_color = TEAM_COLORS[i_team_color as usize];
-            template.piece_style = format!("color: {team_color}");
-
+            template.piece_style = format!("color: {}", team_color(team));
             template.value_style = "".into();
-
-            template.hex_value = if value == 0 {
-                "&#x1F542;".to_string()
-            } else {
-                value.to_string()
-            }
+            template.hex_value = value_text(value);
         }
 
         get_template("templates/hexy/hexagon.html", Some(template.into()))
@@ -119,40 +174,210 @@ impl HexId {
         HexId { x, y }
     }
 
-    pub fn from_grid_id(grid_x: u32, grid_y: u32, size: u32) -> HexId {
-        let first_corner_i = size - 1;
-        let second_corner_i = first_corner_i * 3 + 2; 
-
-        let mut x = 1;
-        let mut y = 1;
-
-        // println!("grid_x={grid_x}, grid_y={grid_y}, size={size}, first_corner_i={first_corner_i}, second_corner_i={second_corner_i}");
-        for i in 1..=grid_y {
-            // println!("x={x}, y={y}, i={i}");
-            if i <= first_corner_i {
-                x += 1;
-            } else if i <= second_corner_i {
-                x += (i - first_corner_i + 1) % 2;
-                y += (i - first_corner_i) % 2;
-            } else {
-                y += 1;
-            }
+    /// Whether this hex lies on a board with the given size.
+    pub fn is_valid(&self, size: u32) -> bool {
+        if size == 0 {
+            return false;
         }
-        // println!("x={x}, y={y}");
 
-        x -= grid_x;
-        y += grid_x;
+        let max = size * 2 - 1;
+        (1..=max).contains(&self.x) && (1..=max).contains(&self.y) && self.x.abs_diff(self.y) < size
+    }
+
+    /// Expects a GridId that is valid for the given size, see `GridId::is_valid`.
+    pub fn from_grid_id(grid_x: u32, grid_y: u32, size: u32) -> HexId {
+        let (_, max_x) = row_x_range(grid_y, size);
+        let x = max_x.saturating_sub(grid_x);
+        let y = (grid_y + 2).saturating_sub(x);
 
         HexId::new(x, y)
     }
+
+    pub fn to_grid_id(&self, size: u32) -> Option<GridId> {
+        if !self.is_valid(size) {
+            return None;
+        }
+
+        let grid_y = self.x + self.y - 2;
+        let (_, max_x) = row_x_range(grid_y, size);
+
+        Some(GridId::new(max_x - self.x, grid_y))
+    }
+
+    pub fn to_cube(&self, size: u32) -> CubeId {
+        let size = size as i32;
+        let x = self.x as i32;
+        let y = self.y as i32;
+
+        CubeId::new(x - size, size - y, y - x)
+    }
+
+    pub fn from_cube(cube: CubeId, size: u32) -> Option<HexId> {
+        let x = cube.q + size as i32;
+        let y = size as i32 - cube.r;
+        if x < 1 || y < 1 {
+            return None;
+        }
+
+        Some(HexId::new(x as u32, y as u32)).filter(|hex_id| hex_id.is_valid(size))
+    }
+}
+
+/// The range of HexId x values in a row of the grid, both inclusive.
+/// Every hex in a row has the same x + y, and the grid x counts from the highest x downwards.
+fn row_x_range(grid_y: u32, size: u32) -> (u32, u32) {
+    let sum = (grid_y + 2) as i64;
+    let size = size as i64;
+
+    let min_x = 1.max(sum - (size * 2 - 1)).max((sum - size + 2) / 2);
+    let max_x = (sum - 1).min(size * 2 - 1).min((sum + size - 1) / 2);
+
+    (min_x.max(0) as u32, max_x.max(0) as u32)
+}
+
+/// Cube coordinates of a hex, with (0, 0, 0) in the center of the board.
+/// The three coordinates always add up to 0, so (q, r) alone are the axial coordinates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CubeId {
+    pub q: i32,
+    pub r: i32,
+    pub s: i32,
+}
+
+impl Display for CubeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {}, {})", self.q, self.r, self.s)
+    }
+}
+
+impl CubeId {
+    pub fn new(q: i32, r: i32, s: i32) -> CubeId {
+        debug_assert_eq!(0, q + r + s, "Cube coordinates must add up to 0.");
+        CubeId { q, r, s }
+    }
+
+    pub fn from_axial(q: i32, r: i32) -> CubeId {
+        CubeId::new(q, r, -q - r)
+    }
+
+    pub fn to_axial(&self) -> (i32, i32) {
+        (self.q, self.r)
+    }
+
+    /// The amount of steps from the center of the board.
+    pub fn length(&self) -> u32 {
+        self.q
+            .unsigned_abs()
+            .max(self.r.unsigned_abs())
+            .max(self.s.unsigned_abs())
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::hexy::hexagon::row_count;
+    use crate::hexy::hexagon::team_color;
+    use crate::hexy::hexagon::CubeId;
     use crate::hexy::hexagon::GridId;
     use crate::hexy::hexagon::HexId;
+    use crate::util::hex_count;
 
-    #[test] fn test_x6_y7_size4_test_both() { assert_eq!(HexId::new(6, 7), GridId::new(2, 11).to_hex_id(4)); }
+    fn all_grid_ids(size: u32) -> Vec<GridId> {
+        (0..row_count(size))
+            .flat_map(|y| (0..size).map(move |x| GridId::new(x, y)))
+            .filter(|grid_id| grid_id.is_valid(size))
+            .collect()
+    }
+
+    #[test]
+    fn test_grid_id_count_all_sizes() {
+        for size in 0..=12 {
+            assert_eq!(
+                hex_count(size) as usize,
+                all_grid_ids(size).len(),
+                "size={size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_grid_id_round_trip_all_sizes() {
+        for size in 1..=12 {
+            for grid_id in all_grid_ids(size) {
+                let hex_id = grid_id.to_hex_id(size);
+                assert!(
+                    hex_id.is_valid(size),
+                    "size={size}, grid_id={grid_id}, hex_id={hex_id}"
+                );
+                assert_eq!(Some(grid_id), hex_id.to_grid_id(size));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hex_id_validity_all_sizes() {
+        for size in 1..=12 {
+            let max = size * 2;
+            let valid: Vec<HexId> = (0..=max)
+                .flat_map(|x| (0..=max).map(move |y| HexId::new(x, y)))
+                .filter(|hex_id| hex_id.is_valid(size))
+                .collect();
+
+            assert_eq!(hex_count(size) as usize, valid.len(), "size={size}");
+            for hex_id in valid {
+                let grid_id = hex_id.to_grid_id(size).expect("Valid HexId has a GridId.");
+                assert!(
+                    grid_id.is_valid(size),
+                    "size={size}, hex_id={hex_id}, grid_id={grid_id}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_cube_round_trip_all_sizes() {
+        for size in 1..=12 {
+            for grid_id in all_grid_ids(size) {
+                let hex_id = grid_id.to_hex_id(size);
+                let cube = hex_id.to_cube(size);
+                assert!(
+                    cube.length() < size,
+                    "size={size}, hex_id={hex_id}, cube={cube}"
+                );
+                assert_eq!(
+                    cube,
+                    CubeId::from_axial(cube.to_axial().0, cube.to_axial().1)
+                );
+                assert_eq!(Some(hex_id), HexId::from_cube(cube, size));
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_ids() {
+        assert!(!HexId::new(0, 1).is_valid(4));
+        assert!(!HexId::new(1, 5).is_valid(4));
+        assert!(!HexId::new(8, 7).is_valid(4));
+        assert!(!HexId::new(1, 1).is_valid(0));
+        assert_eq!(None, HexId::new(7, 1).to_grid_id(4));
+        assert_eq!(None, HexId::from_cube(CubeId::new(4, -4, 0), 4));
+        assert_eq!(None, HexId::from_cube(CubeId::new(-4, 0, 4), 4));
+        assert!(!GridId::new(1, 0).is_valid(4));
+        assert!(!GridId::new(3, 4).is_valid(4));
+        assert!(!GridId::new(1, 12).is_valid(4));
+        assert!(!GridId::new(0, 13).is_valid(4));
+    }
+
+    #[test]
+    fn test_cube_corners_size4() {
+        assert_eq!(CubeId::new(-3, 3, 0), HexId::new(1, 1).to_cube(4));
+        assert_eq!(CubeId::new(0, 0, 0), HexId::new(4, 4).to_cube(4));
+        assert_eq!(CubeId::new(3, -3, 0), HexId::new(7, 7).to_cube(4));
+        assert_eq!(CubeId::new(3, 0, -3), HexId::new(7, 4).to_cube(4));
+        assert_eq!(CubeId::new(-3, 0, 3), HexId::new(1, 4).to_cube(4));
+    }
+
+    #[test] fn test_x6_y7_size4_test_both() { assert_eq!(HexId::new(6, 7), GridId::new(1, 11).to_hex_id(4)); }
 
 
     #[test] fn test_x1_y1_size4_test_both() { assert_eq!(HexId::new(1, 1), GridId::new(0, 0).to_hex_id(4)); }
@@ -187,8 +412,8 @@ mod test {
     #[test] fn test_x3_y6_size4_test_both() { assert_eq!(HexId::new(3, 6), GridId::new(3, 7).to_hex_id(4)); }
     #[test] fn test_x7_y4_size4_test_both() { assert_eq!(HexId::new(7, 4), GridId::new(0, 9).to_hex_id(4)); }
     #[test] fn test_x7_y5_size4_test_both() { assert_eq!(HexId::new(7, 5), GridId::new(0, 10).to_hex_id(4)); }
-    #[test] fn test_x7_y6_size4_test_both() { assert_eq!(HexId::new(7, 6), GridId::new(1, 11).to_hex_id(4)); }
-    #[test] fn test_x7_y7_size4_test_both() { assert_eq!(HexId::new(7, 7), GridId::new(1, 12).to_hex_id(4)); }
+    #[test] fn test_x7_y6_size4_test_both() { assert_eq!(HexId::new(7, 6), GridId::new(0, 11).to_hex_id(4)); }
+    #[test] fn test_x7_y7_size4_test_both() { assert_eq!(HexId::new(7, 7), GridId::new(0, 12).to_hex_id(4)); }
     #[test] fn test_x5_y7_size4_test_both() { assert_eq!(HexId::new(5, 7), GridId::new(2, 10).to_hex_id(4)); }
     #[test] fn test_x4_y7_size4_test_both() { assert_eq!(HexId::new(4, 7), GridId::new(3, 9).to_hex_id(4)); }
 
@@ -225,9 +450,9 @@ mod test {
     #[test] fn test_x3_y6_size4_test_x() { assert_eq!(3, GridId::new(3, 7).to_hex_id(4).x); }
     #[test] fn test_x7_y4_size4_test_x() { assert_eq!(7, GridId::new(0, 9).to_hex_id(4).x); }
     #[test] fn test_x7_y5_size4_test_x() { assert_eq!(7, GridId::new(0, 10).to_hex_id(4).x); }
-    #[test] fn test_x7_y6_size4_test_x() { assert_eq!(7, GridId::new(1, 11).to_hex_id(4).x); }
-    #[test] fn test_x7_y7_size4_test_x() { assert_eq!(7, GridId::new(1, 12).to_hex_id(4).x); }
-    #[test] fn test_x6_y7_size4_test_x() { assert_eq!(6, GridId::new(2, 11).to_hex_id(4).x); }
+    #[test] fn test_x7_y6_size4_test_x() { assert_eq!(7, GridId::new(0, 11).to_hex_id(4).x); }
+    #[test] fn test_x7_y7_size4_test_x() { assert_eq!(7, GridId::new(0, 12).to_hex_id(4).x); }
+    #[test] fn test_x6_y7_size4_test_x() { assert_eq!(6, GridId::new(1, 11).to_hex_id(4).x); }
     #[test] fn test_x5_y7_size4_test_x() { assert_eq!(5, GridId::new(2, 10).to_hex_id(4).x); }
     #[test] fn test_x4_y7_size4_test_x() { assert_eq!(4, GridId::new(3, 9).to_hex_id(4).x); }
 
@@ -264,9 +489,18 @@ mod test {
     #[test] fn test_x3_y6_size4_test_y() { assert_eq!(6, GridId::new(3, 7).to_hex_id(4).y); }
     #[test] fn test_x7_y4_size4_test_y() { assert_eq!(4, GridId::new(0, 9).to_hex_id(4).y); }
     #[test] fn test_x7_y5_size4_test_y() { assert_eq!(5, GridId::new(0, 10).to_hex_id(4).y); }
-    #[test] fn test_x7_y6_size4_test_y() { assert_eq!(6, GridId::new(1, 11).to_hex_id(4).y); }
-    #[test] fn test_x7_y7_size4_test_y() { assert_eq!(7, GridId::new(1, 12).to_hex_id(4).y); }
-    #[test] fn test_x6_y7_size4_test_y() { assert_eq!(7, GridId::new(2, 11).to_hex_id(4).y); }
+    #[test] fn test_x7_y6_size4_test_y() { assert_eq!(6, GridId::new(0, 11).to_hex_id(4).y); }
+    #[test] fn test_x7_y7_size4_test_y() { assert_eq!(7, GridId::new(0, 12).to_hex_id(4).y); }
+    #[test] fn test_x6_y7_size4_test_y() { assert_eq!(7, GridId::new(1, 11).to_hex_id(4).y); }
     #[test] fn test_x5_y7_size4_test_y() { assert_eq!(7, GridId::new(2, 10).to_hex_id(4).y); }
     #[test] fn test_x4_y7_size4_test_y() { assert_eq!(7, GridId::new(3, 9).to_hex_id(4).y); }
+
+    #[test]
+    fn test_team_colors_differ() {
+        for team in 0..6 {
+            for other in 0..team {
+                assert_ne!(team_color(team), team_color(other), "{team} {other}");
+            }
+        }
+    }
 }
@@ -0,0 +1,201 @@
+//! Where the hexes of a board are drawn, in pixels.
+//!
+//! The board is laid out on the screen rows of the hexes, `x + y`, and their screen columns,
+//! `y - x`. With flat-top hexes a1 is the top corner and every column is a vertical line of hexes,
+//! like the HTML board. With pointy-top hexes the board is turned a twelfth of a circle
+//! counterclockwise, so every line of the notation is a horizontal row.
+
+use rocket::FromFormField;
+
+use super::hex_board::HexBoard;
+use super::hexagon::HexId;
+
+const SQRT_3: f64 = 1.732_050_807_568_877_2;
+
+/// Which way up the hexes are drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum Orientation {
+    /// The hexes have a flat edge at the top, neighbours are above and below each other.
+    #[default]
+    #[field(value = "flat")]
+    FlatTop,
+    /// The hexes have a corner at the top, neighbours are left and right of each other.
+    #[field(value = "pointy")]
+    PointyTop,
+}
+
+impl Orientation {
+    /// The center of a hex, from the center of the hex at (0, 0), for hexes with `radius`.
+    pub fn center(&self, hex_id: &HexId, radius: f64) -> (f64, f64) {
+        let (x, y) = (hex_id.x as f64, hex_id.y as f64);
+        match self {
+            Orientation::FlatTop => (1.5 * radius * (y - x), SQRT_3 / 2.0 * radius * (x + y)),
+            Orientation::PointyTop => (SQRT_3 * radius * (y - x / 2.0), 1.5 * radius * x),
+        }
+    }
+
+    /// How far a hex with `radius` reaches left and right, and up and down, from its center.
+    pub fn extent(&self, radius: f64) -> (f64, f64) {
+        match self {
+            Orientation::FlatTop => (radius, SQRT_3 / 2.0 * radius),
+            Orientation::PointyTop => (SQRT_3 / 2.0 * radius, radius),
+        }
+    }
+
+    /// The angle of the first corner of a hex, the others follow every sixth of a circle.
+    fn first_corner(&self) -> f64 {
+        match self {
+            Orientation::FlatTop => 0.0,
+            Orientation::PointyTop => std::f64::consts::FRAC_PI_6,
+        }
+    }
+}
+
+/// The pixels a board is drawn on: the size of the image and where every hex goes in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub orientation: Orientation,
+    /// From the center of a hex to its corners.
+    pub radius: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Moves the centers from `Orientation::center` into the image.
+    offset: (f64, f64),
+}
+
+impl Layout {
+    /// Fits the hexes of `board` in an image, with `margin` pixels around them.
+    pub fn new(board: &HexBoard, orientation: Orientation, radius: f64, margin: f64) -> Layout {
+        let (left, top, right, bottom) = board
+            .hexagons()
+            .map(|hexagon| orientation.center(&hexagon.hex_id, radius))
+            .fold(
+                (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                |(left, top, right, bottom), (x, y)| {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                },
+            );
+        if left > right {
+            return Layout {
+                orientation,
+                radius,
+                width: margin * 2.0,
+                height: margin * 2.0,
+                offset: (margin, margin),
+            };
+        }
+
+        let (extent_x, extent_y) = orientation.extent(radius);
+        Layout {
+            orientation,
+            radius,
+            width: right - left + (extent_x + margin) * 2.0,
+            height: bottom - top + (extent_y + margin) * 2.0,
+            offset: (margin + extent_x - left, margin + extent_y - top),
+        }
+    }
+
+    /// Fits the hexes of `board` in an image at most `width` by `height` pixels, with `margin`
+    /// pixels around them. The board keeps its shape, so one side is usually shorter.
+    pub fn fit(
+        board: &HexBoard,
+        orientation: Orientation,
+        (width, height): (f64, f64),
+        margin: f64,
+    ) -> Layout {
+        let unit = Layout::new(board, orientation, 1.0, 0.0);
+        let radius_x = (width - margin * 2.0).max(0.0) / unit.width.max(f64::EPSILON);
+        let radius_y = (height - margin * 2.0).max(0.0) / unit.height.max(f64::EPSILON);
+
+        Layout::new(board, orientation, radius_x.min(radius_y), margin)
+    }
+
+    /// Where the center of the hex is in the image.
+    pub fn center(&self, hex_id: &HexId) -> (f64, f64) {
+        let (x, y) = self.orientation.center(hex_id, self.radius);
+        (x + self.offset.0, y + self.offset.1)
+    }
+
+    /// The corners of the hex in the image, clockwise.
+    pub fn corners(&self, hex_id: &HexId) -> [(f64, f64); 6] {
+        let (x, y) = self.center(hex_id);
+        let first = self.orientation.first_corner();
+
+        std::array::from_fn(|i| {
+            let angle = first + std::f64::consts::FRAC_PI_3 * i as f64;
+            (x + self.radius * angle.cos(), y + self.radius * angle.sin())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::geometry::Direction;
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::HexId;
+    use crate::hexy::layout::{Layout, Orientation};
+    use crate::hexy::shape::BoardShape;
+
+    #[test]
+    fn test_neighbours_touch() {
+        let center = HexId::new(3, 3);
+        for orientation in [Orientation::FlatTop, Orientation::PointyTop] {
+            let layout = Layout::new(&HexBoard::new(3), orientation, 10.0, 0.0);
+            let (x, y) = layout.center(&center);
+            for direction in Direction::ALL {
+                let neighbour = center.step(direction, 1, 3).unwrap();
+                let (nx, ny) = layout.center(&neighbour);
+                let distance = ((nx - x).powi(2) + (ny - y).powi(2)).sqrt();
+                assert!(
+                    (distance - 10.0 * 3f64.sqrt()).abs() < 1e-9,
+                    "{orientation:?} {direction:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fit() {
+        for board in [
+            HexBoard::new(5),
+            HexBoard::with_shape(BoardShape::Rhombus(6)),
+            HexBoard::with_shape(BoardShape::Rectangle {
+                width: 1,
+                height: 50,
+            }),
+        ] {
+            for orientation in [Orientation::FlatTop, Orientation::PointyTop] {
+                let layout = Layout::fit(&board, orientation, (400.0, 300.0), 10.0);
+                assert!(layout.width < 400.0 + 1e-9, "{orientation:?}");
+                assert!(layout.height < 300.0 + 1e-9, "{orientation:?}");
+                assert!(
+                    (layout.width - 400.0).abs() < 1e-9 || (layout.height - 300.0).abs() < 1e-9,
+                    "{orientation:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_fits() {
+        let board = HexBoard::new(2);
+        let layout = Layout::new(&board, Orientation::FlatTop, 10.0, 5.0);
+        assert!((layout.width - 60.0).abs() < 1e-9);
+        assert!((layout.height - (30.0 * 3f64.sqrt() + 10.0)).abs() < 1e-9);
+
+        // The top corner of the board, a flat edge touching the margin.
+        let (x, y) = layout.center(&HexId::new(1, 1));
+        assert!((x - 30.0).abs() < 1e-9);
+        assert!((y - (5.0 + 5.0 * 3f64.sqrt())).abs() < 1e-9);
+
+        for orientation in [Orientation::FlatTop, Orientation::PointyTop] {
+            let layout = Layout::new(&board, orientation, 10.0, 5.0);
+            for hexagon in board.hexagons() {
+                for (x, y) in layout.corners(&hexagon.hex_id) {
+                    assert!(x > 4.99 && x < layout.width - 4.99, "{orientation:?}");
+                    assert!(y > 4.99 && y < layout.height - 4.99, "{orientation:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Who plays which team of a game.
+//!
+//! Browsers are told apart by a random id in a cookie, so players do not need an account. A team
+//! with a player in its seat can only be moved by that player. Once anyone sat down, and in every
+//! game listed in the lobby, the teams of empty seats wait for a player. Only an unlisted game
+//! without any player can be moved by anyone, like on a shared screen.
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use rocket::http::{Cookie, SameSite};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{FromFormField, Request};
+use serde::Serialize;
+
+use super::hex_board::Renderer;
+use super::notation::team_letter;
+
+pub type PlayerId = u64;
+
+const PLAYER_COOKIE: &str = "hexy_player";
+const NAME_COOKIE: &str = "hexy_name";
+const RENDERER_COOKIE: &str = "hexy_renderer";
+
+/// The longest name a player can sit down with.
+pub const MAX_NAME_LENGTH: usize = 30;
+
+/// The browser a request came from, with the name it last sat down with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    pub id: PlayerId,
+    pub name: Option<String>,
+    /// How the browser wants boards drawn.
+    pub renderer: Renderer,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Player {
+    type Error = std::convert::Infallible;
+
+    /// Gives a browser without an id a new one.
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Player, Self::Error> {
+        let cookies = request.cookies();
+        let id = cookies
+            .get(PLAYER_COOKIE)
+            .and_then(|cookie| cookie.value().parse().ok())
+            .unwrap_or_else(|| {
+                let id: PlayerId = rand::random();
+                cookies.add(player_cookie(PLAYER_COOKIE, id.to_string()));
+                id
+            });
+        let name = cookies
+            .get(NAME_COOKIE)
+            .map(|cookie| cookie.value().to_string());
+        let renderer = cookies
+            .get(RENDERER_COOKIE)
+            .and_then(|cookie| Renderer::from_name(cookie.value()))
+            .unwrap_or_default();
+
+        Outcome::Success(Player { id, name, renderer })
+    }
+}
+
+impl Player {
+    /// Remembers the name for the next time the player sits down.
+    pub fn name_cookie(name: &str) -> Cookie<'static> {
+        player_cookie(NAME_COOKIE, name.to_string())
+    }
+
+    /// Remembers how the browser wants boards drawn.
+    pub fn renderer_cookie(renderer: Renderer) -> Cookie<'static> {
+        player_cookie(RENDERER_COOKIE, renderer.name().to_string())
+    }
+}
+
+fn player_cookie(name: &'static str, value: String) -> Cookie<'static> {
+    Cookie::build((name, value))
+        .path("/hexy")
+        .same_site(SameSite::Lax)
+        .permanent()
+        .build()
+}
+
+/// Checks a name players chose for themselves.
+pub fn clean_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Choose a name to sit down with.".into());
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "A name can not be longer than {MAX_NAME_LENGTH} characters."
+        ));
+    }
+
+    Ok(name.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Seat {
+    pub player: PlayerId,
+    pub name: String,
+}
+
+/// A seat for every team of a game, by team index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seats {
+    seats: Vec<Option<Seat>>,
+}
+
+impl Seats {
+    pub fn new(teams: u32) -> Seats {
+        Seats {
+            seats: vec![None; teams as usize],
+        }
+    }
+
+    pub fn get(&self, team: u32) -> Option<&Seat> {
+        self.seats.get(team as usize)?.as_ref()
+    }
+
+    pub fn teams(&self) -> u32 {
+        self.seats.len() as u32
+    }
+
+    /// The teams the player sits at.
+    pub fn teams_of(&self, player: PlayerId) -> Vec<u32> {
+        (0..self.teams())
+            .filter(|team| self.get(*team).is_some_and(|seat| seat.player == player))
+            .collect()
+    }
+
+    pub fn is_seated(&self, player: PlayerId) -> bool {
+        !self.teams_of(player).is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seats.iter().all(Option::is_none)
+    }
+
+    /// Seats the player at a team, a player can sit at more than one team.
+    pub fn join(&mut self, team: u32, player: PlayerId, name: &str) -> Result<(), String> {
+        let seat = self
+            .seats
+            .get_mut(team as usize)
+            .ok_or_else(|| format!("There is no team {}.", team_letter(team)))?;
+
+        match seat {
+            Some(seat) if seat.player != player => Err(format!(
+                "{} already plays team {}.",
+                seat.name,
+                team_letter(team)
+            )),
+            _ => {
+                *seat = Some(Seat {
+                    player,
+                    name: name.to_string(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Frees every seat of the player, false when it had none.
+    pub fn leave(&mut self, player: PlayerId) -> bool {
+        let mut left = false;
+        for seat in &mut self.seats {
+            if seat.as_ref().is_some_and(|seat| seat.player == player) {
+                *seat = None;
+                left = true;
+            }
+        }
+
+        left
+    }
+}
+
+/// The rules a game is played with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum Variant {
+    #[field(value = "standard")]
+    Standard,
+}
+
+impl Variant {
+    /// The name in game records.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Variant> {
+        match name {
+            "Standard" => Some(Variant::Standard),
+            _ => None,
+        }
+    }
+}
+
+/// How much time each team has for the whole game, how much it gains with every move, and how
+/// long it can think about a move before its clock starts to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+    pub delay: Duration,
+}
+
+impl TimeControl {
+    /// A time control from the minutes and seconds players pick, none without minutes.
+    pub fn new(minutes: u32, increment_seconds: u32, delay_seconds: u32) -> Option<TimeControl> {
+        (minutes > 0).then(|| TimeControl {
+            base: Duration::from_secs(minutes as u64 * 60),
+            increment: Duration::from_secs(increment_seconds as u64),
+            delay: Duration::from_secs(delay_seconds as u64),
+        })
+    }
+}
+
+/// Written like `5+3`, for five minutes and three seconds more for every move, and `5+0 d2`
+/// with a delay of two seconds.
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}+{}",
+            self.base.as_secs() / 60,
+            self.increment.as_secs()
+        )?;
+        if !self.delay.is_zero() {
+            write!(f, " d{}", self.delay.as_secs())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::hexy::lobby::{clean_name, Seats, TimeControl};
+
+    #[test]
+    fn test_join_and_leave() {
+        let mut seats = Seats::new(3);
+        assert!(seats.is_empty());
+
+        seats.join(0, 7, "Ann").unwrap();
+        seats.join(2, 7, "Ann").unwrap();
+        assert_eq!(
+            Err("Ann already plays team a.".into()),
+            seats.join(0, 8, "Bob")
+        );
+        assert!(seats.join(3, 8, "Bob").is_err());
+        seats.join(1, 8, "Bob").unwrap();
+
+        assert_eq!(vec![0, 2], seats.teams_of(7));
+        assert_eq!("Bob", seats.get(1).unwrap().name);
+        assert!(seats.leave(7));
+        assert!(!seats.leave(7));
+        assert!(!seats.is_seated(7));
+        assert!(seats.is_seated(8));
+        assert_eq!(None, seats.get(0));
+    }
+
+    #[test]
+    fn test_clean_name() {
+        assert_eq!(Ok("Ann".to_string()), clean_name("  Ann "));
+        assert!(clean_name(" ").is_err());
+        assert!(clean_name(&"x".repeat(31)).is_err());
+    }
+
+    #[test]
+    fn test_time_control() {
+        assert_eq!(None, TimeControl::new(0, 5, 0));
+
+        let time_control = TimeControl::new(5, 3, 0).unwrap();
+        assert_eq!(Duration::from_secs(300), time_control.base);
+        assert_eq!("5+3", time_control.to_string());
+        assert_eq!("5+0 d2", TimeControl::new(5, 0, 2).unwrap().to_string());
+    }
+}
@@ -0,0 +1,452 @@
+//! The rules of Hexy.
+//!
+//! - Every team has one leader, the piece with value 0. A team without its leader on the board
+//!   is out of the game, its other pieces stay behind as obstacles that can still be captured.
+//! - A piece with value `v > 0` moves 1 to `v` steps in a straight line over free hexes. It may
+//!   end its move on a piece of another team with a value of at most `v`, capturing it.
+//!   Leaders can be captured by any piece.
+//! - A leader moves a single step to a free neighbouring hex, it can not capture.
+//! - The teams take turns in order of their index, skipping the teams that are out.
+//! - The last team with its leader on the board wins. When the team to move has no legal moves
+//!   the game ends in a draw.
+
+use std::fmt::Display;
+
+use super::geometry::Direction;
+use super::hex_board::{HexBoard, MAX_SIZE};
+use super::hexagon::{HexId, HexState};
+
+/// A move of a piece, `captured` is what was on the target hex before the move.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: HexId,
+    pub to: HexId,
+    pub captured: HexState,
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = match self.captured {
+            HexState::Free => '-',
+            HexState::Piece { .. } => 'x',
+        };
+
+        write!(
+            f,
+            "{}{}{}",
+            self.from.to_notation(),
+            separator,
+            self.to.to_notation()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Won(u32),
+    Draw,
+}
+
+/// The teams that still have their leader on the board, by increasing index.
+pub fn teams_in_play(board: &HexBoard) -> Vec<u32> {
+    let mut teams: Vec<u32> = board
+        .hexagons()
+        .filter_map(|hexagon| match hexagon.state {
+            HexState::Piece { team, value: 0 } if team < board.teams() => Some(team),
+            _ => None,
+        })
+        .collect();
+
+    teams.sort_unstable();
+    teams.dedup();
+    teams
+}
+
+/// All legal moves for a team, no moves are legal once the game is over.
+pub fn moves(board: &HexBoard, team: u32) -> Vec<Move> {
+    let in_play = teams_in_play(board);
+    if in_play.len() < 2 || !in_play.contains(&team) {
+        return Vec::new();
+    }
+
+    let shape = board.shape();
+    let mut moves = Vec::new();
+
+    for hexagon in board.hexagons() {
+        let HexState::Piece {
+            team: piece_team,
+            value,
+        } = hexagon.state
+        else {
+            continue;
+        };
+
+        if piece_team != team {
+            continue;
+        }
+
+        for direction in Direction::ALL {
+            if value == 0 {
+                let Some(to) = hexagon.hex_id.neighbour(direction, shape) else {
+                    continue;
+                };
+
+                if board
+                    .get(&to)
+                    .is_some_and(|target| target.state == HexState::Free)
+                {
+                    moves.push(Move {
+                        from: hexagon.hex_id.clone(),
+                        to,
+                        captured: HexState::Free,
+                    });
+                }
+
+                continue;
+            }
+
+            for to in hexagon
+                .hex_id
+                .ray(direction, shape)
+                .into_iter()
+                .take(value as usize)
+            {
+                let Some(target) = board.get(&to) else {
+                    break;
+                };
+
+                match target.state {
+                    HexState::Free => moves.push(Move {
+                        from: hexagon.hex_id.clone(),
+                        to,
+                        captured: HexState::Free,
+                    }),
+                    HexState::Piece {
+                        team: target_team,
+                        value: target_value,
+                    } => {
+                        if target_team != team && target_value <= value {
+                            moves.push(Move {
+                                from: hexagon.hex_id.clone(),
+                                to,
+                                captured: target.state,
+                            });
+                        }
+
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    moves
+}
+
+/// Finds the legal move for the side to move between two hexes.
+pub fn find_move(board: &HexBoard, from: &HexId, to: &HexId) -> Option<Move> {
+    moves(board, board.to_move())
+        .into_iter()
+        .find(|mv| &mv.from == from && &mv.to == to)
+}
+
+/// Parses a move like `d2-e3` or `d2xe3` and finds it among the legal moves.
+pub fn parse_move(board: &HexBoard, text: &str) -> Option<Move> {
+    // The separator comes after the row of the first hex, `x` is also the letter of a column.
+    let (separator, _) = text.char_indices().find(|(i, c)| {
+        ['-', 'x'].contains(c) && text[..*i].ends_with(|c: char| c.is_ascii_digit())
+    })?;
+    let from = HexId::from_notation(&text[..separator])?;
+    let to = HexId::from_notation(&text[separator + 1..])?;
+
+    find_move(board, &from, &to)
+}
+
+pub fn status(board: &HexBoard) -> GameStatus {
+    match teams_in_play(board).as_slice() {
+        [] => GameStatus::Draw,
+        [team] => GameStatus::Won(*team),
+        _ if moves(board, board.to_move()).is_empty() => GameStatus::Draw,
+        _ => GameStatus::Ongoing,
+    }
+}
+
+/// The corners of the board the teams start in, as directions from the center.
+fn starting_corners(teams: u32) -> Option<Vec<Direction>> {
+    let corners = match teams {
+        2 => vec![0, 3],
+        3 => vec![0, 2, 4],
+        4 => vec![1, 2, 4, 5],
+        5 => vec![0, 1, 2, 4, 5],
+        6 => vec![0, 1, 2, 3, 4, 5],
+        _ => return None,
+    };
+
+    Some(corners.into_iter().map(|i| Direction::ALL[i]).collect())
+}
+
+/// The standard starting position: every team starts in a corner with its leader, surrounded
+/// by pieces of value 3 and 2 and, on large enough boards, an outer ring of pieces of value 1.
+/// Returns None when the board is too small to keep the teams apart.
+pub fn starting_position(size: u32, teams: u32) -> Option<HexBoard> {
+    if !(2..=MAX_SIZE).contains(&size) {
+        return None;
+    }
+
+    let corners = starting_corners(teams)?;
+    let adjacent_corners = corners
+        .iter()
+        .any(|corner| corners.contains(&corner.rotate_clockwise(1)));
+    let corner_distance = if adjacent_corners {
+        size - 1
+    } else {
+        (size - 1) * 2
+    };
+    let radius = 2.min(corner_distance.saturating_sub(1) / 2);
+    if radius == 0 {
+        return None;
+    }
+
+    let mut board = HexBoard::new(size);
+    board.set_teams(teams);
+    board.set_to_move(0);
+
+    let center = HexId::new(size, size);
+    for (team, direction) in corners.into_iter().enumerate() {
+        let corner = center.step(direction, size - 1, size)?;
+        let towards_center = corner.neighbour(direction.opposite(), size);
+        let team = team as u32;
+
+        for (distance, hex_ids) in
+            (0..=radius).map(|distance| (distance, corner.ring(distance, size)))
+        {
+            for hex_id in hex_ids {
+                let value = match distance {
+                    0 => 0,
+                    1 if Some(&hex_id) == towards_center.as_ref() => 3,
+                    1 => 2,
+                    _ => 1,
+                };
+
+                board.set_state(&hex_id, HexState::Piece { team, value })?;
+            }
+        }
+    }
+
+    Some(board)
+}
+
+/// Counts the positions at exactly `depth` moves from this one, used to test the move generator.
+pub fn perft(board: &mut HexBoard, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = moves(board, board.to_move());
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves {
+        board.apply_move(&mv);
+        nodes += perft(board, depth - 1);
+        board.undo_move(&mv);
+    }
+
+    nodes
+}
+
+impl HexBoard {
+    /// Plays a move, which is expected to be legal for the side to move.
+    pub fn apply_move(&mut self, mv: &Move) {
+        let piece = self.set_state(&mv.from, HexState::Free).unwrap_or_default();
+        self.set_state(&mv.to, piece);
+
+        if let HexState::Piece { team, .. } = piece {
+            let in_play = teams_in_play(self);
+            let next = (1..=self.teams())
+                .map(|offset| (team + offset) % self.teams())
+                .find(|next| in_play.contains(next))
+                .unwrap_or(team);
+            self.set_to_move(next);
+        }
+    }
+
+    /// Takes back a move that was the last one applied to this board.
+    pub fn undo_move(&mut self, mv: &Move) {
+        let piece = self.set_state(&mv.to, mv.captured).unwrap_or_default();
+        self.set_state(&mv.from, piece);
+
+        if let HexState::Piece { team, .. } = piece {
+            self.set_to_move(team);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{HexId, HexState};
+    use crate::hexy::moves::{
+        find_move, moves, parse_move, perft, starting_position, status, GameStatus,
+    };
+
+    fn board(notation: &str) -> HexBoard {
+        HexBoard::from_notation(notation).unwrap()
+    }
+
+    #[test]
+    fn test_starting_positions() {
+        assert!(starting_position(2, 2).is_none());
+        assert!(starting_position(3, 4).is_none());
+        assert!(starting_position(4, 7).is_none());
+        assert_eq!(
+            "4 a0a2a11/a2a3a12/a1a1a13/7/3b1b1b1/2b1b3b2/1b1b2b0 a 2",
+            starting_position(4, 2).unwrap().to_notation()
+        );
+
+        for (size, teams) in [(3, 2), (3, 3), (4, 4), (4, 6), (6, 6)] {
+            let board = starting_position(size, teams).unwrap();
+            for team in 0..teams {
+                let pieces = board
+                    .hexagons()
+                    .filter(|hexagon| matches!(hexagon.state, HexState::Piece { team: t, .. } if t == team))
+                    .count();
+                assert!(pieces >= 4, "size={size}, teams={teams}, team={team}");
+            }
+            assert_eq!(GameStatus::Ongoing, status(&board));
+        }
+    }
+
+    #[test]
+    fn test_leader_moves_one_step_without_capturing() {
+        let board = board("3 a0b01/4/5/4/3 a 2");
+        let leader_moves: Vec<String> = moves(&board, 0).iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(vec!["a1-b2", "a1-b1"], leader_moves);
+        assert!(find_move(&board, &HexId::new(1, 1), &HexId::new(1, 2)).is_none());
+    }
+
+    #[test]
+    fn test_slides_and_captures() {
+        // A value 2 piece on a2, next to a friendly piece on a3 and a value 3 enemy on b3,
+        // with a value 1 enemy two steps away on c2.
+        let board = board("3 1a2a1/2b31/1b13/b03/a02 a 2");
+        let targets: Vec<String> = moves(&board, 0)
+            .iter()
+            .filter(|mv| mv.from == HexId::new(1, 2))
+            .map(|mv| mv.to_string())
+            .collect();
+        assert_eq!(vec!["a2-b2", "a2xc2", "a2-a1"], targets);
+        assert!(parse_move(&board, "a2xc2").is_some());
+        assert!(parse_move(&board, "a2-b3").is_none());
+    }
+
+    #[test]
+    fn test_moves_round_trip_on_large_boards() {
+        // Pieces in column `x` and in columns of two letters.
+        let mut board = HexBoard::new(20);
+        for (x, y, team, value) in [
+            (24, 20, 0, 0),
+            (24, 21, 0, 2),
+            (30, 30, 1, 0),
+            (29, 30, 1, 1),
+        ] {
+            board.set_state(&HexId::new(x, y), HexState::Piece { team, value });
+        }
+
+        for team in 0..2 {
+            board.set_to_move(team);
+            for mv in moves(&board, team) {
+                assert_eq!(Some(&mv), parse_move(&board, &mv.to_string()).as_ref());
+            }
+        }
+        board.set_to_move(0);
+        assert!(parse_move(&board, "x21-x22").is_some());
+    }
+
+    #[test]
+    fn test_apply_and_undo() {
+        let mut board = starting_position(4, 3).unwrap();
+        let notation = board.to_notation();
+
+        for mv in moves(&board, 0) {
+            board.apply_move(&mv);
+            assert_eq!(1, board.to_move());
+            board.undo_move(&mv);
+            assert_eq!(notation, board.to_notation());
+        }
+    }
+
+    #[test]
+    fn test_capturing_last_leader_wins() {
+        let mut board = board("2 a0a1/1b01/2 a 2");
+        assert_eq!(GameStatus::Ongoing, status(&board));
+
+        let capture = parse_move(&board, "a2xb2").unwrap();
+        assert_eq!(HexState::Piece { team: 1, value: 0 }, capture.captured);
+        board.apply_move(&capture);
+        assert_eq!(GameStatus::Won(0), status(&board));
+        assert!(moves(&board, 0).is_empty());
+    }
+
+    #[test]
+    fn test_eliminated_team_is_skipped() {
+        let mut board = board("3 a0a11/1b02/5/4/c02 a 3");
+        let capture = parse_move(&board, "a2xb2").unwrap();
+        board.apply_move(&capture);
+        assert_eq!(2, board.to_move());
+        board.undo_move(&capture);
+        assert_eq!(0, board.to_move());
+    }
+
+    #[test]
+    fn test_stuck_side_to_move_is_a_draw() {
+        assert_eq!(GameStatus::Draw, status(&board("2 a0b1/b1b01/2 a 2")));
+    }
+
+    #[test]
+    fn test_perft_size4_two_teams() {
+        let mut board = starting_position(4, 2).unwrap();
+        assert_eq!(
+            vec![1, 11, 121, 2028, 33842],
+            (0..5)
+                .map(|depth| perft(&mut board, depth))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_perft_size3_two_teams() {
+        let mut board = starting_position(3, 2).unwrap();
+        assert_eq!(
+            vec![1, 12, 135, 2075, 31741],
+            (0..5)
+                .map(|depth| perft(&mut board, depth))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_perft_size5_three_teams() {
+        let mut board = starting_position(5, 3).unwrap();
+        assert_eq!(
+            vec![1, 11, 121, 1331, 22627],
+            (0..5)
+                .map(|depth| perft(&mut board, depth))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_perft_size4_six_teams() {
+        let mut board = starting_position(4, 6).unwrap();
+        assert_eq!(
+            vec![1, 13, 158, 1896, 22404],
+            (0..5)
+                .map(|depth| perft(&mut board, depth))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,818 @@
+//! A compact text notation for Hexy positions, in the spirit of FEN for chess.
+//!
+//! A position is written as four fields separated by a single space:
+//!
+//! ```text
+//! <shape> <lines> <side to move> <teams>
+//! ```
+//!
+//! - `shape` is the shape of the board, see `BoardShape`. A regular hexagon is written as the
+//!   amount of hexes on a side, a rhombus, triangle and star as that amount after an `r`, `t`
+//!   or `s`, and a rectangle as its width and height like `8x6`. A custom shape is an `m`
+//!   followed by a line for every x, separated by `/`. Such a line is the length of every run of
+//!   hexes by increasing y, alternating between runs that are not on the board and runs that
+//!   are, separated by `.`. So `m0.2/0.1.1.1/1.2` is the hexagon of size 2 without its center.
+//! - `lines` has one line for every HexId x from 1 to the highest x of the shape, separated by
+//!   `/`. A line lists its hexes by increasing y. A run of free hexes is written as its length,
+//!   a piece as its team letter (`a` is team 0) followed by its value. Values of one digit are
+//!   written as is, larger values up to `MAX_VALUE` are put in parentheses, so `a(12)3` is a
+//!   piece of team 0 with value 12 followed by three free hexes.
+//! - `side to move` is the letter of the team whose turn it is.
+//! - `teams` is the amount of teams playing.
+//!
+//! The empty board of size 2 with two teams is `2 2/3/2 a 2`.
+//!
+//! A single hex is written as the letters of its x followed by its y, so the top hex of every
+//! board is `a1`. The letters count like the columns of a spreadsheet: `a` is 1, `z` is 26,
+//! `aa` is 27 and `go` is 197, the most lines a board has.
+
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use super::hex_board::{HexBoard, MAX_EXTENT, MAX_SIZE};
+use super::hexagon::{HexId, HexState};
+use super::shape::{BoardShape, Mask, Shape};
+
+/// The most teams a position can have, every team is written as a letter.
+pub const MAX_TEAMS: u32 = 26;
+
+/// The highest value a piece can have, which keeps the scores of engines far from overflowing.
+pub const MAX_VALUE: u32 = 999;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationErrorKind {
+    MissingField(&'static str),
+    TrailingInput,
+    InvalidNumber,
+    InvalidSize,
+    InvalidMask,
+    InvalidTeamCount(u32),
+    UnexpectedChar(char),
+    UnclosedValue,
+    LineTooLong { line: u32, length: u32 },
+    LineTooShort { line: u32, length: u32, found: u32 },
+    WrongLineCount { expected: u32, found: u32 },
+    TeamOutOfRange(u32),
+    ValueTooLarge(u32),
+}
+
+impl Display for NotationErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationErrorKind::MissingField(field) => write!(f, "missing {field}"),
+            NotationErrorKind::TrailingInput => write!(f, "unexpected input after the last field"),
+            NotationErrorKind::InvalidNumber => write!(f, "expected a number"),
+            NotationErrorKind::InvalidSize => {
+                write!(f, "the board size is not between 1 and {MAX_SIZE}")
+            }
+            NotationErrorKind::InvalidMask => write!(
+                f,
+                "a custom board needs at least one hex and at most {MAX_EXTENT} lines of \
+                 {MAX_EXTENT} hexes"
+            ),
+            NotationErrorKind::InvalidTeamCount(teams) => {
+                write!(f, "{teams} teams is not between 1 and {MAX_TEAMS}")
+            }
+            NotationErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{c}'"),
+            NotationErrorKind::UnclosedValue => write!(f, "missing ')' after a piece value"),
+            NotationErrorKind::LineTooLong { line, length } => {
+                write!(f, "line {line} has more than {length} hexes")
+            }
+            NotationErrorKind::LineTooShort {
+                line,
+                length,
+                found,
+            } => {
+                write!(f, "line {line} has {found} of its {length} hexes")
+            }
+            NotationErrorKind::WrongLineCount { expected, found } => {
+                write!(f, "expected {expected} lines, found {found}")
+            }
+            NotationErrorKind::TeamOutOfRange(team) => {
+                write!(f, "team '{}' is not playing", team_letter(*team))
+            }
+            NotationErrorKind::ValueTooLarge(value) => {
+                write!(f, "a piece value of {value} is more than {MAX_VALUE}")
+            }
+        }
+    }
+}
+
+/// A notation that could not be parsed, `position` is the index of the offending character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotationError {
+    pub position: usize,
+    pub kind: NotationErrorKind,
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid notation at character {}: {}",
+            self.position, self.kind
+        )
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+pub fn team_letter(team: u32) -> char {
+    char::from_u32('a' as u32 + team).unwrap_or('?')
+}
+
+fn letter_team(letter: char) -> Option<u32> {
+    letter
+        .is_ascii_lowercase()
+        .then(|| letter as u32 - 'a' as u32)
+}
+
+impl HexId {
+    pub fn to_notation(&self) -> String {
+        let mut letters = Vec::new();
+        let mut x = self.x;
+        while x > 0 {
+            x -= 1;
+            letters.push(char::from(b'a' + (x % 26) as u8));
+            x /= 26;
+        }
+
+        let column: String = letters.iter().rev().collect();
+        format!("{column}{}", self.y)
+    }
+
+    /// Parses a hex like `d2` or `ab14`, without checking if it is on the board.
+    pub fn from_notation(text: &str) -> Option<HexId> {
+        let digits = text.find(|c: char| !c.is_ascii_lowercase())?;
+        let (column, row) = text.split_at(digits);
+        if column.is_empty() {
+            return None;
+        }
+
+        let x = column.chars().try_fold(0u32, |x, letter| {
+            x.checked_mul(26)?
+                .checked_add(letter as u32 - 'a' as u32 + 1)
+        })?;
+        let y = row.parse().ok().filter(|y| *y > 0)?;
+
+        Some(HexId::new(x, y))
+    }
+}
+
+impl HexBoard {
+    pub fn to_notation(&self) -> String {
+        let shape = self.shape();
+        let mut lines = Vec::new();
+
+        for x in 1..=shape.max_x() {
+            let mut line = String::new();
+            let mut free = 0;
+
+            for hex_id in shape.line(x) {
+                match self.get(&hex_id).map(|hexagon| hexagon.state) {
+                    Some(HexState::Piece { team, value }) => {
+                        if free > 0 {
+                            line.push_str(&free.to_string());
+                            free = 0;
+                        }
+
+                        line.push(team_letter(team));
+                        if value < 10 {
+                            line.push_str(&value.to_string());
+                        } else {
+                            line.push_str(&format!("({value})"));
+                        }
+                    }
+                    _ => free += 1,
+                }
+            }
+
+            if free > 0 {
+                line.push_str(&free.to_string());
+            }
+
+            lines.push(line);
+        }
+
+        format!(
+            "{} {} {} {}",
+            shape.to_notation(),
+            lines.join("/"),
+            team_letter(self.to_move()),
+            self.teams()
+        )
+    }
+
+    pub fn from_notation(notation: &str) -> Result<HexBoard, NotationError> {
+        let mut parser = Parser {
+            chars: notation.char_indices().peekable(),
+            length: notation.len(),
+        };
+
+        let shape = parser.shape()?;
+        parser.separator("board")?;
+
+        let mut board = HexBoard::with_shape(shape);
+        let mut pieces = Vec::new();
+        let line_count = board.shape().max_x();
+        let mut x = 1;
+
+        loop {
+            let line = board.shape().line(x);
+            let length = line.len() as u32;
+            let mut hex_ids = line.into_iter();
+            let mut found = 0;
+
+            while let Some(&(position, c)) = parser.chars.peek() {
+                if c == '/' || c == ' ' {
+                    break;
+                }
+
+                if c.is_ascii_digit() {
+                    let free = parser.number().map_err(|kind| error_at(position, kind))?;
+                    for _ in 0..free {
+                        hex_ids.next().ok_or(NotationError {
+                            position,
+                            kind: NotationErrorKind::LineTooLong { line: x, length },
+                        })?;
+                    }
+
+                    found += free;
+                    continue;
+                }
+
+                let team = letter_team(c).ok_or(NotationError {
+                    position,
+                    kind: NotationErrorKind::UnexpectedChar(c),
+                })?;
+                parser.chars.next();
+                let value = parser.piece_value()?;
+                let hex_id = hex_ids.next().ok_or(NotationError {
+                    position,
+                    kind: NotationErrorKind::LineTooLong { line: x, length },
+                })?;
+
+                pieces.push((position, hex_id, HexState::Piece { team, value }));
+                found += 1;
+            }
+
+            if found < length {
+                return Err(parser.error_here(NotationErrorKind::LineTooShort {
+                    line: x,
+                    length,
+                    found,
+                }));
+            }
+
+            match parser.chars.peek() {
+                Some((position, '/')) if x == line_count => {
+                    return Err(error_at(
+                        *position,
+                        NotationErrorKind::WrongLineCount {
+                            expected: line_count,
+                            found: x + 1,
+                        },
+                    ));
+                }
+                Some((_, '/')) => {
+                    parser.chars.next();
+                    x += 1;
+                }
+                _ if x < line_count => {
+                    return Err(parser.error_here(NotationErrorKind::WrongLineCount {
+                        expected: line_count,
+                        found: x,
+                    }));
+                }
+                _ => break,
+            }
+        }
+
+        parser.separator("side to move")?;
+        let (side_position, side) = parser
+            .chars
+            .next()
+            .ok_or(parser.error_here(NotationErrorKind::MissingField("side to move")))?;
+        let to_move = letter_team(side).ok_or(NotationError {
+            position: side_position,
+            kind: NotationErrorKind::UnexpectedChar(side),
+        })?;
+
+        parser.separator("team count")?;
+        let position = parser.position();
+        let teams = parser.number().map_err(|kind| error_at(position, kind))?;
+        if teams == 0 || teams > MAX_TEAMS {
+            return Err(error_at(
+                position,
+                NotationErrorKind::InvalidTeamCount(teams),
+            ));
+        }
+
+        if parser.chars.peek().is_some() {
+            return Err(parser.error_here(NotationErrorKind::TrailingInput));
+        }
+
+        if to_move >= teams {
+            return Err(error_at(
+                side_position,
+                NotationErrorKind::TeamOutOfRange(to_move),
+            ));
+        }
+
+        for (position, hex_id, state) in pieces {
+            if let HexState::Piece { team, .. } = state {
+                if team >= teams {
+                    return Err(error_at(position, NotationErrorKind::TeamOutOfRange(team)));
+                }
+            }
+
+            board.set_state(&hex_id, state);
+        }
+
+        board.set_teams(teams);
+        board.set_to_move(to_move);
+
+        Ok(board)
+    }
+}
+
+impl BoardShape {
+    pub fn to_notation(&self) -> String {
+        match self {
+            BoardShape::Hexagon(size) => size.to_string(),
+            BoardShape::Rhombus(size) => format!("r{size}"),
+            BoardShape::Triangle(size) => format!("t{size}"),
+            BoardShape::Star(size) => format!("s{size}"),
+            BoardShape::Rectangle { width, height } => format!("{width}x{height}"),
+            BoardShape::Custom(_) => {
+                let lines: Vec<String> = (1..=self.max_x())
+                    .map(|x| {
+                        let mut runs = Vec::new();
+                        let mut on_board = false;
+                        let mut run = 0;
+                        for y in 1..=self.max_y() {
+                            if self.contains(&HexId::new(x, y)) != on_board {
+                                runs.push(run.to_string());
+                                on_board = !on_board;
+                                run = 0;
+                            }
+                            run += 1;
+                        }
+
+                        if on_board {
+                            runs.push(run.to_string());
+                        } else if runs.is_empty() {
+                            runs.push("0".to_string());
+                        }
+                        runs.join(".")
+                    })
+                    .collect();
+
+                format!("m{}", lines.join("/"))
+            }
+        }
+    }
+
+    /// Parses the shape field of a position on its own, like `r11`.
+    pub fn from_notation(notation: &str) -> Result<BoardShape, NotationError> {
+        let mut parser = Parser {
+            chars: notation.char_indices().peekable(),
+            length: notation.len(),
+        };
+
+        let shape = parser.shape()?;
+        if parser.chars.peek().is_some() {
+            return Err(parser.error_here(NotationErrorKind::TrailingInput));
+        }
+
+        Ok(shape)
+    }
+}
+
+fn error_at(position: usize, kind: NotationErrorKind) -> NotationError {
+    NotationError { position, kind }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    length: usize,
+}
+
+impl Parser<'_> {
+    fn position(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map(|(position, _)| *position)
+            .unwrap_or(self.length)
+    }
+
+    fn error_here(&mut self, kind: NotationErrorKind) -> NotationError {
+        let position = self.position();
+        error_at(position, kind)
+    }
+
+    fn separator(&mut self, next_field: &'static str) -> Result<(), NotationError> {
+        match self.chars.next() {
+            Some((_, ' ')) => Ok(()),
+            Some((position, c)) => Err(error_at(position, NotationErrorKind::UnexpectedChar(c))),
+            None => Err(self.error_here(NotationErrorKind::MissingField(next_field))),
+        }
+    }
+
+    fn shape(&mut self) -> Result<BoardShape, NotationError> {
+        let start = self.position();
+        let letter = self
+            .chars
+            .next_if(|(_, c)| c.is_ascii_lowercase())
+            .map(|(_, c)| c);
+        match letter {
+            Some('m') => return self.mask(start),
+            Some(c) if !['r', 't', 's'].contains(&c) => {
+                return Err(error_at(start, NotationErrorKind::UnexpectedChar(c)));
+            }
+            _ => {}
+        }
+
+        let position = self.position();
+        let size = self.number().map_err(|kind| error_at(position, kind))?;
+        let (shape, height) = match letter {
+            Some('r') => (BoardShape::Rhombus(size), size),
+            Some('t') => (BoardShape::Triangle(size), size),
+            Some('s') => (BoardShape::Star(size), size),
+            _ if self.chars.next_if(|(_, c)| *c == 'x').is_some() => {
+                let position = self.position();
+                let height = self.number().map_err(|kind| error_at(position, kind))?;
+                let shape = BoardShape::Rectangle {
+                    width: size,
+                    height,
+                };
+                (shape, height)
+            }
+            _ => (BoardShape::Hexagon(size), size),
+        };
+
+        if [size, height]
+            .iter()
+            .any(|size| *size == 0 || *size > MAX_SIZE)
+        {
+            return Err(error_at(start, NotationErrorKind::InvalidSize));
+        }
+
+        Ok(shape)
+    }
+
+    /// The lines of a custom shape, after its `m`.
+    fn mask(&mut self, start: usize) -> Result<BoardShape, NotationError> {
+        let mut hex_ids = Vec::new();
+        let mut x = 1;
+
+        loop {
+            let mut y = 1;
+            let mut on_board = false;
+
+            loop {
+                let position = self.position();
+                let run = self.number().map_err(|kind| error_at(position, kind))?;
+                if run > MAX_EXTENT || y + run > MAX_EXTENT + 1 {
+                    return Err(error_at(start, NotationErrorKind::InvalidMask));
+                }
+
+                if on_board {
+                    hex_ids.extend((y..y + run).map(|y| HexId::new(x, y)));
+                }
+                y += run;
+                on_board = !on_board;
+
+                if self.chars.next_if(|(_, c)| *c == '.').is_none() {
+                    break;
+                }
+            }
+
+            if self.chars.next_if(|(_, c)| *c == '/').is_none() {
+                break;
+            }
+
+            x += 1;
+            if x > MAX_EXTENT {
+                return Err(error_at(start, NotationErrorKind::InvalidMask));
+            }
+        }
+
+        if hex_ids.is_empty() {
+            return Err(error_at(start, NotationErrorKind::InvalidMask));
+        }
+
+        Ok(BoardShape::Custom(Mask::new(hex_ids)))
+    }
+
+    fn number(&mut self) -> Result<u32, NotationErrorKind> {
+        let mut digits = String::new();
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+            digits.push(c);
+        }
+
+        digits.parse().map_err(|_| NotationErrorKind::InvalidNumber)
+    }
+
+    fn piece_value(&mut self) -> Result<u32, NotationError> {
+        match self.chars.next() {
+            Some((_, '(')) => {
+                let position = self.position();
+                let value = self.number().map_err(|kind| error_at(position, kind))?;
+                if value > MAX_VALUE {
+                    return Err(error_at(position, NotationErrorKind::ValueTooLarge(value)));
+                }
+                match self.chars.next() {
+                    Some((_, ')')) => Ok(value),
+                    Some((position, _)) => {
+                        Err(error_at(position, NotationErrorKind::UnclosedValue))
+                    }
+                    None => Err(self.error_here(NotationErrorKind::UnclosedValue)),
+                }
+            }
+            Some((_, c)) if c.is_ascii_digit() => Ok(c as u32 - '0' as u32),
+            Some((position, c)) => Err(error_at(position, NotationErrorKind::UnexpectedChar(c))),
+            None => Err(self.error_here(NotationErrorKind::MissingField("piece value"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{HexId, HexState};
+    use crate::hexy::notation::{NotationError, NotationErrorKind};
+    use crate::hexy::shape::{BoardShape, Mask};
+
+    fn error(notation: &str) -> NotationError {
+        HexBoard::from_notation(notation).expect_err(notation)
+    }
+
+    #[test]
+    fn test_empty_boards() {
+        assert_eq!("1 1 a 2", HexBoard::new(1).to_notation());
+        assert_eq!("2 2/3/2 a 2", HexBoard::new(2).to_notation());
+        assert_eq!("4 4/5/6/7/6/5/4 a 2", HexBoard::new(4).to_notation());
+    }
+
+    #[test]
+    fn test_pieces() {
+        let mut board = HexBoard::new(3);
+        board.set_state(&HexId::new(1, 1), HexState::Piece { team: 0, value: 0 });
+        board.set_state(&HexId::new(1, 3), HexState::Piece { team: 1, value: 12 });
+        board.set_state(&HexId::new(3, 2), HexState::Piece { team: 2, value: 3 });
+        board.set_teams(3);
+        board.set_to_move(2);
+
+        let notation = board.to_notation();
+        assert_eq!("3 a01b(12)/4/1c33/4/3 c 3", notation);
+
+        let parsed = HexBoard::from_notation(&notation).unwrap();
+        assert_eq!(notation, parsed.to_notation());
+        assert_eq!(
+            Some(HexState::Piece { team: 1, value: 12 }),
+            parsed.get(&HexId::new(1, 3)).map(|hexagon| hexagon.state)
+        );
+        assert_eq!(3, parsed.teams());
+        assert_eq!(2, parsed.to_move());
+    }
+
+    #[test]
+    fn test_round_trip_all_sizes() {
+        for size in 1..=9 {
+            let mut board = HexBoard::new(size);
+            let hex_ids: Vec<HexId> = board
+                .hexagons()
+                .map(|hexagon| hexagon.hex_id.clone())
+                .collect();
+            for (i, hex_id) in hex_ids.iter().enumerate() {
+                if i % 3 != 0 {
+                    continue;
+                }
+
+                board.set_state(
+                    hex_id,
+                    HexState::Piece {
+                        team: (i % 2) as u32,
+                        value: i as u32 % 14,
+                    },
+                );
+            }
+            board.set_to_move(1);
+
+            let notation = board.to_notation();
+            assert_eq!(
+                notation,
+                HexBoard::from_notation(&notation).unwrap().to_notation()
+            );
+        }
+    }
+
+    #[test]
+    fn test_shapes() {
+        let hexagon = BoardShape::Hexagon(2);
+        let shapes = [
+            (BoardShape::Rhombus(3), "r3 3/3/3 a 2"),
+            (BoardShape::Triangle(3), "t3 3/2/1 a 2"),
+            (BoardShape::Star(2), "s2 1/4/3/4/1 a 2"),
+            (
+                BoardShape::Rectangle {
+                    width: 3,
+                    height: 2,
+                },
+                "3x2 1/3/2 a 2",
+            ),
+            (
+                hexagon.with_holes(&[HexId::new(2, 2)]),
+                "m0.2/0.1.1.1/1.2 2/2/2 a 2",
+            ),
+        ];
+
+        for (shape, notation) in shapes {
+            let mut board = HexBoard::with_shape(shape.clone());
+            assert_eq!(notation, board.to_notation());
+            assert_eq!(
+                Ok(shape.clone()),
+                BoardShape::from_notation(&shape.to_notation())
+            );
+
+            let hex_id = shape.rows().concat().pop().unwrap();
+            board.set_state(&hex_id, HexState::Piece { team: 1, value: 4 });
+            let parsed = HexBoard::from_notation(&board.to_notation()).unwrap();
+            assert_eq!(&shape, parsed.shape());
+            assert_eq!(board.to_notation(), parsed.to_notation());
+        }
+
+        assert_eq!(
+            "m0/1.1",
+            BoardShape::Custom(Mask::new([HexId::new(2, 2)])).to_notation()
+        );
+        assert_eq!(
+            NotationErrorKind::InvalidSize,
+            BoardShape::from_notation("3x0").unwrap_err().kind
+        );
+        assert_eq!(
+            NotationErrorKind::InvalidMask,
+            BoardShape::from_notation("m3/0").unwrap_err().kind
+        );
+        assert_eq!(
+            NotationErrorKind::UnexpectedChar('q'),
+            BoardShape::from_notation("q3").unwrap_err().kind
+        );
+        assert_eq!(
+            NotationErrorKind::TrailingInput,
+            BoardShape::from_notation("r3r").unwrap_err().kind
+        );
+    }
+
+    #[test]
+    fn test_hex_id_notation() {
+        assert_eq!("a1", HexId::new(1, 1).to_notation());
+        assert_eq!("g12", HexId::new(7, 12).to_notation());
+        assert_eq!(Some(HexId::new(4, 2)), HexId::from_notation("d2"));
+        assert_eq!(Some(HexId::new(26, 10)), HexId::from_notation("z10"));
+        assert_eq!(None, HexId::from_notation("d0"));
+        assert_eq!(None, HexId::from_notation("D2"));
+        assert_eq!(None, HexId::from_notation("d"));
+        assert_eq!(None, HexId::from_notation("2d"));
+        assert_eq!("aa1", HexId::new(27, 1).to_notation());
+        assert_eq!("go3", HexId::new(197, 3).to_notation());
+        assert_eq!(Some(HexId::new(28, 5)), HexId::from_notation("ab5"));
+        assert_eq!(None, HexId::from_notation("zzzzzzzzz1"));
+    }
+
+    #[test]
+    fn test_hex_id_notation_round_trips_on_large_boards() {
+        for shape in [BoardShape::Hexagon(20), BoardShape::Star(8)] {
+            let board = HexBoard::with_shape(shape);
+            for hexagon in board.hexagons() {
+                let notation = hexagon.hex_id.to_notation();
+                assert_eq!(
+                    Some(hexagon.hex_id.clone()),
+                    HexId::from_notation(&notation),
+                    "{notation}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            NotationError {
+                position: 0,
+                kind: NotationErrorKind::InvalidNumber
+            },
+            error("")
+        );
+        assert_eq!(
+            NotationError {
+                position: 0,
+                kind: NotationErrorKind::InvalidSize
+            },
+            error("0 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 1,
+                kind: NotationErrorKind::MissingField("board")
+            },
+            error("2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 4,
+                kind: NotationErrorKind::LineTooShort {
+                    line: 1,
+                    length: 2,
+                    found: 1
+                }
+            },
+            error("2 a0/3/2 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 7,
+                kind: NotationErrorKind::LineTooLong { line: 2, length: 3 }
+            },
+            error("2 2/2b13/2 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 7,
+                kind: NotationErrorKind::WrongLineCount {
+                    expected: 3,
+                    found: 4
+                }
+            },
+            error("2 2/3/2/1 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 5,
+                kind: NotationErrorKind::WrongLineCount {
+                    expected: 3,
+                    found: 2
+                }
+            },
+            error("2 2/3 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 4,
+                kind: NotationErrorKind::UnexpectedChar('X')
+            },
+            error("2 2/X3/2 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 7,
+                kind: NotationErrorKind::UnclosedValue
+            },
+            error("2 a(123/3/2 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 4,
+                kind: NotationErrorKind::ValueTooLarge(30000000)
+            },
+            error("2 a(30000000)a0/b02/2 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 2,
+                kind: NotationErrorKind::TeamOutOfRange(2)
+            },
+            error("2 c01/3/2 a 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 8,
+                kind: NotationErrorKind::TeamOutOfRange(2)
+            },
+            error("2 2/3/2 c 2")
+        );
+        assert_eq!(
+            NotationError {
+                position: 10,
+                kind: NotationErrorKind::InvalidTeamCount(0)
+            },
+            error("2 2/3/2 a 0")
+        );
+        assert_eq!(
+            NotationError {
+                position: 11,
+                kind: NotationErrorKind::TrailingInput
+            },
+            error("2 2/3/2 a 2 x")
+        );
+        assert_eq!(
+            NotationError {
+                position: 9,
+                kind: NotationErrorKind::MissingField("team count")
+            },
+            error("2 2/3/2 a")
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Pictures of boards as PNG images, drawn without a browser to put positions in documents and
+//! messages.
+//!
+//! The hexes are laid out like the SVG board, see the `layout` module. Text is drawn from the
+//! outlines of Noto Sans, which is built into the program, so pictures look the same everywhere.
+
+use ab_glyph::{Font, FontRef, OutlineCurve};
+use rocket::{FromForm, FromFormField};
+use tiny_skia::{Color, FillRule, Paint, Path, PathBuilder, Pixmap, Stroke, Transform};
+
+use super::hex_board::{BoardView, HexBoard};
+use super::hexagon::{team_color, HexMarks, HexState};
+use super::layout::{Layout, Orientation};
+
+pub const DEFAULT_WIDTH: u32 = 600;
+
+/// The widest and highest picture that is drawn, to keep the memory a picture takes in bounds.
+pub const MAX_WIDTH: u32 = 4000;
+
+pub const MIN_WIDTH: u32 = 50;
+
+/// The colours of a picture.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum Theme {
+    /// Grey hexes on white, for documents.
+    #[default]
+    #[field(value = "light")]
+    Light,
+    /// The colours of the game page, black hexes on blue.
+    #[field(value = "dark")]
+    Dark,
+}
+
+struct Palette {
+    background: Color,
+    hex: Color,
+    text: Color,
+    label: Color,
+    last_move: Color,
+    target: Color,
+    capture: Color,
+    selected: Color,
+}
+
+impl Theme {
+    fn palette(&self) -> Palette {
+        match self {
+            Theme::Light => Palette {
+                background: rgb(0xff, 0xff, 0xff),
+                hex: rgb(0xd9, 0xd9, 0xd9),
+                text: rgb(0xff, 0xff, 0xff),
+                label: rgb(0x55, 0x55, 0x55),
+                last_move: rgb(0xa9, 0xc1, 0xe8),
+                target: rgb(0xb5, 0xd6, 0xa7),
+                capture: rgb(0xe8, 0xa9, 0xa9),
+                selected: rgb(0xf0, 0xd0, 0x60),
+            },
+            Theme::Dark => Palette {
+                background: rgb(0x5f, 0x9e, 0xa0),
+                hex: rgb(0x00, 0x00, 0x00),
+                text: rgb(0xff, 0xff, 0xff),
+                label: rgb(0xaa, 0xaa, 0xaa),
+                last_move: rgb(0x1d, 0x2b, 0x4f),
+                target: rgb(0x2e, 0x5e, 0x2e),
+                capture: rgb(0x8b, 0x1a, 0x1a),
+                selected: rgb(0xda, 0xa5, 0x20),
+            },
+        }
+    }
+}
+
+impl Palette {
+    fn hex(&self, marks: HexMarks) -> Color {
+        if marks.selected {
+            self.selected
+        } else if marks.capture {
+            self.capture
+        } else if marks.target {
+            self.target
+        } else if marks.last_move {
+            self.last_move
+        } else {
+            self.hex
+        }
+    }
+}
+
+/// How a picture of a board is drawn.
+#[derive(Debug, Clone, PartialEq, Eq, FromForm)]
+pub struct PictureOptions {
+    /// The width and height in pixels the board is fitted in. The board keeps its shape, so the
+    /// picture is narrower than this for boards that are higher than wide.
+    #[field(default = DEFAULT_WIDTH)]
+    pub width: u32,
+    #[field(default = Orientation::FlatTop)]
+    pub orientation: Orientation,
+    #[field(default = Theme::Light)]
+    pub theme: Theme,
+    /// Writes the name of every hex, like `d2`, on it.
+    #[field(default = false)]
+    pub labels: bool,
+}
+
+impl Default for PictureOptions {
+    fn default() -> PictureOptions {
+        PictureOptions {
+            width: DEFAULT_WIDTH,
+            orientation: Orientation::default(),
+            theme: Theme::default(),
+            labels: false,
+        }
+    }
+}
+
+fn rgb(red: u8, green: u8, blue: u8) -> Color {
+    Color::from_rgba8(red, green, blue, 0xff)
+}
+
+/// Reads a colour like `#f00` or `#ff0000`.
+fn parse_color(text: &str) -> Option<Color> {
+    let digits = text.strip_prefix('#')?;
+    let channel = |i: usize, width: usize| {
+        let value = u8::from_str_radix(digits.get(i * width..(i + 1) * width)?, 16).ok()?;
+        Some(if width == 1 { value * 0x11 } else { value })
+    };
+
+    let width = match digits.len() {
+        3 => 1,
+        6 => 2,
+        _ => return None,
+    };
+    Some(rgb(
+        channel(0, width)?,
+        channel(1, width)?,
+        channel(2, width)?,
+    ))
+}
+
+fn polygon(points: &[(f64, f64)]) -> Option<Path> {
+    let mut builder = PathBuilder::new();
+    let (first, rest) = points.split_first()?;
+    builder.move_to(first.0 as f32, first.1 as f32);
+    for (x, y) in rest {
+        builder.line_to(*x as f32, *y as f32);
+    }
+    builder.close();
+
+    builder.finish()
+}
+
+/// The outline of `text` in a font of `size` pixels, centered on (`x`, `y`).
+fn text_path(font: &FontRef, text: &str, size: f64, (x, y): (f64, f64)) -> Option<Path> {
+    let scale = (size / font.units_per_em()? as f64) as f32;
+    let mut builder = PathBuilder::new();
+    let mut cursor = 0.0;
+    let mut previous = None;
+
+    for c in text.chars() {
+        let glyph = font.glyph_id(c);
+        if let Some(previous) = previous {
+            cursor += font.kern_unscaled(previous, glyph);
+        }
+        previous = Some(glyph);
+
+        let Some(outline) = font.outline(glyph) else {
+            cursor += font.h_advance_unscaled(glyph);
+            continue;
+        };
+
+        // Font units grow upwards, pixels downwards.
+        let point = |point: ab_glyph::Point| ((cursor + point.x) * scale, -point.y * scale);
+        let mut end = None;
+        for curve in &outline.curves {
+            let (start, last) = match curve {
+                OutlineCurve::Line(from, to) => (*from, *to),
+                OutlineCurve::Quad(from, _, to) => (*from, *to),
+                OutlineCurve::Cubic(from, _, _, to) => (*from, *to),
+            };
+            if end != Some(start) {
+                if end.is_some() {
+                    builder.close();
+                }
+                let (x, y) = point(start);
+                builder.move_to(x, y);
+            }
+            end = Some(last);
+
+            match curve {
+                OutlineCurve::Line(_, to) => {
+                    let (x, y) = point(*to);
+                    builder.line_to(x, y);
+                }
+                OutlineCurve::Quad(_, control, to) => {
+                    let (cx, cy) = point(*control);
+                    let (x, y) = point(*to);
+                    builder.quad_to(cx, cy, x, y);
+                }
+                OutlineCurve::Cubic(_, first, second, to) => {
+                    let (c1x, c1y) = point(*first);
+                    let (c2x, c2y) = point(*second);
+                    let (x, y) = point(*to);
+                    builder.cubic_to(c1x, c1y, c2x, c2y, x, y);
+                }
+            }
+        }
+        if end.is_some() {
+            builder.close();
+        }
+        cursor += font.h_advance_unscaled(glyph);
+    }
+
+    let path = builder.finish()?;
+    let bounds = path.bounds();
+    path.transform(Transform::from_translate(
+        x as f32 - (bounds.left() + bounds.right()) / 2.0,
+        y as f32 - (bounds.top() + bounds.bottom()) / 2.0,
+    ))
+}
+
+fn fill(pixmap: &mut Pixmap, path: &Path, color: Color) {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    pixmap.fill_path(path, &paint, FillRule::Winding, Transform::identity(), None);
+}
+
+fn stroke(pixmap: &mut Pixmap, path: &Path, color: Color, width: f64) {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    let stroke = Stroke {
+        width: width as f32,
+        ..Default::default()
+    };
+    pixmap.stroke_path(path, &paint, &stroke, Transform::identity(), None);
+}
+
+impl HexBoard {
+    /// Draws the board with the marks of the view, see `to_png`.
+    pub fn to_pixmap(&self, view: &BoardView, options: &PictureOptions) -> Result<Pixmap, String> {
+        if !(MIN_WIDTH..=MAX_WIDTH).contains(&options.width) {
+            return Err(format!(
+                "A picture is {MIN_WIDTH} to {MAX_WIDTH} pixels wide."
+            ));
+        }
+
+        let font =
+            FontRef::try_from_slice(notosans::REGULAR_TTF).map_err(|error| error.to_string())?;
+        let palette = options.theme.palette();
+        let margin = options.width as f64 / 50.0;
+        let size = options.width as f64;
+        let layout = Layout::fit(self, options.orientation, (size, size), margin);
+        let radius = layout.radius;
+        let mut pixmap = Pixmap::new(layout.width.round() as u32, layout.height.round() as u32)
+            .ok_or_else(|| "The board is too large to draw.".to_string())?;
+        pixmap.fill(palette.background);
+
+        for hexagon in self.hexagons() {
+            let hex_id = &hexagon.hex_id;
+            let center = layout.center(hex_id);
+            if let Some(shape) = polygon(&layout.corners(hex_id)) {
+                fill(&mut pixmap, &shape, palette.hex(view.marks(hex_id)));
+                stroke(&mut pixmap, &shape, palette.background, radius / 15.0);
+            }
+
+            if let HexState::Piece { team, value } = hexagon.state {
+                let color = parse_color(team_color(team)).unwrap_or(palette.text);
+                if let Some(piece) = PathBuilder::from_circle(
+                    center.0 as f32,
+                    center.1 as f32,
+                    (radius * 0.6) as f32,
+                ) {
+                    fill(&mut pixmap, &piece, color);
+                }
+
+                // Leaders get a ring instead of a number, the sign on the game page is not in
+                // the font.
+                if value == 0 {
+                    let (x, y) = (center.0 as f32, center.1 as f32);
+                    if let Some(ring) = PathBuilder::from_circle(x, y, (radius * 0.3) as f32) {
+                        stroke(&mut pixmap, &ring, palette.text, radius / 8.0);
+                    }
+                } else if let Some(text) =
+                    text_path(&font, &value.to_string(), radius * 0.5, center)
+                {
+                    fill(&mut pixmap, &text, palette.text);
+                }
+            }
+
+            if options.labels {
+                let place = (center.0, center.1 + radius * 0.72);
+                if let Some(label) = text_path(&font, &hex_id.to_notation(), radius * 0.2, place) {
+                    fill(&mut pixmap, &label, palette.label);
+                }
+            }
+        }
+
+        Ok(pixmap)
+    }
+
+    /// A PNG picture of the board with the marks of the view.
+    pub fn to_png(&self, view: &BoardView, options: &PictureOptions) -> Result<Vec<u8>, String> {
+        self.to_pixmap(view, options)?
+            .encode_png()
+            .map_err(|error| error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::hex_board::{BoardView, HexBoard};
+    use crate::hexy::hexagon::HexId;
+    use crate::hexy::layout::Orientation;
+    use crate::hexy::picture::{parse_color, rgb, PictureOptions, Theme, MAX_WIDTH};
+    use crate::hexy::shape::BoardShape;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Some(rgb(0xff, 0, 0)), parse_color("#f00"));
+        assert_eq!(Some(rgb(0x12, 0x34, 0x56)), parse_color("#123456"));
+        assert_eq!(None, parse_color("f00"));
+        assert_eq!(None, parse_color("#f0"));
+    }
+
+    #[test]
+    fn test_picture() {
+        let board = HexBoard::from_notation("3 a0a21/4/5/4/2b0 a 2").unwrap();
+        let view = BoardView {
+            last_move: Some((HexId::new(1, 2), HexId::new(3, 3))),
+            ..Default::default()
+        };
+        let options = PictureOptions {
+            width: 200,
+            orientation: Orientation::PointyTop,
+            theme: Theme::Dark,
+            labels: true,
+        };
+
+        let pixmap = board.to_pixmap(&view, &options).unwrap();
+        assert_eq!(200, pixmap.width());
+        assert!(pixmap.height() > 100 && pixmap.height() < 200);
+
+        // The corner of the picture is the background, the center the marked middle hex.
+        let color = |x, y| {
+            let pixel = pixmap.pixel(x, y).unwrap();
+            (pixel.red(), pixel.green(), pixel.blue())
+        };
+        assert_eq!((0x5f, 0x9e, 0xa0), color(0, 0));
+        assert_eq!((0x1d, 0x2b, 0x4f), color(100, pixmap.height() / 2));
+
+        let png = board.to_png(&view, &options).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let too_wide = PictureOptions {
+            width: 10_000,
+            ..Default::default()
+        };
+        assert!(board.to_png(&view, &too_wide).is_err());
+    }
+
+    #[test]
+    fn test_tall_boards_fit_in_the_picture() {
+        let board = HexBoard::with_shape(BoardShape::Rectangle {
+            width: 1,
+            height: 50,
+        });
+        let options = PictureOptions {
+            width: MAX_WIDTH,
+            ..Default::default()
+        };
+
+        let pixmap = board.to_pixmap(&BoardView::default(), &options).unwrap();
+        assert_eq!(MAX_WIDTH, pixmap.height());
+        assert!(pixmap.width() < MAX_WIDTH / 10);
+    }
+}
@@ -0,0 +1,324 @@
+//! Ratings of the players, from the results of their finished games.
+//!
+//! Two-team games change the ratings like Elo does for chess. A game with more teams counts as
+//! a game between every pair of teams: the winner beat every other team, and the teams that
+//! lost drew with each other. The change of every pair is divided by the amount of opponents,
+//! so a game moves a rating about as much as a two-team game does.
+//!
+//! A rating belongs to a name, and a name belongs to the first player id that finished a rated
+//! game with it. Games where someone else used the name are not rated, so nobody can play
+//! under the name of another player. The records of rated games keep the player ids next to
+//! the names, so the ratings are nothing but the results of the rated records in order, and
+//! can always be computed again.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::lobby::PlayerId;
+use super::moves::GameStatus;
+use super::record::{GameRecord, DATE};
+
+/// The rating of a player without rated games.
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// How much a single result can change a rating.
+const K_FACTOR: f64 = 32.0;
+
+/// The share of the points a player is expected to score against an opponent.
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// What the team scored against the other team, 1 for a win and 0.5 for a draw.
+fn score(result: GameStatus, team: u32, other: u32) -> f64 {
+    match result {
+        GameStatus::Won(winner) if winner == team => 1.0,
+        GameStatus::Won(winner) if winner == other => 0.0,
+        _ => 0.5,
+    }
+}
+
+/// How the rating of every team changes with the result, from the ratings before the game.
+pub fn rating_changes(ratings: &[f64], result: GameStatus) -> Vec<f64> {
+    let opponents = ratings.len().saturating_sub(1).max(1) as f64;
+
+    (0..ratings.len())
+        .map(|team| {
+            (0..ratings.len())
+                .filter(|other| *other != team)
+                .map(|other| {
+                    let score = score(result, team as u32, other as u32);
+                    score - expected_score(ratings[team], ratings[other])
+                })
+                .sum::<f64>()
+                * K_FACTOR
+                / opponents
+        })
+        .collect()
+}
+
+/// How a rated game changed the rating of a player.
+#[derive(Debug, Clone, Serialize)]
+pub struct RatingChange {
+    /// The index of the game in the rated games.
+    pub game: usize,
+    pub date: String,
+    pub opponents: Vec<String>,
+    /// Won, lost or draw.
+    pub result: &'static str,
+    pub change: f64,
+    /// The rating after the game.
+    pub rating: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerRating {
+    pub name: String,
+    pub rating: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Every rated game of the player, oldest first.
+    pub history: Vec<RatingChange>,
+}
+
+impl PlayerRating {
+    fn new(name: &str) -> PlayerRating {
+        PlayerRating {
+            name: name.to_string(),
+            rating: INITIAL_RATING,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.history.len()
+    }
+}
+
+/// The ratings of all players, and the games they were computed from.
+#[derive(Debug, Default)]
+pub struct Ratings {
+    players: HashMap<String, PlayerRating>,
+    /// The player id every rated name belongs to.
+    owners: HashMap<String, PlayerId>,
+    games: Vec<GameRecord>,
+}
+
+impl Ratings {
+    /// Rates the games of the records in order, skipping the ones that are not rated.
+    pub fn from_records(records: impl IntoIterator<Item = GameRecord>) -> Ratings {
+        let mut ratings = Ratings::default();
+        for record in records {
+            ratings.add(record);
+        }
+
+        ratings
+    }
+
+    /// The names of the players of a game when it counts for the ratings: it is over, every
+    /// team was played by someone else, and every name belongs to the player id that used it.
+    pub fn rated_players(&self, record: &GameRecord) -> Option<Vec<String>> {
+        if record.result == GameStatus::Ongoing {
+            return None;
+        }
+
+        let players = (0..record.initial.teams())
+            .map(|team| Some((record.player(team)?.to_string(), record.player_id(team)?)))
+            .collect::<Option<Vec<(String, PlayerId)>>>()?;
+        let distinct = players.iter().enumerate().all(|(i, (name, id))| {
+            players[..i]
+                .iter()
+                .all(|(other_name, other_id)| other_name != name && other_id != id)
+        });
+        let owned = players.iter().all(|(name, id)| self.may_use(name, *id));
+
+        (players.len() >= 2 && distinct && owned)
+            .then(|| players.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Whether the player can play rated games under the name, false when it belongs to
+    /// someone else.
+    pub fn may_use(&self, name: &str, player: PlayerId) -> bool {
+        self.owners.get(name).is_none_or(|owner| *owner == player)
+    }
+
+    /// Changes the ratings of the players with the result of the game, false when the game
+    /// is not rated.
+    pub fn add(&mut self, record: GameRecord) -> bool {
+        let Some(players) = self.rated_players(&record) else {
+            return false;
+        };
+        for (team, name) in players.iter().enumerate() {
+            if let Some(id) = record.player_id(team as u32) {
+                self.owners.insert(name.clone(), id);
+            }
+        }
+
+        let before: Vec<f64> = players
+            .iter()
+            .map(|name| {
+                self.players
+                    .get(name)
+                    .map_or(INITIAL_RATING, |player| player.rating)
+            })
+            .collect();
+        let changes = rating_changes(&before, record.result);
+        let date = record.header(DATE).unwrap_or_default().to_string();
+
+        for (team, name) in players.iter().enumerate() {
+            let player = self
+                .players
+                .entry(name.clone())
+                .or_insert_with(|| PlayerRating::new(name));
+            let result = match record.result {
+                GameStatus::Won(winner) if winner == team as u32 => {
+                    player.wins += 1;
+                    "won"
+                }
+                GameStatus::Won(_) => {
+                    player.losses += 1;
+                    "lost"
+                }
+                _ => {
+                    player.draws += 1;
+                    "draw"
+                }
+            };
+
+            player.rating += changes[team];
+            player.history.push(RatingChange {
+                game: self.games.len(),
+                date: date.clone(),
+                opponents: players
+                    .iter()
+                    .filter(|other| *other != name)
+                    .cloned()
+                    .collect(),
+                result,
+                change: changes[team],
+                rating: player.rating,
+            });
+        }
+
+        self.games.push(record);
+        true
+    }
+
+    pub fn player(&self, name: &str) -> Option<&PlayerRating> {
+        self.players.get(name)
+    }
+
+    /// The players from the highest rating to the lowest.
+    pub fn leaderboard(&self) -> Vec<&PlayerRating> {
+        let mut players: Vec<&PlayerRating> = self.players.values().collect();
+        players.sort_by(|a, b| b.rating.total_cmp(&a.rating).then(a.name.cmp(&b.name)));
+
+        players
+    }
+
+    /// The rated games, in the order they were rated.
+    pub fn games(&self) -> &[GameRecord] {
+        &self.games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::moves::{starting_position, GameStatus};
+    use crate::hexy::rating::{expected_score, rating_changes, Ratings, INITIAL_RATING};
+    use crate::hexy::record::GameRecord;
+
+    const ANN: (&str, u64) = ("Ann", 1);
+    const BOB: (&str, u64) = ("Bob", 2);
+    const CID: (&str, u64) = ("Cid", 3);
+
+    fn record(players: &[(&str, u64)], result: GameStatus) -> GameRecord {
+        let mut record = GameRecord::new(starting_position(5, players.len() as u32).unwrap());
+        for (team, (name, id)) in players.iter().enumerate() {
+            record.set_player(team as u32, name);
+            record.set_player_id(team as u32, *id);
+        }
+        record.result = result;
+
+        record
+    }
+
+    #[test]
+    fn test_elo() {
+        assert_eq!(0.5, expected_score(1500.0, 1500.0));
+        assert!((expected_score(1900.0, 1500.0) - 0.909).abs() < 0.001);
+
+        assert_eq!(
+            vec![16.0, -16.0],
+            rating_changes(&[1500.0, 1500.0], GameStatus::Won(0))
+        );
+        assert_eq!(
+            vec![0.0, 0.0],
+            rating_changes(&[1500.0, 1500.0], GameStatus::Draw)
+        );
+
+        // The winner gains what the others lose, the losers draw with each other.
+        let changes = rating_changes(&[1500.0, 1500.0, 1500.0], GameStatus::Won(1));
+        assert_eq!(vec![-8.0, 16.0, -8.0], changes);
+    }
+
+    #[test]
+    fn test_ratings() {
+        let ratings = Ratings::from_records([
+            record(&[ANN, BOB], GameStatus::Won(0)),
+            record(&[BOB, ANN], GameStatus::Draw),
+            record(&[ANN, ANN], GameStatus::Won(0)),
+            record(&[ANN, CID], GameStatus::Ongoing),
+            record(&[CID, BOB, ANN], GameStatus::Won(0)),
+        ]);
+        assert_eq!(3, ratings.games().len());
+
+        let names: Vec<&str> = ratings
+            .leaderboard()
+            .iter()
+            .map(|player| player.name.as_str())
+            .collect();
+        assert_eq!(vec!["Cid", "Ann", "Bob"], names);
+
+        let ann = ratings.player("Ann").unwrap();
+        assert_eq!((1, 1, 1), (ann.wins, ann.draws, ann.losses));
+        assert_eq!(3, ann.games());
+        assert_eq!(INITIAL_RATING + 16.0, ann.history[0].rating);
+        assert_eq!(vec!["Cid", "Bob"], ann.history[2].opponents);
+        assert_eq!(2, ann.history[2].game);
+
+        let total: f64 = ratings
+            .leaderboard()
+            .iter()
+            .map(|player| player.rating)
+            .sum();
+        assert!((total - 3.0 * INITIAL_RATING).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_names_belong_to_their_first_player() {
+        let mut ratings = Ratings::default();
+        assert!(ratings.add(record(&[ANN, BOB], GameStatus::Won(0))));
+        assert!(!ratings.may_use("Ann", 2));
+        assert!(ratings.may_use("Ann", 1));
+        assert!(ratings.may_use("Dan", 2));
+
+        // Someone else playing as Ann, or a record without ids, does not change the rating of Ann.
+        let impostor = record(&[("Ann", 9), BOB], GameStatus::Won(1));
+        assert!(ratings.rated_players(&impostor).is_none());
+        assert!(!ratings.add(impostor));
+        let mut without_ids = GameRecord::new(starting_position(5, 2).unwrap());
+        without_ids.set_player(0, "Ann");
+        without_ids.set_player(1, "Bob");
+        without_ids.result = GameStatus::Won(1);
+        assert!(!ratings.add(without_ids));
+
+        assert_eq!(1, ratings.games().len());
+        assert_eq!(0, ratings.player("Ann").unwrap().losses);
+    }
+}
@@ -0,0 +1,631 @@
+//! A text format for whole Hexy games, in the spirit of PGN for chess.
+//!
+//! A record starts with headers, one per line, followed by the moves:
+//!
+//! ```text
+//! [Event "Club night"]
+//! [Date "2024.03.30"]
+//! [Variant "Standard"]
+//! [TeamA "Alice"]
+//! [TeamB "Bob"]
+//! [Size "3"]
+//! [Teams "2"]
+//! [Result "*"]
+//! [Position "3 a0a21/a2a32/5/2b3b2/1b2b0 a 2"]
+//!
+//! 1. a2-a3 {Opens the centre.} 2. e4-d3 *
+//! ```
+//!
+//! - A header is a name and a quoted value, `\"` and `\\` escape a quote and a backslash.
+//!   The players are the headers `TeamA`, `TeamB` and so on. The records of rated games the
+//!   server keeps also have `TeamAId` and so on, the player ids that own the names.
+//! - `Position` is the position the game started from, in board notation. `Size` and `Teams`
+//!   repeat what it says for readers that do not parse it.
+//! - `Result` is `*` while the game goes on, `draw`, or the letter of the winning team
+//!   followed by `-wins`, like `b-wins`.
+//! - The moves are written like `d2-e3` or `d2xe3`, numbered by ply. A comment in braces
+//!   belongs to the move before it and can not contain braces. The result may end the moves.
+
+use std::fmt::Display;
+
+use super::hex_board::HexBoard;
+use super::moves::{parse_move, GameStatus, Move};
+use super::notation::{team_letter, NotationError};
+
+pub const EVENT: &str = "Event";
+pub const DATE: &str = "Date";
+pub const VARIANT: &str = "Variant";
+const SIZE: &str = "Size";
+const TEAMS: &str = "Teams";
+const RESULT: &str = "Result";
+const POSITION: &str = "Position";
+
+/// The headers that are written from the record itself instead of from `headers`.
+const DERIVED_HEADERS: [&str; 4] = [SIZE, TEAMS, RESULT, POSITION];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordErrorKind {
+    InvalidHeader,
+    MissingHeader(&'static str),
+    InvalidPosition(NotationError),
+    HeaderMismatch(&'static str),
+    InvalidResult(String),
+    IllegalMove(String),
+    UnclosedComment,
+    MovesAfterResult,
+}
+
+impl Display for RecordErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordErrorKind::InvalidHeader => write!(f, "expected a header like [Name \"value\"]"),
+            RecordErrorKind::MissingHeader(name) => write!(f, "missing the {name} header"),
+            RecordErrorKind::InvalidPosition(error) => write!(f, "{error}"),
+            RecordErrorKind::HeaderMismatch(name) => {
+                write!(f, "the {name} header does not match the position")
+            }
+            RecordErrorKind::InvalidResult(result) => write!(f, "'{result}' is not a result"),
+            RecordErrorKind::IllegalMove(text) => {
+                write!(f, "'{text}' is not a legal move in this position")
+            }
+            RecordErrorKind::UnclosedComment => write!(f, "missing '}}' after a comment"),
+            RecordErrorKind::MovesAfterResult => write!(f, "unexpected input after the result"),
+        }
+    }
+}
+
+/// A record that could not be parsed, `line` counts from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordError {
+    pub line: usize,
+    pub kind: RecordErrorKind,
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid record at line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// A move of a record with the comment that follows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedMove {
+    pub mv: Move,
+    pub comment: Option<String>,
+}
+
+/// A whole game: where it started, the moves and what is known about it.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    /// The headers in the order they are written, without the ones derived from the game.
+    pub headers: Vec<(String, String)>,
+    pub initial: HexBoard,
+    /// A comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<RecordedMove>,
+    pub result: GameStatus,
+}
+
+impl GameRecord {
+    /// A record of a game without moves, dated today.
+    pub fn new(initial: HexBoard) -> GameRecord {
+        GameRecord {
+            headers: vec![
+                (EVENT.into(), "Hexy game".into()),
+                (DATE.into(), today()),
+                (VARIANT.into(), "Standard".into()),
+            ],
+            initial,
+            comment: None,
+            moves: Vec::new(),
+            result: GameStatus::Ongoing,
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of a header, or adds the header at the end.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(header, _)| header == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn player(&self, team: u32) -> Option<&str> {
+        self.header(&player_header(team))
+    }
+
+    pub fn set_player(&mut self, team: u32, name: &str) {
+        self.set_header(&player_header(team), name);
+    }
+
+    /// The id of the player of the team, only in the records of rated games.
+    pub fn player_id(&self, team: u32) -> Option<u64> {
+        self.header(&format!("{}Id", player_header(team)))?
+            .parse()
+            .ok()
+    }
+
+    pub fn set_player_id(&mut self, team: u32, id: u64) {
+        self.set_header(&format!("{}Id", player_header(team)), &id.to_string());
+    }
+
+    /// The position after all moves.
+    pub fn final_board(&self) -> HexBoard {
+        let mut board = self.initial.clone();
+        for recorded in &self.moves {
+            board.apply_move(&recorded.mv);
+        }
+
+        board
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let derived = [
+            (SIZE, self.initial.shape().to_notation()),
+            (TEAMS, self.initial.teams().to_string()),
+            (RESULT, result_text(self.result)),
+            (POSITION, self.initial.to_notation()),
+        ];
+
+        let headers = self
+            .headers
+            .iter()
+            .filter(|(name, _)| !DERIVED_HEADERS.contains(&name.as_str()))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain(derived.iter().map(|(name, value)| (*name, value.as_str())));
+        for (name, value) in headers {
+            text.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+
+        let mut tokens = Vec::new();
+        if let Some(comment) = &self.comment {
+            tokens.push(comment_text(comment));
+        }
+        for (i, recorded) in self.moves.iter().enumerate() {
+            tokens.push(format!("{}. {}", i + 1, recorded.mv));
+            if let Some(comment) = &recorded.comment {
+                tokens.push(comment_text(comment));
+            }
+        }
+        tokens.push(result_text(self.result));
+
+        text.push('\n');
+        text.push_str(&wrap(&tokens, 80));
+        text.push('\n');
+        text
+    }
+
+    /// Reads a record and checks that all of its moves are legal.
+    pub fn parse(text: &str) -> Result<GameRecord, RecordError> {
+        let mut headers = Vec::new();
+        let mut movetext_start = text.lines().count();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if !line.starts_with('[') {
+                movetext_start = i;
+                break;
+            }
+
+            let header = parse_header(line).ok_or(RecordError {
+                line: i + 1,
+                kind: RecordErrorKind::InvalidHeader,
+            })?;
+            headers.push((i + 1, header));
+        }
+
+        let find = |name: &'static str| {
+            headers
+                .iter()
+                .find(|(_, (header, _))| header == name)
+                .map(|(line, (_, value))| (*line, value.as_str()))
+        };
+
+        let (position_line, position) = find(POSITION).ok_or(RecordError {
+            line: 1,
+            kind: RecordErrorKind::MissingHeader(POSITION),
+        })?;
+        let initial = HexBoard::from_notation(position).map_err(|error| RecordError {
+            line: position_line,
+            kind: RecordErrorKind::InvalidPosition(error),
+        })?;
+
+        let expected_headers = [
+            (SIZE, initial.shape().to_notation()),
+            (TEAMS, initial.teams().to_string()),
+        ];
+        for (name, expected) in expected_headers {
+            if let Some((line, value)) = find(name) {
+                if value != expected {
+                    return Err(RecordError {
+                        line,
+                        kind: RecordErrorKind::HeaderMismatch(name),
+                    });
+                }
+            }
+        }
+
+        let mut result = match find(RESULT) {
+            Some((line, value)) => Some(parse_result(value).ok_or(RecordError {
+                line,
+                kind: RecordErrorKind::InvalidResult(value.to_string()),
+            })?),
+            None => None,
+        };
+
+        let mut record = GameRecord {
+            headers: headers
+                .into_iter()
+                .map(|(_, header)| header)
+                .filter(|(name, _)| !DERIVED_HEADERS.contains(&name.as_str()))
+                .collect(),
+            initial: initial.clone(),
+            comment: None,
+            moves: Vec::new(),
+            result: GameStatus::Ongoing,
+        };
+
+        let mut board = initial;
+        let mut ended = false;
+        for (line, token) in movetext_tokens(text, movetext_start)? {
+            let error = |kind| Err(RecordError { line, kind });
+            if ended {
+                return error(RecordErrorKind::MovesAfterResult);
+            }
+
+            match token {
+                Token::Comment(comment) => match record.moves.last_mut() {
+                    Some(last) => last.comment = Some(comment),
+                    None => record.comment = Some(comment),
+                },
+                Token::Word(word) if is_move_number(word) => {}
+                Token::Word(word) => {
+                    if let Some(found) = parse_result(word) {
+                        if result.is_some_and(|result| result != found) {
+                            return error(RecordErrorKind::HeaderMismatch(RESULT));
+                        }
+
+                        result = Some(found);
+                        ended = true;
+                        continue;
+                    }
+
+                    let Some(mv) = parse_move(&board, word) else {
+                        return error(RecordErrorKind::IllegalMove(word.to_string()));
+                    };
+
+                    board.apply_move(&mv);
+                    record.moves.push(RecordedMove { mv, comment: None });
+                }
+            }
+        }
+
+        record.result = result.unwrap_or(GameStatus::Ongoing);
+        Ok(record)
+    }
+}
+
+enum Token<'a> {
+    Word(&'a str),
+    Comment(String),
+}
+
+/// Splits the moves into words and comments, with the line each starts on.
+fn movetext_tokens(text: &str, first_line: usize) -> Result<Vec<(usize, Token<'_>)>, RecordError> {
+    let mut tokens = Vec::new();
+    let mut comment: Option<(usize, String)> = None;
+
+    for (i, line) in text.lines().enumerate().skip(first_line) {
+        let mut rest = line;
+        loop {
+            if let Some((start, text)) = &mut comment {
+                match rest.split_once('}') {
+                    Some((end, after)) => {
+                        text.push_str(end);
+                        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                        tokens.push((*start, Token::Comment(text)));
+                        comment = None;
+                        rest = after;
+                    }
+                    None => {
+                        text.push_str(rest);
+                        text.push(' ');
+                        break;
+                    }
+                }
+            }
+
+            let trimmed = rest.trim_start();
+            if trimmed.is_empty() {
+                break;
+            }
+            if let Some(after) = trimmed.strip_prefix('{') {
+                comment = Some((i + 1, String::new()));
+                rest = after;
+                continue;
+            }
+
+            let end = trimmed
+                .find(|c: char| c.is_whitespace() || c == '{')
+                .unwrap_or(trimmed.len());
+            tokens.push((i + 1, Token::Word(&trimmed[..end])));
+            rest = &trimmed[end..];
+        }
+    }
+
+    match comment {
+        Some((line, _)) => Err(RecordError {
+            line,
+            kind: RecordErrorKind::UnclosedComment,
+        }),
+        None => Ok(tokens),
+    }
+}
+
+fn is_move_number(word: &str) -> bool {
+    word.strip_suffix('.')
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn player_header(team: u32) -> String {
+    format!("Team{}", team_letter(team).to_ascii_uppercase())
+}
+
+pub fn result_text(result: GameStatus) -> String {
+    match result {
+        GameStatus::Ongoing => "*".to_string(),
+        GameStatus::Won(team) => format!("{}-wins", team_letter(team)),
+        GameStatus::Draw => "draw".to_string(),
+    }
+}
+
+pub fn parse_result(text: &str) -> Option<GameStatus> {
+    match text {
+        "*" => Some(GameStatus::Ongoing),
+        "draw" => Some(GameStatus::Draw),
+        _ => {
+            let mut chars = text.strip_suffix("-wins")?.chars();
+            let letter = chars.next().filter(char::is_ascii_lowercase)?;
+            chars
+                .next()
+                .is_none()
+                .then(|| GameStatus::Won(letter as u32 - 'a' as u32))
+        }
+    }
+}
+
+/// Parses `[Name "value"]`.
+fn parse_header(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            '"' => return None,
+            c => unescaped.push(c),
+        }
+    }
+
+    Some((name.to_string(), unescaped))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace(['\n', '\r'], " ")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
+
+fn comment_text(comment: &str) -> String {
+    format!("{{{}}}", comment.replace('{', "(").replace('}', ")"))
+}
+
+/// Joins the tokens with spaces, starting a new line before a line gets longer than `width`.
+fn wrap(tokens: &[String], width: usize) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > width {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+
+        text.push_str(token);
+        line_length += token.len();
+    }
+
+    text
+}
+
+/// Today's date in UTC, written like `2024.03.30`.
+pub fn today() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_date(seconds as i64 / 86_400);
+
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+/// The date of a day counted from 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::moves::{moves, starting_position, GameStatus};
+    use crate::hexy::record::{
+        civil_date, parse_result, result_text, GameRecord, RecordErrorKind, RecordedMove, DATE,
+    };
+
+    const RECORD: &str = r#"[Event "Club \"night\""]
+[Date "2024.03.30"]
+[TeamA "Alice"]
+[TeamB "Bob"]
+[Size "3"]
+[Teams "2"]
+[Result "*"]
+[Position "3 a0a21/a2a32/5/2b3b2/1b2b0 a 2"]
+
+{Both sides start in their corner.}
+1. a2-a3 {Opens
+the centre.} 2. e4-d3 *
+"#;
+
+    #[test]
+    fn test_parse() {
+        let record = GameRecord::parse(RECORD).unwrap();
+
+        assert_eq!(Some("Club \"night\""), record.header("Event"));
+        assert_eq!(Some("Alice"), record.player(0));
+        assert_eq!(Some("Bob"), record.player(1));
+        assert_eq!(None, record.header("Position"));
+        assert_eq!(
+            Some("Both sides start in their corner."),
+            record.comment.as_deref()
+        );
+        assert_eq!(2, record.moves.len());
+        assert_eq!("a2-a3", record.moves[0].mv.to_string());
+        assert_eq!(
+            Some("Opens the centre."),
+            record.moves[0].comment.as_deref()
+        );
+        assert_eq!(None, record.moves[1].comment);
+        assert_eq!(GameStatus::Ongoing, record.result);
+        assert_eq!(
+            "3 a01a2/a2a32/5/1b2b3b2/2b0 a 2",
+            record.final_board().to_notation()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut record = GameRecord::new(starting_position(4, 3).unwrap());
+        record.set_player(2, "Carol");
+        record.comment = Some("A {braced} comment".into());
+        let mut board = record.initial.clone();
+        for i in 0..40 {
+            let Some(mv) = moves(&board, board.to_move()).pop() else {
+                break;
+            };
+            board.apply_move(&mv);
+            let comment = (i % 7 == 0).then(|| format!("Move {i}"));
+            record.moves.push(RecordedMove { mv, comment });
+        }
+        record.result = GameStatus::Won(1);
+
+        let text = record.to_text();
+        assert!(text
+            .lines()
+            .all(|line| line.len() <= 80 || line.starts_with('[')));
+
+        let parsed = GameRecord::parse(&text).unwrap();
+        assert_eq!(record.headers, parsed.headers);
+        assert_eq!(Some("A (braced) comment"), parsed.comment.as_deref());
+        assert_eq!(record.moves, parsed.moves);
+        assert_eq!(record.result, parsed.result);
+        assert_eq!(text, parsed.to_text());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| GameRecord::parse(text).unwrap_err();
+        let position = "[Position \"3 a0a21/a2a32/5/2b3b2/1b2b0 a 2\"]\n";
+
+        assert_eq!(
+            RecordErrorKind::MissingHeader("Position"),
+            error("[Event \"x\"]\n\n*").kind
+        );
+        assert_eq!(2, error("[Event \"x\"]\n[Event x]").line);
+        assert!(matches!(
+            error("[Position \"3 a0\"]").kind,
+            RecordErrorKind::InvalidPosition(_)
+        ));
+        assert_eq!(
+            RecordErrorKind::HeaderMismatch("Size"),
+            error(&format!("[Size \"4\"]\n{position}")).kind
+        );
+
+        let illegal = error(&format!("{position}\n1. a2-a3\n2. a3-a4"));
+        assert_eq!(4, illegal.line);
+        assert_eq!(RecordErrorKind::IllegalMove("a3-a4".into()), illegal.kind);
+        assert_eq!(
+            RecordErrorKind::UnclosedComment,
+            error(&format!("{position}\n1. a2-a3 {{open")).kind
+        );
+        assert_eq!(
+            RecordErrorKind::MovesAfterResult,
+            error(&format!("{position}\n* 1. a2-a3")).kind
+        );
+        assert_eq!(
+            RecordErrorKind::HeaderMismatch("Result"),
+            error(&format!("[Result \"draw\"]\n{position}\n*")).kind
+        );
+    }
+
+    #[test]
+    fn test_results() {
+        for result in [GameStatus::Ongoing, GameStatus::Draw, GameStatus::Won(3)] {
+            assert_eq!(Some(result), parse_result(&result_text(result)));
+        }
+        assert_eq!("c-wins", result_text(GameStatus::Won(2)));
+        assert_eq!(None, parse_result("ab-wins"));
+        assert_eq!(None, parse_result("1-0"));
+    }
+
+    #[test]
+    fn test_new_record_is_dated() {
+        let record = GameRecord::new(HexBoard::new(3));
+        let date = record.header(DATE).unwrap();
+        assert_eq!(10, date.len());
+        assert_eq!(Some('.'), date.chars().nth(4));
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!((1970, 1, 1), civil_date(0));
+        assert_eq!((2000, 2, 29), civil_date(11_016));
+        assert_eq!((2024, 3, 30), civil_date(19_812));
+        assert_eq!((1969, 12, 31), civil_date(-1));
+    }
+}
@@ -0,0 +1,291 @@
+//! Replays of whole games as animated SVG images, to share finished games.
+//!
+//! A replay is a single image that shows the starting position, then every move in turn with an
+//! arrow from where the piece came from, and finally the result, before it starts over. The board
+//! is drawn once with the geometry of the SVG board, every frame only adds the pieces and marks
+//! of its position and is shown at its own time of the animation.
+
+use rocket::FromForm;
+use serde::Serialize;
+use tera::Context;
+
+use crate::util::get_template;
+
+use super::hex_board::HexBoard;
+use super::hexagon::{team_color, value_text, HexState};
+use super::layout::{Layout, Orientation};
+use super::moves::{GameStatus, Move};
+use super::notation::team_letter;
+use super::record::GameRecord;
+use super::svg::{coordinate, points, HEX_RADIUS, MARGIN, PIECE_RADIUS, VALUE_SIZE};
+
+/// How long every position is shown by default, in milliseconds.
+pub const DEFAULT_DELAY: u64 = 1000;
+
+/// How long the result is shown by default, in milliseconds.
+pub const DEFAULT_RESULT_DELAY: u64 = 3000;
+
+pub const MIN_DELAY: u64 = 100;
+
+pub const MAX_DELAY: u64 = 60_000;
+
+/// How a replay is drawn.
+#[derive(Debug, Clone, PartialEq, Eq, FromForm)]
+pub struct ReplayOptions {
+    /// How long every position is shown, in milliseconds.
+    #[field(default = DEFAULT_DELAY)]
+    pub delay: u64,
+    /// How long the result is shown before the replay starts over, in milliseconds.
+    #[field(default = DEFAULT_RESULT_DELAY)]
+    pub result_delay: u64,
+    #[field(default = Orientation::FlatTop)]
+    pub orientation: Orientation,
+    /// Draws an arrow from where every move started to where it ended.
+    #[field(default = true)]
+    pub arrows: bool,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> ReplayOptions {
+        ReplayOptions {
+            delay: DEFAULT_DELAY,
+            result_delay: DEFAULT_RESULT_DELAY,
+            orientation: Orientation::default(),
+            arrows: true,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReplayPiece {
+    x: String,
+    y: String,
+    color: &'static str,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct Arrow {
+    x1: String,
+    y1: String,
+    x2: String,
+    y2: String,
+}
+
+/// A position of the replay, with the `values` of its visibility at the `key_times` of the
+/// animation.
+#[derive(Serialize)]
+struct Frame {
+    values: String,
+    key_times: String,
+    /// The polygons of the hexes the move that led to the position went from and to.
+    marked: Vec<String>,
+    pieces: Vec<ReplayPiece>,
+    arrow: Option<Arrow>,
+    /// The result of the game, on the last frame.
+    banner: Option<String>,
+}
+
+/// When a frame is visible during the animation, which lasts `total` milliseconds and shows the
+/// frame from `start` to `end`.
+fn visibility(start: u64, end: u64, total: u64) -> (String, String) {
+    let key_time = |time: u64| format!("{:.4}", time as f64 / total as f64);
+    let mut values = Vec::new();
+    let mut key_times = Vec::new();
+
+    if start > 0 {
+        values.push("hidden");
+        key_times.push(key_time(0));
+    }
+    values.push("visible");
+    key_times.push(key_time(start));
+    if end < total {
+        values.push("hidden");
+        key_times.push(key_time(end));
+    }
+
+    (values.join(";"), key_times.join(";"))
+}
+
+fn pieces(board: &HexBoard, layout: &Layout) -> Vec<ReplayPiece> {
+    board
+        .hexagons()
+        .filter_map(|hexagon| match hexagon.state {
+            HexState::Piece { team, value } => {
+                let (x, y) = layout.center(&hexagon.hex_id);
+                Some(ReplayPiece {
+                    x: coordinate(x),
+                    y: coordinate(y),
+                    color: team_color(team),
+                    value: value_text(value),
+                })
+            }
+            HexState::Free => None,
+        })
+        .collect()
+}
+
+/// An arrow from the center of the hex the move started on, ending a little before the center
+/// of the hex it went to so the head stays inside the hex.
+fn arrow(mv: &Move, layout: &Layout) -> Arrow {
+    let (x1, y1) = layout.center(&mv.from);
+    let (x2, y2) = layout.center(&mv.to);
+    let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2))
+        .sqrt()
+        .max(f64::EPSILON);
+    let shorten = (layout.radius * 0.4).min(length / 2.0);
+
+    Arrow {
+        x1: coordinate(x1),
+        y1: coordinate(y1),
+        x2: coordinate(x2 - (x2 - x1) / length * shorten),
+        y2: coordinate(y2 - (y2 - y1) / length * shorten),
+    }
+}
+
+fn result_description(result: GameStatus, board: &HexBoard) -> String {
+    match result {
+        GameStatus::Won(team) => format!("Team {} won", team_letter(team)),
+        GameStatus::Draw => "Draw".to_string(),
+        GameStatus::Ongoing => format!("Team {} to move", team_letter(board.to_move())),
+    }
+}
+
+impl GameRecord {
+    /// An animated SVG image that plays the game move by move and ends with the result.
+    pub fn to_replay_svg(&self, options: &ReplayOptions) -> Result<String, String> {
+        for delay in [options.delay, options.result_delay] {
+            if !(MIN_DELAY..=MAX_DELAY).contains(&delay) {
+                return Err(format!(
+                    "A frame is shown for {MIN_DELAY} to {MAX_DELAY} milliseconds."
+                ));
+            }
+        }
+
+        let layout = Layout::new(&self.initial, options.orientation, HEX_RADIUS, MARGIN);
+        let move_count = self.moves.len() as u64;
+        let result_start = options.delay * (move_count + 1);
+        let total = result_start + options.result_delay;
+
+        let mut board = self.initial.clone();
+        let mut frames = Vec::new();
+        for ply in 0..=self.moves.len() {
+            let mv = ply.checked_sub(1).map(|ply| &self.moves[ply].mv);
+            if let Some(mv) = mv {
+                board.apply_move(mv);
+            }
+
+            let start = options.delay * ply as u64;
+            let (values, key_times) = visibility(start, start + options.delay, total);
+            frames.push(Frame {
+                values,
+                key_times,
+                marked: mv.map_or_else(Vec::new, |mv| {
+                    vec![points(&layout, &mv.from), points(&layout, &mv.to)]
+                }),
+                pieces: pieces(&board, &layout),
+                arrow: mv.filter(|_| options.arrows).map(|mv| arrow(mv, &layout)),
+                banner: None,
+            });
+        }
+
+        let (values, key_times) = visibility(result_start, total, total);
+        frames.push(Frame {
+            values,
+            key_times,
+            marked: Vec::new(),
+            pieces: pieces(&board, &layout),
+            arrow: None,
+            banner: Some(result_description(self.result, &board)),
+        });
+
+        let all_hexes: Vec<String> = self
+            .initial
+            .hexagons()
+            .map(|hexagon| points(&layout, &hexagon.hex_id))
+            .collect();
+
+        let mut context = Context::new();
+        context.insert("width", &coordinate(layout.width));
+        context.insert("height", &coordinate(layout.height));
+        context.insert("duration", &format!("{total}ms"));
+        context.insert("hexes", &all_hexes);
+        context.insert("frames", &frames);
+        context.insert("piece_radius", &coordinate(PIECE_RADIUS));
+        context.insert("font_size", &coordinate(VALUE_SIZE));
+        context.insert("arrow_width", &coordinate(HEX_RADIUS / 6.0));
+        context.insert("banner_y", &coordinate(layout.height / 2.0));
+        context.insert("banner_top", &coordinate(layout.height / 2.0 - HEX_RADIUS));
+        context.insert("banner_height", &coordinate(HEX_RADIUS * 2.0));
+        context.insert("banner_size", &coordinate(HEX_RADIUS));
+
+        get_template("templates/hexy/replay.html", Some(context))
+            .ok_or_else(|| "Failed to load the replay template.".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::moves::{moves, starting_position, GameStatus};
+    use crate::hexy::record::{GameRecord, RecordedMove};
+    use crate::hexy::replay::{visibility, ReplayOptions};
+
+    #[test]
+    fn test_visibility() {
+        assert_eq!(
+            ("visible;hidden".to_string(), "0.0000;0.2500".to_string()),
+            visibility(0, 100, 400)
+        );
+        assert_eq!(
+            (
+                "hidden;visible;hidden".to_string(),
+                "0.0000;0.2500;0.5000".to_string()
+            ),
+            visibility(100, 200, 400)
+        );
+        assert_eq!(
+            ("hidden;visible".to_string(), "0.0000;0.7500".to_string()),
+            visibility(300, 400, 400)
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let initial = starting_position(4, 2).unwrap();
+        let mut record = GameRecord::new(initial.clone());
+        let mut board = initial;
+        for _ in 0..2 {
+            let mv = moves(&board, board.to_move())[0].clone();
+            board.apply_move(&mv);
+            record.moves.push(RecordedMove { mv, comment: None });
+        }
+        record.result = GameStatus::Draw;
+
+        let svg = record.to_replay_svg(&ReplayOptions::default()).unwrap();
+        assert!(svg.starts_with("<svg"));
+        // The starting position, two moves and the result.
+        assert_eq!(4, svg.matches("<animate ").count());
+        assert_eq!(2, svg.matches("<line ").count());
+        assert!(svg.contains("dur=\"6000ms\""));
+        assert!(svg.contains(">Draw</text>"));
+
+        let options = ReplayOptions {
+            arrows: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            0,
+            record
+                .to_replay_svg(&options)
+                .unwrap()
+                .matches("<line ")
+                .count()
+        );
+
+        let options = ReplayOptions {
+            delay: 10,
+            ..Default::default()
+        };
+        assert!(record.to_replay_svg(&options).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! The shapes a board can have.
+//!
+//! A shape is the set of HexIds on the board. Every shape keeps its hexes at an x and y of at
+//! least 1 and as close to `a1` as it can, so the regular hexagon of Hexy is only one of them:
+//! the rhombus is the board of the game of Hex, and a custom mask can leave holes in any shape.
+
+use super::hexagon::HexId;
+use crate::util::hex_count;
+
+/// Knows which hexes are on a board, the geometry of `HexId` takes one to stop at the edge.
+pub trait Shape {
+    fn contains(&self, hex_id: &HexId) -> bool;
+}
+
+/// A bare size is the regular hexagon with that many hexes on a side.
+impl Shape for u32 {
+    fn contains(&self, hex_id: &HexId) -> bool {
+        hex_id.is_valid(*self)
+    }
+}
+
+impl<S: Shape + ?Sized> Shape for &S {
+    fn contains(&self, hex_id: &HexId) -> bool {
+        (**self).contains(hex_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BoardShape {
+    /// A regular hexagon with this many hexes on a side, the board of Hexy.
+    Hexagon(u32),
+    /// This many hexes on every side, with `a1` in the top corner. The board of Hex.
+    Rhombus(u32),
+    /// This many hexes on every side, with a straight edge from `a1` down.
+    Triangle(u32),
+    /// A hexagon with this many hexes on a side, and a point on each of its sides.
+    Star(u32),
+    /// `width` columns of `height` hexes, every other column half a hex lower.
+    Rectangle { width: u32, height: u32 },
+    /// Any set of hexes, like another shape with holes in it.
+    Custom(Mask),
+}
+
+impl Shape for BoardShape {
+    fn contains(&self, hex_id: &HexId) -> bool {
+        let (x, y) = (hex_id.x, hex_id.y);
+        if x == 0 || y == 0 {
+            return false;
+        }
+
+        match self {
+            BoardShape::Hexagon(size) => hex_id.is_valid(*size),
+            BoardShape::Rhombus(size) => x <= *size && y <= *size,
+            BoardShape::Triangle(size) => x <= y && y <= *size,
+            BoardShape::Star(size) => {
+                if *size == 0 {
+                    return false;
+                }
+
+                // Two triangles on top of each other, in cube coordinates around the center.
+                let center = (*size * 2 - 1) as i64;
+                let point = (*size - 1) as i64;
+                let cube = [x as i64 - center, center - y as i64, y as i64 - x as i64];
+                cube.iter().all(|c| *c >= -point) || cube.iter().all(|c| *c <= point)
+            }
+            BoardShape::Rectangle { width, height } => {
+                let column = y as i64 - x as i64 + ((*width as i64 - 1) / 2);
+                let row = y as i64 - 1 - (column + 1) / 2;
+                (0..*width as i64).contains(&column) && (0..*height as i64).contains(&row)
+            }
+            BoardShape::Custom(mask) => mask.contains(hex_id),
+        }
+    }
+}
+
+impl BoardShape {
+    /// The amount of hexes on a side, the longest side for shapes that have more than one.
+    pub fn size(&self) -> u32 {
+        match self {
+            BoardShape::Hexagon(size)
+            | BoardShape::Rhombus(size)
+            | BoardShape::Triangle(size)
+            | BoardShape::Star(size) => *size,
+            BoardShape::Rectangle { width, height } => *width.max(height),
+            BoardShape::Custom(mask) => mask.max_x().max(mask.max_y()),
+        }
+    }
+
+    pub fn hex_count(&self) -> u32 {
+        match self {
+            BoardShape::Hexagon(size) => hex_count(*size),
+            BoardShape::Rhombus(size) => size * size,
+            BoardShape::Triangle(size) => size * (size + 1) / 2,
+            BoardShape::Star(0) => 0,
+            BoardShape::Star(size) => {
+                let triangle = (size * 3 - 2) * (size * 3 - 1) / 2;
+                triangle * 2 - hex_count(*size)
+            }
+            BoardShape::Rectangle { width, height } => width * height,
+            BoardShape::Custom(mask) => mask.count(),
+        }
+    }
+
+    /// The highest x of a hex on the board, which is the amount of lines in the notation.
+    pub fn max_x(&self) -> u32 {
+        match self {
+            BoardShape::Hexagon(0) | BoardShape::Star(0) => 0,
+            BoardShape::Hexagon(size) => size * 2 - 1,
+            BoardShape::Rhombus(size) | BoardShape::Triangle(size) => *size,
+            BoardShape::Star(size) => size * 4 - 3,
+            BoardShape::Rectangle { width: 0, .. } => 0,
+            BoardShape::Rectangle { width, height } => height + (width - 1) / 2,
+            BoardShape::Custom(mask) => mask.max_x(),
+        }
+    }
+
+    /// The highest y of a hex on the board.
+    pub fn max_y(&self) -> u32 {
+        match self {
+            BoardShape::Rectangle { height: 0, .. } => 0,
+            BoardShape::Rectangle { width, height } => height + width / 2,
+            BoardShape::Custom(mask) => mask.max_y(),
+            _ => self.max_x(),
+        }
+    }
+
+    /// The hexes with the given x, by increasing y.
+    pub fn line(&self, x: u32) -> Vec<HexId> {
+        (1..=self.max_y())
+            .map(|y| HexId::new(x, y))
+            .filter(|hex_id| self.contains(hex_id))
+            .collect()
+    }
+
+    /// The hexes row by row from the top of the screen, every row from left to right.
+    /// The hexes of a row have the same x + y, the rows in between can be empty for a custom
+    /// shape.
+    pub fn rows(&self) -> Vec<Vec<HexId>> {
+        let max_x = self.max_x();
+        let mut rows: Vec<Vec<HexId>> = (2..=max_x + self.max_y())
+            .map(|sum| {
+                (1..=max_x.min(sum - 1))
+                    .rev()
+                    .map(|x| HexId::new(x, sum - x))
+                    .filter(|hex_id| self.contains(hex_id))
+                    .collect()
+            })
+            .collect();
+
+        while rows.last().is_some_and(Vec::is_empty) {
+            rows.pop();
+        }
+        let first = rows
+            .iter()
+            .position(|row| !row.is_empty())
+            .unwrap_or(rows.len());
+        rows.drain(..first);
+
+        rows
+    }
+
+    /// The hex in the middle of the board. For a custom shape it is the middle of the lines it
+    /// spans, which can be a hole.
+    pub fn center(&self) -> HexId {
+        match self {
+            BoardShape::Hexagon(size) => HexId::new(*size, *size),
+            BoardShape::Rhombus(size) => HexId::new(size.div_ceil(2), size.div_ceil(2)),
+            BoardShape::Triangle(size) => HexId::new(size.div_ceil(3), (size * 2 + 1) / 3),
+            BoardShape::Star(size) => {
+                HexId::new((size * 2).saturating_sub(1), (size * 2).saturating_sub(1))
+            }
+            BoardShape::Rectangle { width, height } => {
+                let column = width.saturating_sub(1) / 2;
+                let row = height.saturating_sub(1) / 2;
+                HexId::new(
+                    row + 1 + width.saturating_sub(1) / 2 - column / 2,
+                    row + 1 + column.div_ceil(2),
+                )
+            }
+            BoardShape::Custom(mask) => {
+                HexId::new(mask.max_x().div_ceil(2), mask.max_y().div_ceil(2))
+            }
+        }
+    }
+
+    /// This shape without the given hexes.
+    pub fn with_holes(&self, holes: &[HexId]) -> BoardShape {
+        BoardShape::Custom(Mask::new(
+            self.rows()
+                .into_iter()
+                .flatten()
+                .filter(|hex_id| !holes.contains(hex_id)),
+        ))
+    }
+}
+
+/// The hexes of a custom shape, as a line of booleans for every x.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Mask {
+    lines: Vec<Vec<bool>>,
+}
+
+impl Mask {
+    /// A mask of the given hexes, hexes with an x or y of 0 are left out.
+    pub fn new(hex_ids: impl IntoIterator<Item = HexId>) -> Mask {
+        let mut lines: Vec<Vec<bool>> = Vec::new();
+        for hex_id in hex_ids {
+            if hex_id.x == 0 || hex_id.y == 0 {
+                continue;
+            }
+
+            let (x, y) = (hex_id.x as usize - 1, hex_id.y as usize - 1);
+            if lines.len() <= x {
+                lines.resize(x + 1, Vec::new());
+            }
+            if lines[x].len() <= y {
+                lines[x].resize(y + 1, false);
+            }
+            lines[x][y] = true;
+        }
+
+        Mask { lines }
+    }
+
+    pub fn contains(&self, hex_id: &HexId) -> bool {
+        let (Some(x), Some(y)) = (hex_id.x.checked_sub(1), hex_id.y.checked_sub(1)) else {
+            return false;
+        };
+
+        self.lines
+            .get(x as usize)
+            .and_then(|line| line.get(y as usize))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn max_x(&self) -> u32 {
+        self.lines.len() as u32
+    }
+
+    pub fn max_y(&self) -> u32 {
+        self.lines.iter().map(Vec::len).max().unwrap_or(0) as u32
+    }
+
+    pub fn count(&self) -> u32 {
+        self.lines.iter().flatten().filter(|on| **on).count() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::geometry::Direction;
+    use crate::hexy::hexagon::HexId;
+    use crate::hexy::shape::{BoardShape, Mask, Shape};
+
+    fn shapes(size: u32) -> Vec<BoardShape> {
+        vec![
+            BoardShape::Hexagon(size),
+            BoardShape::Rhombus(size),
+            BoardShape::Triangle(size),
+            BoardShape::Star(size),
+            BoardShape::Rectangle {
+                width: size,
+                height: size + 2,
+            },
+            BoardShape::Rectangle {
+                width: size + 3,
+                height: size,
+            },
+            BoardShape::Hexagon(size).with_holes(&[HexId::new(size, size)]),
+        ]
+    }
+
+    #[test]
+    fn test_hex_counts() {
+        for size in 1..=8 {
+            for shape in shapes(size) {
+                let rows = shape.rows();
+                let count = rows.iter().map(Vec::len).sum::<usize>();
+                assert_eq!(shape.hex_count() as usize, count, "{shape:?}");
+
+                let lines: usize = (1..=shape.max_x()).map(|x| shape.line(x).len()).sum();
+                assert_eq!(count, lines, "{shape:?}");
+                assert!(rows
+                    .iter()
+                    .flatten()
+                    .all(|hex_id| { hex_id.x <= shape.max_x() && hex_id.y <= shape.max_y() }));
+            }
+        }
+
+        assert_eq!(121, BoardShape::Star(5).hex_count());
+        assert_eq!(121, BoardShape::Rhombus(11).hex_count());
+        assert_eq!(10, BoardShape::Triangle(4).hex_count());
+    }
+
+    #[test]
+    fn test_contains() {
+        let rhombus = BoardShape::Rhombus(3);
+        assert!(rhombus.contains(&HexId::new(1, 1)));
+        assert!(rhombus.contains(&HexId::new(3, 3)));
+        assert!(!rhombus.contains(&HexId::new(4, 1)));
+        assert!(!rhombus.contains(&HexId::new(0, 1)));
+
+        let triangle = BoardShape::Triangle(3);
+        assert!(triangle.contains(&HexId::new(1, 3)));
+        assert!(triangle.contains(&HexId::new(3, 3)));
+        assert!(!triangle.contains(&HexId::new(2, 1)));
+
+        // The points of the star, and the corners of the hexagon in between.
+        let star = BoardShape::Star(3);
+        for hex_id in [
+            HexId::new(3, 1),
+            HexId::new(1, 3),
+            HexId::new(9, 7),
+            HexId::new(3, 3),
+        ] {
+            assert!(star.contains(&hex_id), "{hex_id}");
+        }
+        assert!(star.contains(&HexId::new(3, 3)));
+        assert!(!star.contains(&HexId::new(2, 1)));
+
+        let rectangle = BoardShape::Rectangle {
+            width: 3,
+            height: 2,
+        };
+        assert_eq!(
+            vec![
+                vec![HexId::new(2, 1), HexId::new(1, 2)],
+                vec![HexId::new(2, 2)],
+                vec![HexId::new(3, 2), HexId::new(2, 3)],
+                vec![HexId::new(3, 3)],
+            ],
+            rectangle.rows()
+        );
+        assert!(rectangle.contains(&rectangle.center()));
+
+        assert!(!BoardShape::Hexagon(3)
+            .with_holes(&[HexId::new(3, 3)])
+            .contains(&HexId::new(3, 3)));
+    }
+
+    #[test]
+    fn test_centers() {
+        for size in 1..=8 {
+            for shape in shapes(size)
+                .into_iter()
+                .filter(|shape| !matches!(shape, BoardShape::Custom(_)))
+            {
+                assert!(shape.contains(&shape.center()), "{shape:?}");
+            }
+        }
+
+        assert_eq!(HexId::new(5, 5), BoardShape::Star(3).center());
+    }
+
+    #[test]
+    fn test_geometry_stops_at_the_edge() {
+        let triangle = BoardShape::Triangle(4);
+        assert_eq!(2, HexId::new(1, 1).neighbours(&triangle).len());
+        assert_eq!(2, HexId::new(1, 1).ring(1, &triangle).len());
+
+        let holed = BoardShape::Rhombus(4).with_holes(&[HexId::new(2, 3)]);
+        assert_eq!(
+            vec![HexId::new(2, 2)],
+            HexId::new(2, 1).ray(Direction::SouthEast, &holed)
+        );
+    }
+
+    #[test]
+    fn test_mask() {
+        let mask = Mask::new([HexId::new(2, 3), HexId::new(0, 1), HexId::new(1, 1)]);
+        assert_eq!(2, mask.count());
+        assert_eq!((2, 3), (mask.max_x(), mask.max_y()));
+        assert!(mask.contains(&HexId::new(2, 3)));
+        assert!(!mask.contains(&HexId::new(2, 2)));
+        assert!(!mask.contains(&HexId::new(0, 1)));
+    }
+}
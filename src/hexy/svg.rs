@@ -0,0 +1,154 @@
+//! Drawing a board as an SVG image, which looks the same in every browser and at every zoom.
+//!
+//! The hexes keep the classes and attributes of the HTML board, so clicking them and marking
+//! them works the same.
+
+use serde::Serialize;
+use tera::Context;
+
+use crate::util::get_template;
+
+use super::hex_board::{BoardView, HexBoard};
+use super::hexagon::{team_color, value_text, HexId, HexMarks, HexState};
+use super::layout::{Layout, Orientation};
+
+/// The radius of a hex in the image, the image is scaled to fit the page.
+pub const HEX_RADIUS: f64 = 30.0;
+
+/// The space around the hexes in the image.
+pub const MARGIN: f64 = 4.0;
+
+pub const PIECE_RADIUS: f64 = HEX_RADIUS * 0.6;
+
+/// The size of the font of the values on the pieces.
+pub const VALUE_SIZE: f64 = HEX_RADIUS * 0.6;
+
+#[derive(Serialize)]
+struct SvgPiece {
+    color: &'static str,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct SvgHex {
+    /// The marks of the hex as classes, each with a space before it.
+    marks: String,
+    grid_id: String,
+    hex_id: String,
+    click_url: Option<String>,
+    x: String,
+    y: String,
+    points: String,
+    piece: Option<SvgPiece>,
+}
+
+/// Writes a coordinate short enough for the image, a hundredth of a pixel is as exact as needed.
+pub fn coordinate(value: f64) -> String {
+    let text = format!("{value:.2}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// The corners of the hex for the `points` of an SVG polygon.
+pub fn points(layout: &Layout, hex_id: &HexId) -> String {
+    layout
+        .corners(hex_id)
+        .iter()
+        .map(|(x, y)| format!("{},{}", coordinate(*x), coordinate(*y)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn mark_classes(marks: HexMarks) -> String {
+    [
+        (marks.selected, " selected"),
+        (marks.target, " target"),
+        (marks.capture, " capture"),
+        (marks.last_move, " last_move"),
+    ]
+    .into_iter()
+    .filter_map(|(marked, class)| marked.then_some(class))
+    .collect()
+}
+
+impl HexBoard {
+    pub fn to_svg(&self, orientation: Orientation) -> String {
+        self.to_svg_with(&BoardView::default(), orientation)
+    }
+
+    pub fn to_svg_with(&self, view: &BoardView, orientation: Orientation) -> String {
+        let layout = Layout::new(self, orientation, HEX_RADIUS, MARGIN);
+        let hexes: Vec<SvgHex> = self
+            .hexagons()
+            .map(|hexagon| {
+                let (x, y) = layout.center(&hexagon.hex_id);
+                let piece = match hexagon.state {
+                    HexState::Piece { team, value } => Some(SvgPiece {
+                        color: team_color(team),
+                        value: value_text(value),
+                    }),
+                    HexState::Free => None,
+                };
+
+                SvgHex {
+                    marks: mark_classes(view.marks(&hexagon.hex_id)),
+                    grid_id: hexagon.grid_id.to_string(),
+                    hex_id: hexagon.hex_id.to_string(),
+                    click_url: view
+                        .click_url
+                        .as_ref()
+                        .map(|url| format!("{url}?hex={}", hexagon.hex_id.to_notation())),
+                    x: coordinate(x),
+                    y: coordinate(y),
+                    points: points(&layout, &hexagon.hex_id),
+                    piece,
+                }
+            })
+            .collect();
+
+        let mut context = Context::new();
+        context.insert("width", &coordinate(layout.width));
+        context.insert("height", &coordinate(layout.height));
+        context.insert("piece_radius", &coordinate(PIECE_RADIUS));
+        context.insert("font_size", &coordinate(VALUE_SIZE));
+        context.insert("hexes", &hexes);
+
+        get_template("templates/hexy/svg_board.html", Some(context))
+            .expect("Failed to load SVG board template.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hexy::hex_board::{BoardView, HexBoard, Renderer};
+    use crate::hexy::hexagon::HexId;
+    use crate::hexy::layout::Orientation;
+    use crate::hexy::svg::coordinate;
+
+    #[test]
+    fn test_coordinate() {
+        assert_eq!("12", coordinate(12.0));
+        assert_eq!("0.5", coordinate(0.499));
+        assert_eq!("-1.23", coordinate(-1.23456));
+    }
+
+    #[test]
+    fn test_svg_board() {
+        let board = HexBoard::from_notation("3 a0a21/4/5/4/2b0 a 2").unwrap();
+        let view = BoardView {
+            renderer: Renderer::Svg(Orientation::PointyTop),
+            click_url: Some("/hexy/games/1/click".into()),
+            selected: Some(HexId::new(1, 1)),
+            ..Default::default()
+        };
+        let svg = board.render(&view);
+
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(19, svg.matches("<polygon").count());
+        assert_eq!(3, svg.matches("<circle").count());
+        assert_eq!(1, svg.matches("class=\"hexagon selected\"").count());
+        assert!(svg.contains("hex-id=\"(1, 1)\""));
+        assert_eq!(19, svg.matches("hx-post=").count());
+        assert!(svg.contains("click?hex=a1\""));
+        assert!(!board.to_svg(Orientation::FlatTop).contains("hx-post"));
+    }
+}
@@ -0,0 +1,542 @@
+//! Matches between engines without a server, to measure whether a change makes an engine
+//! stronger.
+//!
+//! Every pairing plays every starting position twice, once with each engine moving first, so
+//! neither is helped by the side it happens to play. The first starting position of a size is
+//! the standard one, the others start with a few random moves that do not capture anything.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::engine::protocol::GoLimits;
+use super::engine::EngineKind;
+use super::game::Game;
+use super::hex_board::HexBoard;
+use super::hexagon::HexState;
+use super::moves::{moves, starting_position, GameStatus};
+use super::record::{GameRecord, EVENT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Every engine plays every other engine.
+    RoundRobin,
+    /// The first engine plays each of the others, who do not play each other.
+    Gauntlet,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entrant {
+    pub name: String,
+    pub engine: EngineKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentSettings {
+    pub format: Format,
+    pub sizes: Vec<u32>,
+    /// The starting positions for every size, including the standard one.
+    pub openings: u32,
+    /// How many random moves the other starting positions start with.
+    pub opening_plies: u32,
+    /// The limits of every move.
+    pub limits: GoLimits,
+    /// Games that go on longer than this are a draw.
+    pub max_plies: usize,
+    /// How many games are played at the same time.
+    pub threads: usize,
+    pub seed: u64,
+}
+
+impl Default for TournamentSettings {
+    fn default() -> TournamentSettings {
+        TournamentSettings {
+            format: Format::RoundRobin,
+            sizes: vec![5],
+            openings: 1,
+            opening_plies: 4,
+            limits: GoLimits::default(),
+            max_plies: 300,
+            threads: 1,
+            seed: 0,
+        }
+    }
+}
+
+/// A game to play, `first` and `second` are indices of entrants.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub first: usize,
+    pub second: usize,
+    pub size: u32,
+    /// Which starting position of the size, 0 is the standard one.
+    pub opening: u32,
+    pub board: HexBoard,
+}
+
+/// Why a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    /// A leader was captured, a position came up too often or the side to move was stuck.
+    Rules,
+    /// The game went on for `max_plies` and counts as a draw.
+    MoveLimit,
+    /// The side to move did not play a legal move and lost.
+    Forfeit,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameResult {
+    /// The index of the fixture the game was played for.
+    pub index: usize,
+    pub fixture: Fixture,
+    /// The index of the entrant that won, None for a draw.
+    pub winner: Option<usize>,
+    pub ending: Ending,
+    pub record: GameRecord,
+}
+
+/// How an entrant did, one point for a win and half a point for a draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Standing {
+    pub entrant: usize,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Standing {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+pub struct Tournament {
+    pub entrants: Vec<Entrant>,
+    pub settings: TournamentSettings,
+}
+
+impl Tournament {
+    pub fn new(entrants: Vec<Entrant>, settings: TournamentSettings) -> Tournament {
+        Tournament { entrants, settings }
+    }
+
+    /// The games of the tournament, in the order they are started.
+    pub fn fixtures(&self) -> Result<Vec<Fixture>, String> {
+        if self.entrants.len() < 2 {
+            return Err("A tournament needs at least two engines.".into());
+        }
+
+        let pairs: Vec<(usize, usize)> = match self.settings.format {
+            Format::RoundRobin => (0..self.entrants.len())
+                .flat_map(|a| (a + 1..self.entrants.len()).map(move |b| (a, b)))
+                .collect(),
+            Format::Gauntlet => (1..self.entrants.len()).map(|b| (0, b)).collect(),
+        };
+
+        let mut fixtures = Vec::new();
+        for &size in &self.settings.sizes {
+            let start = starting_position(size, 2)
+                .ok_or_else(|| format!("A board of size {size} has no room for two teams."))?;
+
+            for opening in 0..self.settings.openings {
+                let board = self.opening(&start, size, opening);
+                for &(a, b) in &pairs {
+                    for (first, second) in [(a, b), (b, a)] {
+                        fixtures.push(Fixture {
+                            first,
+                            second,
+                            size,
+                            opening,
+                            board: board.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(fixtures)
+    }
+
+    /// Plays all games on `settings.threads` threads, and calls `on_result` as each one ends.
+    /// The results are in the order of the fixtures.
+    pub fn run(&self, mut on_result: impl FnMut(&GameResult)) -> Result<Vec<GameResult>, String> {
+        let fixtures = self.fixtures()?;
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+
+        let mut results = thread::scope(|scope| {
+            for _ in 0..self.settings.threads.clamp(1, fixtures.len().max(1)) {
+                let sender = sender.clone();
+                let (next, fixtures) = (&next, &fixtures);
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(fixture) = fixtures.get(index) else {
+                        break;
+                    };
+                    if sender.send(self.play(index, fixture)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            receiver
+                .iter()
+                .inspect(|result| on_result(result))
+                .collect::<Vec<GameResult>>()
+        });
+
+        results.sort_by_key(|result| result.index);
+        Ok(results)
+    }
+
+    /// Plays one game, an engine that can not start or play a legal move loses it.
+    pub fn play(&self, index: usize, fixture: &Fixture) -> GameResult {
+        let seats = [fixture.first, fixture.second];
+        let seed = self.settings.seed.wrapping_add(index as u64);
+        let mut engines: Vec<_> = seats
+            .iter()
+            .map(|entrant| {
+                self.entrants[*entrant]
+                    .engine
+                    .create(&self.settings.limits, seed)
+            })
+            .collect();
+
+        let mut game = Game::new(index as u64, fixture.board.clone());
+        let (status, ending) = loop {
+            let status = game.status();
+            if status != GameStatus::Ongoing {
+                break (status, Ending::Rules);
+            }
+            if game.ply() >= self.settings.max_plies {
+                break (GameStatus::Draw, Ending::MoveLimit);
+            }
+
+            let team = game.board.to_move();
+            let mv = engines[team as usize]
+                .as_mut()
+                .ok()
+                .and_then(|engine| engine.choose_move(&game.board))
+                .filter(|mv| moves(&game.board, team).contains(mv));
+            match mv {
+                Some(mv) => game.play(&mv),
+                None => break (GameStatus::Won(1 - team), Ending::Forfeit),
+            }
+        };
+
+        let mut record = game.to_record();
+        record.set_header(EVENT, "Hexy tournament");
+        record.set_header("Round", &(index + 1).to_string());
+        for (team, entrant) in seats.iter().enumerate() {
+            record.set_player(team as u32, &self.entrants[*entrant].name);
+        }
+        match ending {
+            Ending::Rules => {}
+            Ending::MoveLimit => record.set_header("Termination", "move limit"),
+            Ending::Forfeit => record.set_header("Termination", "forfeit"),
+        }
+        record.result = status;
+
+        GameResult {
+            index,
+            fixture: fixture.clone(),
+            winner: match status {
+                GameStatus::Won(team) => Some(seats[team as usize]),
+                _ => None,
+            },
+            ending,
+            record,
+        }
+    }
+
+    /// The starting position `opening` of a size, the same on every run with the same seed.
+    fn opening(&self, start: &HexBoard, size: u32, opening: u32) -> HexBoard {
+        let mut board = start.clone();
+        if opening == 0 {
+            return board;
+        }
+
+        let seed = self.settings.seed ^ ((size as u64) << 32 | opening as u64);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..self.settings.opening_plies {
+            let quiet: Vec<_> = moves(&board, board.to_move())
+                .into_iter()
+                .filter(|mv| mv.captured == HexState::Free)
+                .collect();
+            let Some(mv) = quiet.choose(&mut rng) else {
+                break;
+            };
+            board.apply_move(mv);
+        }
+
+        board
+    }
+}
+
+/// The standings of all entrants, best first.
+pub fn standings(entrants: usize, results: &[GameResult]) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = (0..entrants)
+        .map(|entrant| Standing {
+            entrant,
+            ..Default::default()
+        })
+        .collect();
+
+    for result in results {
+        let Fixture { first, second, .. } = result.fixture;
+        match result.winner {
+            Some(winner) => {
+                let loser = if winner == first { second } else { first };
+                standings[winner].wins += 1;
+                standings[loser].losses += 1;
+            }
+            None => {
+                standings[first].draws += 1;
+                standings[second].draws += 1;
+            }
+        }
+    }
+
+    standings.sort_by(|a, b| {
+        b.points()
+            .total_cmp(&a.points())
+            .then(a.entrant.cmp(&b.entrant))
+    });
+    standings
+}
+
+/// A text table of the standings, for a terminal.
+pub struct ResultsTable<'a> {
+    pub entrants: &'a [Entrant],
+    pub standings: &'a [Standing],
+}
+
+impl Display for ResultsTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .entrants
+            .iter()
+            .map(|entrant| entrant.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("Engine".len());
+
+        writeln!(
+            f,
+            "{:>2}  {:<width$}  {:>5}  {:>4}  {:>4}  {:>4}  {:>6}  {:>5}",
+            "#", "Engine", "Games", "Won", "Draw", "Lost", "Points", "Score"
+        )?;
+        for (rank, standing) in self.standings.iter().enumerate() {
+            let score = match standing.games() {
+                0 => 0.0,
+                games => standing.points() / games as f64 * 100.0,
+            };
+            writeln!(
+                f,
+                "{:>2}  {:<width$}  {:>5}  {:>4}  {:>4}  {:>4}  {:>6.1}  {:>4.0}%",
+                rank + 1,
+                self.entrants[standing.entrant].name,
+                standing.games(),
+                standing.wins,
+                standing.draws,
+                standing.losses,
+                standing.points(),
+                score
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::hexy::engine::protocol::GoLimits;
+    use crate::hexy::engine::EngineKind;
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::HexState;
+    use crate::hexy::moves::starting_position;
+    use crate::hexy::tournament::{
+        standings, Ending, Entrant, Format, ResultsTable, Tournament, TournamentSettings,
+    };
+
+    fn entrant(name: &str, engine: EngineKind) -> Entrant {
+        Entrant {
+            name: name.into(),
+            engine,
+        }
+    }
+
+    fn settings() -> TournamentSettings {
+        TournamentSettings {
+            limits: GoLimits {
+                movetime: Some(Duration::from_millis(5)),
+                ..Default::default()
+            },
+            sizes: vec![3],
+            max_plies: 40,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let entrants = vec![
+            entrant("one", EngineKind::AlphaBeta),
+            entrant("two", EngineKind::Mcts),
+            entrant("three", EngineKind::AlphaBeta),
+        ];
+        let mut tournament = Tournament::new(
+            entrants,
+            TournamentSettings {
+                sizes: vec![3, 4],
+                openings: 2,
+                ..settings()
+            },
+        );
+
+        let fixtures = tournament.fixtures().unwrap();
+        assert_eq!(3 * 2 * 2 * 2, fixtures.len());
+        assert_eq!(
+            starting_position(3, 2).unwrap().to_notation(),
+            fixtures[0].board.to_notation()
+        );
+        assert_eq!((0, 1), (fixtures[0].first, fixtures[0].second));
+        assert_eq!((1, 0), (fixtures[1].first, fixtures[1].second));
+
+        let opening = &fixtures[6];
+        assert_eq!((3, 1), (opening.size, opening.opening));
+        assert_ne!(fixtures[0].board.to_notation(), opening.board.to_notation());
+        assert_eq!(opening.board.to_notation(), fixtures[7].board.to_notation());
+        let pieces = |board: &HexBoard| {
+            board
+                .hexagons()
+                .filter(|hexagon| hexagon.state != HexState::Free)
+                .count()
+        };
+        assert_eq!(pieces(&fixtures[0].board), pieces(&opening.board));
+
+        tournament.settings.format = Format::Gauntlet;
+        assert_eq!(2 * 2 * 2 * 2, tournament.fixtures().unwrap().len());
+
+        tournament.entrants.truncate(1);
+        assert!(tournament.fixtures().is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let entrants = vec![
+            entrant("alpha-beta", EngineKind::AlphaBeta),
+            entrant("mcts", EngineKind::Mcts),
+        ];
+        let tournament = Tournament::new(
+            entrants,
+            TournamentSettings {
+                openings: 2,
+                threads: 3,
+                ..settings()
+            },
+        );
+
+        let mut reported = 0;
+        let results = tournament.run(|_| reported += 1).unwrap();
+        assert_eq!(4, results.len());
+        assert_eq!(4, reported);
+        assert!(results
+            .iter()
+            .enumerate()
+            .all(|(i, result)| result.index == i));
+
+        let standings = standings(2, &results);
+        let points: f64 = standings.iter().map(|standing| standing.points()).sum();
+        assert_eq!(4.0, points);
+        assert_eq!(4, standings[0].games());
+
+        let record = &results[1].record;
+        assert_eq!(Some("mcts"), record.player(0));
+        assert_eq!(Some("alpha-beta"), record.player(1));
+        assert_eq!(Some("2"), record.header("Round"));
+
+        let table = ResultsTable {
+            entrants: &tournament.entrants,
+            standings: &standings,
+        }
+        .to_string();
+        assert_eq!(3, table.lines().count());
+        assert!(table.lines().nth(1).unwrap().starts_with(" 1  "));
+    }
+
+    #[test]
+    fn test_each_limit_on_its_own() {
+        let entrants = vec![
+            entrant("alpha-beta", EngineKind::AlphaBeta),
+            entrant("mcts", EngineKind::Mcts),
+        ];
+        let depth = GoLimits {
+            depth: Some(1),
+            ..Default::default()
+        };
+        let iterations = GoLimits {
+            iterations: Some(20),
+            ..Default::default()
+        };
+
+        // The engine without a limit of its own thinks for the default time, one move each.
+        for limits in [depth, iterations] {
+            let tournament = Tournament::new(
+                entrants.clone(),
+                TournamentSettings {
+                    limits,
+                    max_plies: 2,
+                    threads: 2,
+                    ..settings()
+                },
+            );
+
+            let results = tournament.run(|_| {}).unwrap();
+            assert_eq!(2, results.len());
+            assert!(
+                results
+                    .iter()
+                    .all(|result| result.ending == Ending::MoveLimit),
+                "{limits:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_engines_that_do_not_play_forfeit() {
+        let entrants = vec![
+            entrant("alpha-beta", EngineKind::AlphaBeta),
+            entrant(
+                "missing",
+                EngineKind::External {
+                    name: "missing".into(),
+                    command: "hexy-engine-that-does-not-exist".into(),
+                },
+            ),
+        ];
+        let tournament = Tournament::new(entrants, settings());
+
+        let results = tournament.run(|_| {}).unwrap();
+        assert!(results
+            .iter()
+            .all(|result| result.ending == Ending::Forfeit));
+        assert!(results.iter().all(|result| result.winner == Some(0)));
+        assert_eq!(Some("forfeit"), results[0].record.header("Termination"));
+        assert_eq!(2, standings(2, &results)[0].wins);
+    }
+}
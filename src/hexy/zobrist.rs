@@ -0,0 +1,126 @@
+//! Zobrist hashing of Hexy positions.
+//!
+//! Every piece on a hex and every side to move has a random looking 64 bit key, and the hash
+//! of a position is all of its keys xor-ed together. Changing one hex only takes two xors, so
+//! `HexBoard` keeps its hash up to date as pieces move.
+//!
+//! The keys are made by mixing the hex, team and value instead of being looked up in a table,
+//! so boards of any size and pieces of any value have keys. They are the same on every run.
+
+use super::hex_board::HexBoard;
+use super::hexagon::{HexId, HexState};
+
+const PIECE_SEED: u64 = 0x5A0B_1F7A_77C4_9E3D;
+const SIDE_SEED: u64 = 0x0D1C_E5E1_D0C7_A92B;
+
+/// The SplitMix64 finalizer, which turns similar inputs into unrelated outputs.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn piece_key(hex_id: &HexId, team: u32, value: u32) -> u64 {
+    [hex_id.x, hex_id.y, team, value]
+        .into_iter()
+        .fold(PIECE_SEED, |key, part| mix(key ^ part as u64))
+}
+
+/// The key of what is on a hex, a free hex does not change the hash.
+pub fn state_key(hex_id: &HexId, state: HexState) -> u64 {
+    match state {
+        HexState::Free => 0,
+        HexState::Piece { team, value } => piece_key(hex_id, team, value),
+    }
+}
+
+pub fn side_key(team: u32) -> u64 {
+    mix(SIDE_SEED ^ team as u64)
+}
+
+/// Hashes a board from scratch, `HexBoard::hash` gives the same without the work.
+pub fn hash_board(board: &HexBoard) -> u64 {
+    board
+        .hexagons()
+        .fold(side_key(board.to_move()), |hash, hexagon| {
+            hash ^ state_key(&hexagon.hex_id, hexagon.state)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::hexy::hex_board::HexBoard;
+    use crate::hexy::hexagon::{HexId, HexState};
+    use crate::hexy::moves::{moves, starting_position};
+    use crate::hexy::zobrist::{hash_board, piece_key, side_key};
+
+    #[test]
+    fn test_incremental_hash_matches_full_hash() {
+        let mut board = starting_position(5, 3).unwrap();
+        let start = board.hash();
+        assert_eq!(hash_board(&board), start);
+
+        let mut played = Vec::new();
+        for i in 0..30 {
+            let mut legal = moves(&board, board.to_move());
+            if legal.is_empty() {
+                break;
+            }
+
+            let mv = legal.swap_remove(i * 7 % legal.len());
+            board.apply_move(&mv);
+            assert_eq!(hash_board(&board), board.hash());
+            played.push(mv);
+        }
+
+        for mv in played.iter().rev() {
+            board.undo_move(mv);
+        }
+        assert_eq!(start, board.hash());
+    }
+
+    #[test]
+    fn test_notation_keeps_hash() {
+        let board = starting_position(4, 2).unwrap();
+        let parsed = HexBoard::from_notation(&board.to_notation()).unwrap();
+        assert_eq!(board.hash(), parsed.hash());
+
+        let mut other_side = parsed.clone();
+        other_side.set_to_move(1);
+        assert_ne!(board.hash(), other_side.hash());
+    }
+
+    #[test]
+    fn test_set_state_updates_hash() {
+        let mut board = HexBoard::new(3);
+        let empty = board.hash();
+        let hex_id = HexId::new(2, 3);
+        let piece = HexState::Piece { team: 1, value: 4 };
+
+        assert_eq!(Some(HexState::Free), board.set_state(&hex_id, piece));
+        assert_eq!(empty ^ piece_key(&hex_id, 1, 4), board.hash());
+        assert_eq!(Some(piece), board.set_state(&hex_id, HexState::Free));
+        assert_eq!(empty, board.hash());
+        assert_eq!(None, board.set_state(&HexId::new(1, 5), piece));
+    }
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut keys = HashSet::new();
+        for x in 1..=9 {
+            for y in 1..=9 {
+                for team in 0..6 {
+                    for value in 0..5 {
+                        assert!(keys.insert(piece_key(&HexId::new(x, y), team, value)));
+                    }
+                }
+            }
+        }
+        for team in 0..26 {
+            assert!(keys.insert(side_key(team)));
+        }
+    }
+}
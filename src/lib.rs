@@ -0,0 +1,12 @@
+pub mod csv_mfr;
+pub mod hexy;
+pub mod storage;
+pub mod util;
+
+use tera::Tera;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    pub static ref TEMPLATES: Tera = Tera::new("**/*.html").expect("Could not create Tera object.");
+}
@@ -1,7 +1,3 @@
-mod csv_mfr;
-mod hexy;
-mod util;
-
 use std::path::Path;
 
 use rocket::fs::relative;
@@ -12,13 +8,11 @@ use rocket::response::content::RawHtml;
 use rocket::response::Redirect;
 use rocket::routes;
 use tera::Context;
-use tera::Tera;
-
-use lazy_static::lazy_static;
 
-lazy_static! {
-    pub static ref TEMPLATES: Tera = Tera::new("**/*.html").expect("Could not create Tera object.");
-}
+use yground::csv_mfr;
+use yground::hexy;
+use yground::storage;
+use yground::TEMPLATES;
 
 #[get("/")]
 async fn index() -> Option<NamedFile> {
@@ -50,8 +44,16 @@ async fn navbar(active: Option<&str>) -> Option<RawHtml<String>> {
 }
 
 #[rocket::main]
-async fn main() -> Result<(), rocket::Error> {
-    let _rocket = rocket::build()
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let rocket = rocket::build();
+    let store = storage::from_figment(rocket.figment())?;
+    let engines = hexy::engine::external::ExternalEngines::from_figment(rocket.figment());
+    let games = hexy::game::Games::from_figment(rocket.figment(), store.clone(), &engines)?;
+
+    let _rocket = rocket
+        .manage(games)
+        .manage(engines)
+        .manage(store)
         .mount("/", routes![index, navbar, favicon])
         .mount("/public", FileServer::from(relative!("public")))
         .mount("/csv_mfr", csv_mfr::get_routes())
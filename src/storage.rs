@@ -0,0 +1,405 @@
+//! Keeps what would otherwise be lost when the server restarts: Hexy games with their moves,
+//! saved positions and CSV MFR pipelines.
+//!
+//! Everything goes through the `Store` trait. The server uses a `SqliteStore` when the
+//! `database` config value names a file, and a `MemoryStore` otherwise, which is also what tests
+//! use. The stored types are plain data, it is up to the modules that use them to turn them
+//! back into games and pipelines.
+
+pub mod sqlite;
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rocket::figment::Figment;
+use serde::{Deserialize, Serialize};
+
+pub use sqlite::SqliteStore;
+
+/// A store shared by everything on the server, managed by Rocket.
+pub type SharedStore = Arc<dyn Store>;
+
+/// Opens the SQLite database in the `database` config value, or keeps everything in memory
+/// when there is none.
+pub fn from_figment(figment: &Figment) -> Result<SharedStore, StoreError> {
+    match figment.extract_inner::<String>("database") {
+        Ok(path) => Ok(Arc::new(SqliteStore::open(&path)?)),
+        Err(_) => Ok(Arc::new(MemoryStore::default())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreError(pub String);
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Storage failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+pub trait Store: Debug + Send + Sync {
+    /// Adds the game, or replaces the game with the same id.
+    fn save_game(&self, game: &SavedGame) -> Result<(), StoreError>;
+    /// All games, by id.
+    fn games(&self) -> Result<Vec<SavedGame>, StoreError>;
+    fn delete_game(&self, id: u64) -> Result<(), StoreError>;
+
+    /// Adds a position with a new id.
+    fn save_position(&self, name: &str, notation: &str) -> Result<SavedPosition, StoreError>;
+    /// All positions, oldest first.
+    fn positions(&self) -> Result<Vec<SavedPosition>, StoreError>;
+    fn position(&self, id: i64) -> Result<Option<SavedPosition>, StoreError>;
+    /// Removes a position, false when there was none with the id.
+    fn delete_position(&self, id: i64) -> Result<bool, StoreError>;
+
+    /// Adds the pipeline, or replaces the pipeline with the same name.
+    fn save_pipeline(&self, pipeline: &Pipeline) -> Result<(), StoreError>;
+    /// All pipelines, by name.
+    fn pipelines(&self) -> Result<Vec<Pipeline>, StoreError>;
+    fn pipeline(&self, name: &str) -> Result<Option<Pipeline>, StoreError>;
+    /// Removes a pipeline, false when there was none with the name.
+    fn delete_pipeline(&self, name: &str) -> Result<bool, StoreError>;
+
+    /// Keeps the record of a rated game, in Hexy record notation.
+    fn save_rated_record(&self, record: &str) -> Result<(), StoreError>;
+    /// The records of all rated games, in the order they were rated.
+    fn rated_records(&self) -> Result<Vec<String>, StoreError>;
+}
+
+/// A Hexy game as it is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedGame {
+    pub id: u64,
+    /// The position the game started from, in board notation.
+    pub initial: String,
+    /// Every move, including the ones that were undone and can be redone.
+    pub moves: Vec<SavedMove>,
+    /// The amount of moves on the board.
+    pub ply: usize,
+    /// The comment before the first move.
+    pub comment: Option<String>,
+    /// The record headers of an imported game.
+    pub headers: Option<Vec<(String, String)>>,
+    pub seats: Vec<SavedSeat>,
+    pub listed: bool,
+    pub variant: String,
+    pub clock: Option<SavedClock>,
+    /// The team that ran out of time.
+    pub flagged: Option<u32>,
+    pub rated: bool,
+    /// Whether every team had a player when the first move was played.
+    pub seated_at_start: bool,
+    pub computer: Option<SavedComputer>,
+    /// Counts the changes, so pages from before a restart still see the changes after it.
+    pub revision: u64,
+    /// What spectators find the game by, empty for games saved before there was one.
+    pub watch_token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedMove {
+    /// The move like `d2-e3`.
+    pub text: String,
+    /// The comment after the move.
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedSeat {
+    pub team: u32,
+    pub player: u64,
+    pub name: String,
+}
+
+/// The clocks of a game when it was saved. A running clock continues when the game is loaded,
+/// the time the server was down does not count.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedClock {
+    pub base_ms: u64,
+    pub increment_ms: u64,
+    pub delay_ms: u64,
+    pub remaining_ms: Vec<u64>,
+    pub running: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedComputer {
+    /// The name of the engine, see `EngineKind::from_name`.
+    pub engine: String,
+    pub teams: Vec<u32>,
+    pub time_ms: u64,
+}
+
+/// A position someone wanted to keep, in board notation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SavedPosition {
+    pub id: i64,
+    pub name: String,
+    pub notation: String,
+}
+
+/// The stages of a CSV MFR pipeline, in the order they run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub name: String,
+    pub stages: Vec<PipelineStage>,
+    /// The player id of the browser that saved it, the only one that can change it. None for
+    /// pipelines saved before there were owners.
+    #[serde(skip)]
+    pub owner: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineStage {
+    pub kind: StageKind,
+    /// The body of the function the stage calls, in JavaScript.
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StageKind {
+    Map,
+    Filter,
+    Reduce,
+}
+
+impl StageKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StageKind::Map => "map",
+            StageKind::Filter => "filter",
+            StageKind::Reduce => "reduce",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StageKind> {
+        match name {
+            "map" => Some(StageKind::Map),
+            "filter" => Some(StageKind::Filter),
+            "reduce" => Some(StageKind::Reduce),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps everything in memory, until the server stops.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    contents: Mutex<MemoryContents>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryContents {
+    games: BTreeMap<u64, SavedGame>,
+    positions: BTreeMap<i64, SavedPosition>,
+    next_position: i64,
+    pipelines: BTreeMap<String, Pipeline>,
+    rated_records: Vec<String>,
+}
+
+impl MemoryStore {
+    fn lock(&self) -> MutexGuard<'_, MemoryContents> {
+        self.contents
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Store for MemoryStore {
+    fn save_game(&self, game: &SavedGame) -> Result<(), StoreError> {
+        self.lock().games.insert(game.id, game.clone());
+        Ok(())
+    }
+
+    fn games(&self) -> Result<Vec<SavedGame>, StoreError> {
+        Ok(self.lock().games.values().cloned().collect())
+    }
+
+    fn delete_game(&self, id: u64) -> Result<(), StoreError> {
+        self.lock().games.remove(&id);
+        Ok(())
+    }
+
+    fn save_position(&self, name: &str, notation: &str) -> Result<SavedPosition, StoreError> {
+        let mut contents = self.lock();
+        contents.next_position += 1;
+        let position = SavedPosition {
+            id: contents.next_position,
+            name: name.to_string(),
+            notation: notation.to_string(),
+        };
+        contents.positions.insert(position.id, position.clone());
+
+        Ok(position)
+    }
+
+    fn positions(&self) -> Result<Vec<SavedPosition>, StoreError> {
+        Ok(self.lock().positions.values().cloned().collect())
+    }
+
+    fn position(&self, id: i64) -> Result<Option<SavedPosition>, StoreError> {
+        Ok(self.lock().positions.get(&id).cloned())
+    }
+
+    fn delete_position(&self, id: i64) -> Result<bool, StoreError> {
+        Ok(self.lock().positions.remove(&id).is_some())
+    }
+
+    fn save_pipeline(&self, pipeline: &Pipeline) -> Result<(), StoreError> {
+        self.lock()
+            .pipelines
+            .insert(pipeline.name.clone(), pipeline.clone());
+        Ok(())
+    }
+
+    fn pipelines(&self) -> Result<Vec<Pipeline>, StoreError> {
+        Ok(self.lock().pipelines.values().cloned().collect())
+    }
+
+    fn pipeline(&self, name: &str) -> Result<Option<Pipeline>, StoreError> {
+        Ok(self.lock().pipelines.get(name).cloned())
+    }
+
+    fn delete_pipeline(&self, name: &str) -> Result<bool, StoreError> {
+        Ok(self.lock().pipelines.remove(name).is_some())
+    }
+
+    fn save_rated_record(&self, record: &str) -> Result<(), StoreError> {
+        self.lock().rated_records.push(record.to_string());
+        Ok(())
+    }
+
+    fn rated_records(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self.lock().rated_records.clone())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::storage::{
+        MemoryStore, Pipeline, PipelineStage, SavedClock, SavedComputer, SavedGame, SavedMove,
+        SavedSeat, StageKind, Store,
+    };
+
+    pub fn saved_game(id: u64) -> SavedGame {
+        SavedGame {
+            id,
+            initial: "3 a0a21/a2a32/5/2b3b2/1b2b0 a 2".into(),
+            moves: vec![
+                SavedMove {
+                    text: "a2-a3".into(),
+                    comment: Some("Opens the centre.".into()),
+                },
+                SavedMove {
+                    text: "e4-d3".into(),
+                    comment: None,
+                },
+            ],
+            ply: 1,
+            comment: None,
+            headers: None,
+            seats: vec![SavedSeat {
+                team: 1,
+                player: u64::MAX - 8,
+                name: "Bob".into(),
+            }],
+            listed: true,
+            variant: "Standard".into(),
+            clock: Some(SavedClock {
+                base_ms: 60_000,
+                increment_ms: 2_000,
+                delay_ms: 0,
+                remaining_ms: vec![61_000, 59_500],
+                running: Some(1),
+            }),
+            flagged: None,
+            rated: false,
+            computer: Some(SavedComputer {
+                engine: "mcts".into(),
+                teams: vec![0],
+                time_ms: 1_000,
+            }),
+            revision: 4,
+            watch_token: "qwertyuiopasdfghjklzxcvb".into(),
+            seated_at_start: true,
+        }
+    }
+
+    /// Checks what every store has to do, for each implementation.
+    pub fn check_store(store: &dyn Store) {
+        let mut game = saved_game(3);
+        store.save_game(&saved_game(5)).unwrap();
+        store.save_game(&game).unwrap();
+        game.ply = 2;
+        game.moves[1].comment = Some("{Braces}".into());
+        game.headers = Some(vec![("Event".into(), "Club night".into())]);
+        game.clock = None;
+        game.computer = None;
+        store.save_game(&game).unwrap();
+        assert_eq!(vec![game, saved_game(5)], store.games().unwrap());
+        store.delete_game(5).unwrap();
+        assert_eq!(1, store.games().unwrap().len());
+
+        let first = store.save_position("Start", "2 a01/3/b01 a 2").unwrap();
+        let second = store.save_position("Endgame", "2 a0/3/b0 b 2").unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(Some(second.clone()), store.position(second.id).unwrap());
+        assert_eq!(
+            vec![first.clone(), second.clone()],
+            store.positions().unwrap()
+        );
+        assert!(store.delete_position(first.id).unwrap());
+        assert!(!store.delete_position(first.id).unwrap());
+        assert_eq!(vec![second], store.positions().unwrap());
+
+        let mut pipeline = Pipeline {
+            name: "Totals".into(),
+            stages: vec![
+                PipelineStage {
+                    kind: StageKind::Filter,
+                    body: "return index > 0;".into(),
+                },
+                PipelineStage {
+                    kind: StageKind::Map,
+                    body: "return Number(value[1]);".into(),
+                },
+            ],
+            owner: Some(u64::MAX - 3),
+        };
+        store.save_pipeline(&pipeline).unwrap();
+        pipeline.stages.push(PipelineStage {
+            kind: StageKind::Reduce,
+            body: "return previousValue + currentValue;".into(),
+        });
+        store.save_pipeline(&pipeline).unwrap();
+        assert_eq!(Some(pipeline.clone()), store.pipeline("Totals").unwrap());
+        assert_eq!(vec![pipeline], store.pipelines().unwrap());
+        assert!(store.delete_pipeline("Totals").unwrap());
+        assert_eq!(None, store.pipeline("Totals").unwrap());
+
+        assert!(store.rated_records().unwrap().is_empty());
+        store.save_rated_record("[Result \"1-0\"]").unwrap();
+        store.save_rated_record("[Result \"1/2-1/2\"]").unwrap();
+        assert_eq!(
+            vec!["[Result \"1-0\"]", "[Result \"1/2-1/2\"]"],
+            store.rated_records().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(&MemoryStore::default());
+    }
+
+    #[test]
+    fn test_stage_kind_names() {
+        for kind in [StageKind::Map, StageKind::Filter, StageKind::Reduce] {
+            assert_eq!(Some(kind), StageKind::from_name(kind.name()));
+        }
+        assert_eq!(None, StageKind::from_name("sort"));
+    }
+}
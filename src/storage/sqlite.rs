@@ -0,0 +1,490 @@
+//! The `Store` in a SQLite database file.
+//!
+//! `MIGRATIONS` builds the schema step by step. The database remembers how many steps it went
+//! through in `user_version`, and opening it runs the steps that are new since then. A step is
+//! never changed once released, changes to the schema go into a new step.
+
+use std::sync::{Mutex, MutexGuard};
+
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+
+use super::{
+    Pipeline, PipelineStage, SavedGame, SavedMove, SavedPosition, SavedSeat, StageKind, Store,
+    StoreError,
+};
+
+const MIGRATIONS: &[&str] = &[
+    // 1: Hexy games with their moves and seats, saved positions and pipelines.
+    "
+    CREATE TABLE games (
+        id INTEGER PRIMARY KEY,
+        initial TEXT NOT NULL,
+        ply INTEGER NOT NULL,
+        comment TEXT,
+        headers TEXT,
+        listed INTEGER NOT NULL,
+        variant TEXT NOT NULL,
+        clock TEXT,
+        flagged INTEGER,
+        rated INTEGER NOT NULL,
+        computer TEXT,
+        revision INTEGER NOT NULL
+    );
+    CREATE TABLE moves (
+        game INTEGER NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+        ply INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        comment TEXT,
+        PRIMARY KEY (game, ply)
+    );
+    CREATE TABLE seats (
+        game INTEGER NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+        team INTEGER NOT NULL,
+        player INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (game, team)
+    );
+    CREATE TABLE positions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        notation TEXT NOT NULL
+    );
+    CREATE TABLE pipelines (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE pipeline_stages (
+        pipeline INTEGER NOT NULL REFERENCES pipelines (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        body TEXT NOT NULL,
+        PRIMARY KEY (pipeline, position)
+    );
+    ",
+    // 2: The token spectators find a game by.
+    "
+    ALTER TABLE games ADD COLUMN watch_token TEXT NOT NULL DEFAULT '';
+    ",
+    // 3: Whether every team had a player when the first move was played, for the ratings.
+    "
+    ALTER TABLE games ADD COLUMN seated_at_start INTEGER NOT NULL DEFAULT 0;
+    ",
+    // 4: The records of rated games, which the ratings are computed from.
+    "
+    CREATE TABLE rated_records (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        record TEXT NOT NULL
+    );
+    ",
+    // 5: The player who saved a pipeline, the only one who can change it.
+    "
+    ALTER TABLE pipelines ADD COLUMN owner INTEGER;
+    ",
+];
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(error: rusqlite::Error) -> StoreError {
+        StoreError(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(error: serde_json::Error) -> StoreError {
+        StoreError(error.to_string())
+    }
+}
+
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens the database file, creating it when it does not exist yet.
+    pub fn open(path: &str) -> Result<SqliteStore, StoreError> {
+        SqliteStore::new(Connection::open(path)?)
+    }
+
+    /// A database that is gone when the store is dropped.
+    pub fn open_in_memory() -> Result<SqliteStore, StoreError> {
+        SqliteStore::new(Connection::open_in_memory()?)
+    }
+
+    fn new(mut connection: Connection) -> Result<SqliteStore, StoreError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+
+        Ok(SqliteStore {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Runs the migrations the database has not seen yet, each in a transaction of its own.
+fn migrate(connection: &mut Connection) -> Result<(), StoreError> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(StoreError(format!(
+            "the database is at version {version}, this server only knows up to version {}",
+            MIGRATIONS.len()
+        )));
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", i + 1)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+fn json<T: serde::Serialize>(value: &Option<T>) -> Result<Option<String>, StoreError> {
+    value
+        .as_ref()
+        .map(|value| serde_json::to_string(value))
+        .transpose()
+        .map_err(StoreError::from)
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(
+    text: Option<String>,
+) -> Result<Option<T>, StoreError> {
+    text.map(|text| serde_json::from_str(&text))
+        .transpose()
+        .map_err(StoreError::from)
+}
+
+fn read_position(row: &Row) -> rusqlite::Result<SavedPosition> {
+    Ok(SavedPosition {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        notation: row.get(2)?,
+    })
+}
+
+fn read_game(transaction: &Transaction, row: &Row) -> Result<SavedGame, StoreError> {
+    let id: u64 = row.get(0)?;
+
+    let mut moves =
+        transaction.prepare("SELECT text, comment FROM moves WHERE game = ? ORDER BY ply")?;
+    let moves = moves
+        .query_map([id], |row| {
+            Ok(SavedMove {
+                text: row.get(0)?,
+                comment: row.get(1)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut seats =
+        transaction.prepare("SELECT team, player, name FROM seats WHERE game = ? ORDER BY team")?;
+    let seats = seats
+        .query_map([id], |row| {
+            Ok(SavedSeat {
+                team: row.get(0)?,
+                // Player ids use all 64 bits, SQLite integers are signed.
+                player: row.get::<_, i64>(1)? as u64,
+                name: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(SavedGame {
+        id,
+        initial: row.get(1)?,
+        moves,
+        ply: row.get(2)?,
+        comment: row.get(3)?,
+        headers: from_json(row.get(4)?)?,
+        seats,
+        listed: row.get(5)?,
+        variant: row.get(6)?,
+        clock: from_json(row.get(7)?)?,
+        flagged: row.get(8)?,
+        rated: row.get(9)?,
+        computer: from_json(row.get(10)?)?,
+        revision: row.get(11)?,
+        watch_token: row.get(12)?,
+        seated_at_start: row.get(13)?,
+    })
+}
+
+fn read_pipeline(
+    transaction: &Transaction,
+    id: i64,
+    name: String,
+    owner: Option<i64>,
+) -> Result<Pipeline, StoreError> {
+    let mut stages = transaction
+        .prepare("SELECT kind, body FROM pipeline_stages WHERE pipeline = ? ORDER BY position")?;
+    let stages = stages
+        .query_map([id], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+        .map(|stage| {
+            let (kind, body) = stage?;
+            let kind = StageKind::from_name(&kind)
+                .ok_or_else(|| StoreError(format!("unknown stage kind {kind}")))?;
+            Ok(PipelineStage { kind, body })
+        })
+        .collect::<Result<_, StoreError>>()?;
+
+    Ok(Pipeline {
+        name,
+        stages,
+        owner: owner.map(|owner| owner as u64),
+    })
+}
+
+const GAME_COLUMNS: &str = "id, initial, ply, comment, headers, listed, variant, clock, flagged, \
+                            rated, computer, revision, watch_token, seated_at_start";
+
+impl Store for SqliteStore {
+    fn save_game(&self, game: &SavedGame) -> Result<(), StoreError> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction()?;
+
+        transaction.execute("DELETE FROM games WHERE id = ?", [game.id])?;
+        transaction.execute(
+            &format!(
+                "INSERT INTO games ({GAME_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            ),
+            params![
+                game.id,
+                game.initial,
+                game.ply,
+                game.comment,
+                json(&game.headers)?,
+                game.listed,
+                game.variant,
+                json(&game.clock)?,
+                game.flagged,
+                game.rated,
+                json(&game.computer)?,
+                game.revision,
+                game.watch_token,
+                game.seated_at_start,
+            ],
+        )?;
+        for (ply, mv) in game.moves.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO moves (game, ply, text, comment) VALUES (?, ?, ?, ?)",
+                params![game.id, ply + 1, mv.text, mv.comment],
+            )?;
+        }
+        for seat in &game.seats {
+            transaction.execute(
+                "INSERT INTO seats (game, team, player, name) VALUES (?, ?, ?, ?)",
+                params![game.id, seat.team, seat.player as i64, seat.name],
+            )?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn games(&self) -> Result<Vec<SavedGame>, StoreError> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction()?;
+
+        let games = {
+            let mut statement =
+                transaction.prepare(&format!("SELECT {GAME_COLUMNS} FROM games ORDER BY id"))?;
+            let mut rows = statement.query([])?;
+            let mut games = Vec::new();
+            while let Some(row) = rows.next()? {
+                games.push(read_game(&transaction, row)?);
+            }
+            games
+        };
+
+        transaction.commit()?;
+        Ok(games)
+    }
+
+    fn delete_game(&self, id: u64) -> Result<(), StoreError> {
+        self.lock()
+            .execute("DELETE FROM games WHERE id = ?", [id])?;
+        Ok(())
+    }
+
+    fn save_position(&self, name: &str, notation: &str) -> Result<SavedPosition, StoreError> {
+        let connection = self.lock();
+        connection.execute(
+            "INSERT INTO positions (name, notation) VALUES (?, ?)",
+            [name, notation],
+        )?;
+
+        Ok(SavedPosition {
+            id: connection.last_insert_rowid(),
+            name: name.to_string(),
+            notation: notation.to_string(),
+        })
+    }
+
+    fn positions(&self) -> Result<Vec<SavedPosition>, StoreError> {
+        let connection = self.lock();
+        let mut statement =
+            connection.prepare("SELECT id, name, notation FROM positions ORDER BY id")?;
+        let positions = statement
+            .query_map([], read_position)?
+            .collect::<Result<_, _>>()?;
+
+        Ok(positions)
+    }
+
+    fn position(&self, id: i64) -> Result<Option<SavedPosition>, StoreError> {
+        let position = self
+            .lock()
+            .query_row(
+                "SELECT id, name, notation FROM positions WHERE id = ?",
+                [id],
+                read_position,
+            )
+            .optional()?;
+
+        Ok(position)
+    }
+
+    fn delete_position(&self, id: i64) -> Result<bool, StoreError> {
+        let deleted = self
+            .lock()
+            .execute("DELETE FROM positions WHERE id = ?", [id])?;
+        Ok(deleted > 0)
+    }
+
+    fn save_pipeline(&self, pipeline: &Pipeline) -> Result<(), StoreError> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction()?;
+
+        transaction.execute("DELETE FROM pipelines WHERE name = ?", [&pipeline.name])?;
+        transaction.execute(
+            "INSERT INTO pipelines (name, owner) VALUES (?, ?)",
+            params![pipeline.name, pipeline.owner.map(|owner| owner as i64)],
+        )?;
+        let id = transaction.last_insert_rowid();
+        for (position, stage) in pipeline.stages.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO pipeline_stages (pipeline, position, kind, body) VALUES (?, ?, ?, ?)",
+                params![id, position, stage.kind.name(), stage.body],
+            )?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn pipelines(&self) -> Result<Vec<Pipeline>, StoreError> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction()?;
+
+        let pipelines = {
+            let mut statement =
+                transaction.prepare("SELECT id, name, owner FROM pipelines ORDER BY name")?;
+            let rows = statement
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<Result<Vec<(i64, String, Option<i64>)>, _>>()?;
+            rows.into_iter()
+                .map(|(id, name, owner)| read_pipeline(&transaction, id, name, owner))
+                .collect::<Result<_, _>>()?
+        };
+
+        transaction.commit()?;
+        Ok(pipelines)
+    }
+
+    fn pipeline(&self, name: &str) -> Result<Option<Pipeline>, StoreError> {
+        let mut connection = self.lock();
+        let transaction = connection.transaction()?;
+
+        let row: Option<(i64, Option<i64>)> = transaction
+            .query_row(
+                "SELECT id, owner FROM pipelines WHERE name = ?",
+                [name],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let pipeline = row
+            .map(|(id, owner)| read_pipeline(&transaction, id, name.to_string(), owner))
+            .transpose()?;
+
+        transaction.commit()?;
+        Ok(pipeline)
+    }
+
+    fn delete_pipeline(&self, name: &str) -> Result<bool, StoreError> {
+        let deleted = self
+            .lock()
+            .execute("DELETE FROM pipelines WHERE name = ?", [name])?;
+        Ok(deleted > 0)
+    }
+
+    fn save_rated_record(&self, record: &str) -> Result<(), StoreError> {
+        self.lock()
+            .execute("INSERT INTO rated_records (record) VALUES (?)", [record])?;
+        Ok(())
+    }
+
+    fn rated_records(&self) -> Result<Vec<String>, StoreError> {
+        let connection = self.lock();
+        let mut statement = connection.prepare("SELECT record FROM rated_records ORDER BY id")?;
+        let records = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::storage::sqlite::{migrate, SqliteStore, MIGRATIONS};
+    use crate::storage::tests::{check_store, saved_game};
+    use crate::storage::Store;
+
+    #[test]
+    fn test_sqlite_store() {
+        check_store(&SqliteStore::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_migrations() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        // Running them again changes nothing.
+        migrate(&mut connection).unwrap();
+
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(MIGRATIONS.len(), version);
+
+        connection
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(migrate(&mut connection).is_err());
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = std::env::temp_dir().join(format!("yground-test-{}.sqlite", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        SqliteStore::open(path)
+            .unwrap()
+            .save_game(&saved_game(1))
+            .unwrap();
+        let games = SqliteStore::open(path).unwrap().games().unwrap();
+        std::fs::remove_file(path).ok();
+
+        assert_eq!(vec![saved_game(1)], games);
+    }
+}
@@ -3,10 +3,7 @@ use tera::Context;
 use crate::TEMPLATES;
 
 pub fn get_template(path: &str, context: Option<Context>) -> Option<String> {
-    TEMPLATES
-        .render(path, &context.unwrap_or_default())
-        .ok()
-        .map(|value| (value))
+    TEMPLATES.render(path, &context.unwrap_or_default()).ok()
 }
 
 pub fn hex_count(size: u32) -> u32 {